@@ -0,0 +1,429 @@
+//! Non-interactive annotation edits for scripts and agents.
+
+use crate::atomic;
+use crate::cli::AnnotateOptions;
+use crate::file;
+use crate::git;
+use crate::language;
+use crate::config;
+use crate::models::{Annotation, Line, AUTHOR_KEY};
+use crate::sidecar;
+use similar::TextDiff;
+use std::fs;
+
+/// Sets, replaces or (with `None`) removes the annotation thread on a
+/// 1-based editor line, or with `reply` appends `annotation` to the thread.
+/// Returns the previous thread.
+pub fn set_annotation(
+    lines: &mut [Line],
+    line_number: usize,
+    annotation: Option<Annotation>,
+    reply: bool,
+) -> Result<Vec<Annotation>, String> {
+    let total = lines.len();
+    let line = line_number
+        .checked_sub(1)
+        .and_then(|index| lines.get_mut(index))
+        .ok_or_else(|| format!("Line {} is out of range (file has {} lines)", line_number, total))?;
+
+    let annotation = match annotation {
+        Some(annotation) if annotation.text.trim().is_empty() => {
+            return Err("Annotation text is empty (use --remove to delete)".to_string());
+        }
+        Some(annotation) => Some(Annotation {
+            text: annotation.text.trim().lines().map(str::trim).collect::<Vec<_>>().join("\n"),
+            ..annotation
+        }),
+        None => None,
+    };
+
+    let previous = line.annotations.clone();
+    match annotation {
+        Some(annotation) if reply => line.annotations.push(annotation),
+        annotation => line.annotations = annotation.into_iter().collect(),
+    }
+    Ok(previous)
+}
+
+/// Marks every entry of the thread on a 1-based editor line resolved, or
+/// open again.
+pub fn set_status(lines: &mut [Line], line_number: usize, resolved: bool) -> Result<(), String> {
+    let thread = line_number
+        .checked_sub(1)
+        .and_then(|index| lines.get_mut(index))
+        .map(|line| &mut line.annotations)
+        .filter(|thread| !thread.is_empty())
+        .ok_or_else(|| format!("Line {} has no annotation to resolve or reopen", line_number))?;
+    thread.iter_mut().for_each(|annotation| annotation.set_resolved(resolved));
+    Ok(())
+}
+
+/// Applies an `nanot annotate` request and returns what to print: the
+/// unified diff for a dry run, otherwise a one-line summary. Files that keep
+/// their annotations in a sidecar file have that file updated instead.
+pub fn run(options: &AnnotateOptions) -> Result<String, String> {
+    let path = &options.file;
+    let original = fs::read_to_string(path).map_err(|e| format!("Cannot read '{}': {}", path, e))?;
+    let lang_comment = language::detect(path, &original).comment;
+    let inline = file::parse_file(&original, &lang_comment);
+    let sidecar = sidecar::storage_reason(path, &original, &inline, false).is_some();
+    let (mut lines, orphans) = if sidecar {
+        sidecar::load(path, &original).map_err(|e| e.to_string())?
+    } else {
+        (inline, Vec::new())
+    };
+
+    // Block comments can't hold their closing token, so store the text the
+    // way it will read back
+    let mut annotation = options.text.as_deref().map(|text| {
+        let text = if sidecar { text.to_string() } else { file::saved_annotation_text(text, &lang_comment) };
+        let mut annotation = Annotation::new(text);
+        annotation.set_severity(options.severity);
+        annotation.set_span(options.span.unwrap_or(1));
+        annotation.set(AUTHOR_KEY, options.author.as_deref());
+        annotation
+    });
+    let holds_attributes = sidecar || config::holds_attributes(config::marker_tag());
+    let has_attributes = annotation.as_ref().is_some_and(|a| !a.attributes.is_empty()) || options.resolve.is_some();
+    if has_attributes && !holds_attributes {
+        return Err(config::no_attributes_error(config::marker_tag()));
+    }
+    // New text is stamped like an entry added in the editor; a tag without
+    // room for attributes goes without, as it does there
+    if let Some(annotation) = annotation.as_mut()
+        && holds_attributes
+        && !options.no_stamp
+    {
+        let author = options.author.clone().or_else(|| git::user_name(path));
+        annotation.stamp(author.as_deref());
+    }
+    if let Some(span) = options.span
+        && options.line + span - 1 > lines.len()
+    {
+        return Err(format!(
+            "Lines {}-{} are out of range (file has {} lines)",
+            options.line,
+            options.line + span - 1,
+            lines.len()
+        ));
+    }
+    let status_only = annotation.is_none() && options.resolve.is_some();
+    let previous = if status_only {
+        Vec::new()
+    } else {
+        set_annotation(&mut lines, options.line, annotation, options.reply)?
+    };
+    if let Some(resolved) = options.resolve {
+        set_status(&mut lines, options.line, resolved)?;
+    }
+
+    let (target, current, updated) = if sidecar {
+        let sidecar_path = sidecar::path_for(path);
+        let current = fs::read_to_string(&sidecar_path).unwrap_or_default();
+        let updated = sidecar::render(path, &lines, &orphans).unwrap_or_default();
+        (sidecar::display_path(&sidecar_path), current, updated)
+    } else {
+        let updated = file::render_file(&lines, &lang_comment, file::TextFormat::detect(&original));
+        // Markers the parser would not read back (e.g. inside a Markdown
+        // code fence) would silently turn into plain text, so refuse them
+        // up front
+        if file::parse_file(&updated, &lang_comment) != lines {
+            return Err(format!(
+                "An annotation can't be placed on line {} (it would not be read back)",
+                options.line
+            ));
+        }
+        (path.clone(), original, updated)
+    };
+
+    if options.dry_run {
+        return Ok(TextDiff::from_lines(&current, &updated)
+            .unified_diff()
+            .header(&target, &target)
+            .to_string());
+    }
+
+    if updated != current {
+        let written = if sidecar { sidecar::save(path, &lines, &orphans) } else { atomic::write(path, &updated) };
+        written.map_err(|e| format!("Cannot write '{}': {}", target, e))?;
+    }
+
+    let action = match (&options.text, previous.is_empty()) {
+        _ if status_only => "",
+        (Some(_), false) if options.reply => "replied",
+        (Some(_), true) => "added",
+        (Some(_), false) => "replaced",
+        (None, false) => "removed",
+        (None, true) => "unchanged (no annotation)",
+    };
+    let status = match options.resolve {
+        Some(true) => "resolved",
+        Some(false) => "reopened",
+        None => "",
+    };
+    let summary = [action, status].into_iter().filter(|s| !s.is_empty()).collect::<Vec<_>>().join(" and ");
+    let location = match options.span {
+        Some(span) => format!("{}-{}", options.line, options.line + span - 1),
+        None => options.line.to_string(),
+    };
+    Ok(format!("{}:{}: annotation {}\n", path, location, summary))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Severity;
+    use tempfile::TempDir;
+
+    fn options(path: &str, line: usize, text: Option<&str>, dry_run: bool) -> AnnotateOptions {
+        AnnotateOptions {
+            file: path.to_string(),
+            line,
+            span: None,
+            text: text.map(|s| s.to_string()),
+            reply: false,
+            severity: None,
+            author: None,
+            no_stamp: true,
+            resolve: None,
+            dry_run,
+        }
+    }
+
+    fn write_temp(dir: &TempDir, name: &str, content: &str) -> String {
+        let path = dir.path().join(name);
+        fs::write(&path, content).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_set_annotation_replaces_and_removes() {
+        let mut lines = file::parse_file("a\nb\n", "//");
+        assert_eq!(set_annotation(&mut lines, 2, Some("  check  ".into()), false), Ok(vec![]));
+        assert_eq!(lines[1].annotations, vec!["check"]);
+
+        assert_eq!(set_annotation(&mut lines, 2, Some("again".into()), false), Ok(vec!["check".into()]));
+        assert_eq!(set_annotation(&mut lines, 2, Some("reply".into()), true), Ok(vec!["again".into()]));
+        assert_eq!(lines[1].annotations, vec!["again", "reply"]);
+        assert_eq!(set_annotation(&mut lines, 2, None, false).unwrap().len(), 2);
+        assert!(lines[1].annotations.is_empty());
+    }
+
+    #[test]
+    fn test_set_annotation_rejects_bad_input() {
+        let mut lines = file::parse_file("a\n", "//");
+        assert!(set_annotation(&mut lines, 0, Some("x".into()), false).is_err());
+        assert!(set_annotation(&mut lines, 2, Some("x".into()), false).is_err());
+        assert!(set_annotation(&mut lines, 1, Some("   ".into()), true).is_err());
+    }
+
+    #[test]
+    fn test_run_writes_multi_line_annotation() {
+        let dir = TempDir::new().unwrap();
+        let path = write_temp(&dir, "a.py", "x = 1\n");
+
+        run(&options(&path, 1, Some("two\n  lines  \n"), false)).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# [ANNOTATION] two\n# [ANNOTATION]+ lines\nx = 1\n"
+        );
+    }
+
+    #[test]
+    fn test_run_reply_appends_to_thread() {
+        let dir = TempDir::new().unwrap();
+        let path = write_temp(&dir, "a.rs", "// [ANNOTATION] why?\nfn a() {}\n");
+
+        let reply = AnnotateOptions {
+            reply: true,
+            ..options(&path, 1, Some("because"), false)
+        };
+        let output = run(&reply).unwrap();
+        assert_eq!(output, format!("{}:1: annotation replied\n", path));
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "// [ANNOTATION] why?\n// [ANNOTATION] because\nfn a() {}\n"
+        );
+    }
+
+    #[test]
+    fn test_run_writes_metadata() {
+        let dir = TempDir::new().unwrap();
+        let path = write_temp(&dir, "a.py", "x = 1\n");
+
+        let options = AnnotateOptions {
+            severity: Some(Severity::Blocker),
+            author: Some("Jane Doe".to_string()),
+            ..options(&path, 1, Some("unsafe"), false)
+        };
+        run(&options).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# [ANNOTATION sev=blocker by=\"Jane Doe\"] unsafe\nx = 1\n"
+        );
+    }
+
+    #[test]
+    fn test_run_stamps_new_text_like_the_editor() {
+        let dir = TempDir::new().unwrap();
+        let path = write_temp(&dir, "a.py", "x = 1\n");
+
+        let options = AnnotateOptions {
+            severity: Some(Severity::Major),
+            author: Some("Jane Doe".to_string()),
+            no_stamp: false,
+            ..options(&path, 1, Some("unsafe"), false)
+        };
+        run(&options).unwrap();
+
+        // The same entry made in the editor: severity from the picker, then
+        // the stamp
+        let mut expected = Annotation::new("unsafe");
+        expected.set_severity(Some(Severity::Major));
+        expected.set_span(1);
+        expected.stamp(Some("Jane Doe"));
+        let lines = file::parse_file(&fs::read_to_string(&path).unwrap(), "#");
+        assert_eq!(lines[0].annotations, vec![expected]);
+        assert_eq!(lines[0].annotations[0].date(), Some(crate::models::today().as_str()));
+    }
+
+    #[test]
+    fn test_run_resolves_and_reopens_thread() {
+        let dir = TempDir::new().unwrap();
+        let path = write_temp(&dir, "a.py", "# [ANNOTATION sev=nit] why?\n# [ANNOTATION] because\nx = 1\ny = 2\n");
+
+        let resolve = AnnotateOptions {
+            resolve: Some(true),
+            ..options(&path, 1, None, false)
+        };
+        assert_eq!(run(&resolve).unwrap(), format!("{}:1: annotation resolved\n", path));
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# [ANNOTATION sev=nit status=resolved] why?\n# [ANNOTATION status=resolved] because\nx = 1\ny = 2\n"
+        );
+
+        let reply_and_reopen = AnnotateOptions {
+            reply: true,
+            resolve: Some(false),
+            ..options(&path, 1, Some("not yet"), false)
+        };
+        assert_eq!(run(&reply_and_reopen).unwrap(), format!("{}:1: annotation replied and reopened\n", path));
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# [ANNOTATION sev=nit] why?\n# [ANNOTATION] because\n# [ANNOTATION] not yet\nx = 1\ny = 2\n"
+        );
+
+        let nothing_there = AnnotateOptions {
+            resolve: Some(true),
+            ..options(&path, 2, None, false)
+        };
+        assert!(run(&nothing_there).is_err());
+    }
+
+    #[test]
+    fn test_run_annotates_range() {
+        let dir = TempDir::new().unwrap();
+        let path = write_temp(&dir, "a.py", "x = 1\ny = 2\nz = 3\n");
+
+        let range = AnnotateOptions {
+            span: Some(2),
+            ..options(&path, 2, Some("merge these"), false)
+        };
+        assert_eq!(run(&range).unwrap(), format!("{}:2-3: annotation added\n", path));
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "x = 1\n# [ANNOTATION lines=2] merge these\ny = 2\nz = 3\n"
+        );
+
+        let too_long = AnnotateOptions {
+            span: Some(3),
+            ..options(&path, 2, Some("merge these"), false)
+        };
+        assert!(run(&too_long).is_err());
+    }
+
+    #[test]
+    fn test_run_writes_sidecar_for_json() {
+        let dir = TempDir::new().unwrap();
+        let path = write_temp(&dir, "data.json", "{\n  \"a\": 1\n}\n");
+
+        let dry_run = run(&options(&path, 2, Some("rename"), true)).unwrap();
+        assert!(dry_run.lines().any(|line| line.starts_with('+') && line.contains("\"text\": \"rename\"")));
+        assert!(!sidecar::path_for(&path).exists());
+
+        let options = AnnotateOptions {
+            severity: Some(Severity::Nit),
+            ..options(&path, 2, Some("rename"), false)
+        };
+        assert_eq!(run(&options).unwrap(), format!("{}:2: annotation added\n", path));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{\n  \"a\": 1\n}\n");
+        let (lines, _) = sidecar::load(&path, "{\n  \"a\": 1\n}\n").unwrap();
+        assert_eq!(lines[1].annotations[0].text, "rename");
+        assert_eq!(lines[1].annotations[0].severity(), Some(Severity::Nit));
+    }
+
+    #[test]
+    fn test_run_uses_language_comment_prefix() {
+        let dir = TempDir::new().unwrap();
+        let path = write_temp(&dir, "a.py", "x = 1\ny = 2\n");
+
+        let output = run(&options(&path, 2, Some("rename y"), false)).unwrap();
+        assert_eq!(output, format!("{}:2: annotation added\n", path));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "x = 1\n# [ANNOTATION] rename y\ny = 2\n");
+    }
+
+    #[test]
+    fn test_run_line_numbers_skip_existing_markers() {
+        let dir = TempDir::new().unwrap();
+        let path = write_temp(&dir, "a.rs", "// [ANNOTATION] first\nfn a() {}\nfn b() {}\n");
+
+        run(&options(&path, 2, Some("second"), false)).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "// [ANNOTATION] first\nfn a() {}\n// [ANNOTATION] second\nfn b() {}\n"
+        );
+
+        let output = run(&options(&path, 1, None, false)).unwrap();
+        assert!(output.ends_with("annotation removed\n"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fn a() {}\n// [ANNOTATION] second\nfn b() {}\n");
+    }
+
+    #[test]
+    fn test_run_dry_run_prints_diff_without_writing() {
+        let dir = TempDir::new().unwrap();
+        let path = write_temp(&dir, "a.rs", "fn a() {}\n");
+
+        let output = run(&options(&path, 1, Some("note"), true)).unwrap();
+        assert!(output.contains("+// [ANNOTATION] note\n"));
+        assert!(output.contains(" fn a() {}\n"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fn a() {}\n");
+    }
+
+    #[test]
+    fn test_run_refuses_markdown_code_fence() {
+        let dir = TempDir::new().unwrap();
+        let content = "Intro\n```\ncode\n```\n";
+        let path = write_temp(&dir, "notes.md", content);
+
+        assert!(run(&options(&path, 3, Some("inside fence"), false)).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), content);
+        assert!(run(&options(&path, 1, Some("outside fence"), false)).is_ok());
+    }
+
+    #[test]
+    fn test_run_html_block_comment() {
+        let dir = TempDir::new().unwrap();
+        let path = write_temp(&dir, "index.html", "<p>Hi</p>\n");
+
+        run(&options(&path, 1, Some("shorter -- maybe"), false)).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "<!-- [ANNOTATION] shorter - - maybe -->\n<p>Hi</p>\n"
+        );
+    }
+
+    #[test]
+    fn test_run_missing_file() {
+        assert!(run(&options("/nonexistent/nanot.rs", 1, Some("x"), false)).is_err());
+    }
+}
@@ -0,0 +1,282 @@
+//! Crash-safe file writes.
+//!
+//! Content is written to a temporary file next to the target, flushed to
+//! disk and renamed over the target, and the rename is flushed too, so a
+//! crash or a full disk leaves either the old file or the new one, never a
+//! truncated mix. The temporary file has the old file's permissions before
+//! anything is written to it, so private files are never readable by others
+//! along the way, and a symlink is followed so the file it points to is
+//! replaced rather than the link itself.
+
+use std::fs::{self, File, OpenOptions, Permissions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// Appended to a file name for its backup, as nano's `-B` does
+pub const BACKUP_SUFFIX: &str = "~";
+
+/// Symlinks followed before giving up, matching Linux's `ELOOP` limit
+const MAX_SYMLINKS: usize = 40;
+
+/// Temporary names tried before giving up on finding a free one
+const MAX_TEMP_ATTEMPTS: usize = 100;
+
+/// Replaces the file at `path` with `contents` atomically.
+pub fn write(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    write_with_backup(path, contents, false)
+}
+
+/// Replaces the file at `path` with `contents` atomically, first copying
+/// the current file to `<path>~` when `backup` is set and it exists.
+pub fn write_with_backup(path: impl AsRef<Path>, contents: impl AsRef<[u8]>, backup: bool) -> io::Result<()> {
+    replace(path.as_ref(), backup, |file| file.write_all(contents.as_ref()))
+}
+
+/// Where the backup of `path` goes.
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(BACKUP_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// Writes a temporary file with `write` and renames it over the file `path`
+/// resolves to. The temporary file is removed if any step fails, leaving
+/// the target as it was.
+fn replace(path: &Path, backup: bool, write: impl FnOnce(&mut File) -> io::Result<()>) -> io::Result<()> {
+    let target = resolve_symlinks(path)?;
+    let permissions = fs::metadata(&target).ok().map(|metadata| metadata.permissions());
+    let (mut file, temp_path) = create_temp(&target, permissions.as_ref())?;
+    let result = (|| {
+        if let Some(permissions) = permissions {
+            // Creation is subject to the umask, so set the exact mode before
+            // any content goes in
+            file.set_permissions(permissions)?;
+            if backup {
+                fs::copy(&target, backup_path(&target))?;
+            }
+        }
+        write(&mut file)?;
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&temp_path, &target)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result?;
+    sync_dir(parent_dir(&target))
+}
+
+/// Flushes a directory so a rename inside it survives a crash.
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+/// Directories can't be opened as files here; the rename is all there is.
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Directory holding `path`, `.` for a bare file name.
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+/// Follows `path` through any symlinks to the file they point to. The
+/// final target doesn't need to exist yet.
+fn resolve_symlinks(path: &Path) -> io::Result<PathBuf> {
+    let mut path = path.to_path_buf();
+    for _ in 0..MAX_SYMLINKS {
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                let link = fs::read_link(&path)?;
+                // Relative links are relative to the directory holding them
+                path = match path.parent() {
+                    Some(dir) => dir.join(link),
+                    None => link,
+                };
+            }
+            _ => return Ok(path),
+        }
+    }
+    Err(io::Error::other(format!("Too many levels of symbolic links: {}", path.display())))
+}
+
+/// Creates a new hidden file in the same directory as `target`, so the
+/// final rename never crosses file systems. On Unix it is created with the
+/// mode of `permissions` (the target's), so it is never more readable than
+/// the file it replaces.
+fn create_temp(target: &Path, permissions: Option<&Permissions>) -> io::Result<(File, PathBuf)> {
+    let dir = parent_dir(target);
+    let name = target.file_name().unwrap_or_default().to_string_lossy();
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    if let Some(permissions) = permissions {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(permissions.mode() & 0o7777);
+    }
+    #[cfg(not(unix))]
+    let _ = permissions;
+    for attempt in 0..MAX_TEMP_ATTEMPTS {
+        let temp_path = dir.join(format!(".{}.{}-{}.nanot-tmp", name, process::id(), attempt));
+        match options.open(&temp_path) {
+            Ok(file) => return Ok((file, temp_path)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("No free temporary file name next to {}", target.display()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Names in `dir`, sorted, so leftover temporary files show up.
+    fn entries(dir: &TempDir) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_write_replaces_and_creates_files() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("a.rs");
+        write(&path, "fn a() {}\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fn a() {}\n");
+
+        write(&path, "fn b() {}\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fn b() {}\n");
+        assert_eq!(entries(&dir), vec!["a.rs"]);
+    }
+
+    #[test]
+    fn test_failed_write_keeps_original() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("a.rs");
+        fs::write(&path, "fn a() {}\n").unwrap();
+
+        // The disk fills up halfway through the new content
+        let result = replace(&path, false, |file| {
+            file.write_all(b"fn a")?;
+            Err(io::Error::new(io::ErrorKind::StorageFull, "No space left on device"))
+        });
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::StorageFull);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fn a() {}\n");
+        assert_eq!(entries(&dir), vec!["a.rs"]);
+    }
+
+    #[test]
+    fn test_failed_rename_removes_temp_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("target");
+        fs::create_dir(&path).unwrap();
+        fs::write(path.join("keep"), "x").unwrap();
+
+        assert!(write(&path, "content").is_err());
+        assert!(path.join("keep").is_file());
+        assert_eq!(entries(&dir), vec!["target"]);
+    }
+
+    #[test]
+    fn test_backup_keeps_previous_content() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("a.rs");
+
+        // Nothing to back up for a new file
+        write_with_backup(&path, "one\n", true).unwrap();
+        assert_eq!(entries(&dir), vec!["a.rs"]);
+
+        write_with_backup(&path, "two\n", true).unwrap();
+        write_with_backup(&path, "three\n", true).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "three\n");
+        assert_eq!(fs::read_to_string(backup_path(&path)).unwrap(), "two\n");
+        assert_eq!(entries(&dir), vec!["a.rs", "a.rs~"]);
+    }
+
+    #[test]
+    fn test_failed_backup_aborts_save() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("a.rs");
+        fs::write(&path, "old\n").unwrap();
+        fs::create_dir(backup_path(&path)).unwrap();
+
+        assert!(write_with_backup(&path, "new\n", true).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old\n");
+        assert_eq!(entries(&dir), vec!["a.rs", "a.rs~"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("run.sh");
+        fs::write(&path, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o750)).unwrap();
+
+        write_with_backup(&path, "#!/bin/sh\n# [ANNOTATION] quote\n", true).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o750);
+        assert_eq!(fs::metadata(backup_path(&path)).unwrap().permissions().mode() & 0o777, 0o750);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_private_file_is_never_readable_by_others() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("secrets.env");
+        fs::write(&path, "TOKEN=1\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+
+        replace(&path, false, |file| {
+            assert_eq!(file.metadata()?.permissions().mode() & 0o777, 0o600);
+            file.write_all(b"# [ANNOTATION] rotate\nTOKEN=1\n")
+        })
+        .unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_follows_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("real")).unwrap();
+        let real = dir.path().join("real/a.rs");
+        fs::write(&real, "fn a() {}\n").unwrap();
+        let link = dir.path().join("link.rs");
+        symlink("real/a.rs", &link).unwrap();
+        let chained = dir.path().join("chained.rs");
+        symlink(&link, &chained).unwrap();
+
+        write_with_backup(&chained, "// [ANNOTATION] hi\nfn a() {}\n", true).unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert!(fs::symlink_metadata(&chained).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(&real).unwrap(), "// [ANNOTATION] hi\nfn a() {}\n");
+        assert_eq!(fs::read_to_string(backup_path(&real)).unwrap(), "fn a() {}\n");
+        assert_eq!(entries(&dir), vec!["chained.rs", "link.rs", "real"]);
+
+        let looped = dir.path().join("loop");
+        symlink("loop", &looped).unwrap();
+        assert!(write(&looped, "x").is_err());
+    }
+}
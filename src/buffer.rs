@@ -0,0 +1,468 @@
+//! Per-file editing state for sessions with several open files.
+
+use crate::atomic;
+use crate::file::{self, TextFormat};
+use crate::git::{self, ChangedFile, FileChange};
+use crate::language;
+use crate::models::{Action, Line, ViewMode};
+use crate::navigation::center_scroll;
+use crate::patch::{self, Patch};
+use crate::sidecar::{self, Sidecar};
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+
+/// State of one open file.
+///
+/// The active buffer's state lives directly on `Editor`; every other buffer
+/// is parked here until the user switches to it.
+#[derive(Default)]
+pub struct Buffer {
+    pub lines: Vec<Line>,
+    pub file_path: Option<String>,
+    pub lang_comment: String,
+    pub cursor_line: usize,
+    pub scroll_offset: usize,
+    pub view_mode: ViewMode,
+    pub history: Vec<Action>,
+    pub history_index: usize,
+    /// Hash of content at last save (for detecting unsaved changes)
+    pub saved_content_hash: u64,
+    /// Why this buffer can't be annotated or saved (e.g. a deleted file)
+    pub read_only: Option<String>,
+    /// Git status of the file when opened from a review queue
+    pub file_change: Option<FileChange>,
+    /// Enter diff view the first time this buffer becomes active
+    pub pending_diff: bool,
+    /// Set when reviewing a unified diff rather than a source file
+    pub patch: Option<Patch>,
+    /// Syntect syntax the buffer is highlighted with
+    pub syntax: Option<String>,
+    /// Line endings, final newline and BOM to save with
+    pub format: TextFormat,
+    /// How annotations are kept in a sidecar file instead of inline
+    /// markers; `None` when they are written into the file
+    pub sidecar: Option<Sidecar>,
+}
+
+impl Buffer {
+    /// Reads and parses a file into a fresh buffer.
+    #[cfg(test)]
+    pub fn open(file_path: String) -> io::Result<Self> {
+        Buffer::open_with_sidecar(file_path, false)
+    }
+
+    /// Reads a file into a fresh buffer, taking its annotations from a
+    /// sidecar file when inline markers would be unsafe for it, when it
+    /// already has one, or when `sidecar` asks for one.
+    pub fn open_with_sidecar(file_path: String, sidecar: bool) -> io::Result<Self> {
+        let content = fs::read_to_string(&file_path)?;
+        if patch::is_patch_path(&file_path) {
+            return Ok(Buffer::open_patch(&content, file_path));
+        }
+        let language = language::detect(&file_path, &content);
+        let inline = file::parse_file(&content, &language.comment);
+        let (lines, sidecar) = match sidecar::storage_reason(&file_path, &content, &inline, sidecar) {
+            Some(reason) => {
+                let (lines, orphans) = sidecar::load(&file_path, &content)?;
+                (lines, Some(Sidecar { reason, orphans }))
+            }
+            None => (inline, None),
+        };
+        let saved_content_hash = compute_content_hash(&lines);
+
+        Ok(Buffer {
+            lines,
+            file_path: Some(file_path),
+            lang_comment: language.comment,
+            syntax: language.syntax,
+            format: TextFormat::detect(&content),
+            saved_content_hash,
+            sidecar,
+            ..Default::default()
+        })
+    }
+
+    /// Opens a unified diff for review. Annotations already in the patch are
+    /// picked up, and saving writes to `save_path`: an annotated patch, or
+    /// JSON when the path ends in `.json`.
+    pub fn open_patch(content: &str, save_path: String) -> Self {
+        let lines = file::parse_file(content, patch::PATCH_COMMENT);
+        let saved_content_hash = compute_content_hash(&lines);
+        let mut buffer = Buffer {
+            patch: Some(Patch::parse(&lines)),
+            lines,
+            lang_comment: patch::PATCH_COMMENT.to_string(),
+            syntax: Some(patch::PATCH_SYNTAX.to_string()),
+            format: patch::text_format(content),
+            saved_content_hash,
+            ..Default::default()
+        };
+        buffer.set_save_path(save_path);
+        buffer
+    }
+
+    /// Redirects where a patch review is saved.
+    pub fn set_save_path(&mut self, save_path: String) {
+        if let Some(patch) = &mut self.patch {
+            patch.json_output = save_path.ends_with(".json");
+        }
+        self.file_path = Some(save_path);
+    }
+
+    /// Opens an entry of a `--changed` review queue.
+    ///
+    /// Modified and added files open normally and start in diff view.
+    /// Deleted and binary files are kept in the queue as read-only buffers
+    /// so they are visible without being annotatable. Deleted files show
+    /// their content at `base` (or HEAD when reviewing uncommitted changes).
+    /// `sidecar` chooses the annotation storage as for `open_with_sidecar`.
+    pub fn open_changed(file: &ChangedFile, base: Option<&str>, sidecar: bool) -> io::Result<Self> {
+        let abs_path = file.path();
+        let file_path = std::env::current_dir()
+            .ok()
+            .and_then(|cwd| abs_path.strip_prefix(cwd).ok().map(|p| p.to_path_buf()))
+            .unwrap_or_else(|| abs_path.clone())
+            .to_string_lossy()
+            .to_string();
+
+        if file.binary {
+            return Ok(Buffer {
+                lines: file::parse_file(BINARY_PLACEHOLDER, ""),
+                file_path: Some(file_path),
+                read_only: Some(BINARY_READ_ONLY.to_string()),
+                file_change: Some(file.change),
+                ..Default::default()
+            });
+        }
+
+        if file.change == FileChange::Deleted {
+            let content = git::get_changed_file_base_content(file, base)
+                .map_err(|e| io::Error::other(e.to_string()))?;
+            let language = language::detect(&file_path, &content);
+            let lines = file::parse_file(&content, &language.comment);
+            let saved_content_hash = compute_content_hash(&lines);
+            return Ok(Buffer {
+                lines,
+                file_path: Some(file_path),
+                lang_comment: language.comment,
+                syntax: language.syntax,
+                saved_content_hash,
+                read_only: Some(DELETED_READ_ONLY.to_string()),
+                file_change: Some(file.change),
+                ..Default::default()
+            });
+        }
+
+        let mut buffer = Buffer::open_with_sidecar(file_path, sidecar)?;
+        buffer.file_change = Some(file.change);
+        buffer.pending_diff = true;
+        Ok(buffer)
+    }
+
+    /// Places the cursor on a 1-based line, clamped to the file, and scrolls
+    /// so that line sits in the middle of the view.
+    pub fn go_to_line(&mut self, line: usize, visible_height: usize, width: usize) {
+        self.cursor_line = line.saturating_sub(1).min(self.lines.len().saturating_sub(1));
+        self.scroll_offset = center_scroll(self.cursor_line, visible_height, &self.lines, width);
+    }
+
+    /// Check if content has been modified since last save
+    pub fn is_modified(&self) -> bool {
+        compute_content_hash(&self.lines) != self.saved_content_hash
+    }
+
+    /// Writes the buffer to disk with markers tagged `marker` and marks it as
+    /// saved, first copying the file to `<file>~` when `backup` is set.
+    /// Read-only buffers are never written.
+    pub fn save(&mut self, marker: &str, backup: bool) -> io::Result<()> {
+        if self.read_only.is_some() {
+            return Ok(());
+        }
+        if let Some(ref path) = self.file_path {
+            write_file(
+                path,
+                &self.lines,
+                &self.lang_comment,
+                self.format,
+                self.patch.as_ref(),
+                self.sidecar.as_ref(),
+                marker,
+                backup,
+            )?;
+            self.saved_content_hash = compute_content_hash(&self.lines);
+        }
+        Ok(())
+    }
+
+    /// File name shown in the status bar and buffer picker.
+    pub fn display_name(&self) -> String {
+        display_name(&self.file_path, self.file_change)
+    }
+}
+
+/// Content shown for binary files in a review queue
+const BINARY_PLACEHOLDER: &str = "[Binary file]";
+
+/// Status message for binary files in a review queue
+pub const BINARY_READ_ONLY: &str = "Binary file - cannot be annotated";
+
+/// Status message for deleted files in a review queue
+pub const DELETED_READ_ONLY: &str = "File was deleted - cannot be annotated";
+
+/// Status message for files opened with `--view`
+pub const VIEW_READ_ONLY: &str = "Read-only view (--view) - annotations can't be edited";
+
+/// Writes a buffer's lines to disk. Patch reviews headed for a `.json` path
+/// are written as JSON, and with `sidecar` only the sidecar file is
+/// written; everything else keeps annotations as markers tagged `marker`
+/// and the file's original `format`. Files are replaced atomically, and `backup` copies
+/// the file about to be replaced to `<path>~` (sidecar files, which only
+/// nanot writes, are not backed up).
+#[allow(clippy::too_many_arguments)]
+pub fn write_file(
+    path: &str,
+    lines: &[Line],
+    lang_comment: &str,
+    format: TextFormat,
+    patch: Option<&Patch>,
+    sidecar: Option<&Sidecar>,
+    marker: &str,
+    backup: bool,
+) -> io::Result<()> {
+    match (patch, sidecar) {
+        (Some(patch), _) if patch.json_output => {
+            atomic::write_with_backup(path, patch::render_json(patch, lines), backup)
+        }
+        (_, Some(sidecar)) => sidecar::save(path, lines, &sidecar.orphans),
+        _ => atomic::write_with_backup(path, file::render_file_tagged(lines, lang_comment, marker, format), backup),
+    }
+}
+
+/// Compute a hash of the content (lines + annotations)
+pub fn compute_content_hash(lines: &[Line]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for line in lines {
+        line.content.hash(&mut hasher);
+        line.annotations.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Path as given on the command line, or a placeholder for unnamed buffers.
+/// Review queue entries are tagged with their git status.
+pub fn display_name(file_path: &Option<String>, file_change: Option<FileChange>) -> String {
+    let name = file_path.clone().unwrap_or_else(|| "[No Name]".to_string());
+    match file_change {
+        Some(FileChange::Added) => format!("{} (added)", name),
+        Some(FileChange::Deleted) => format!("{} (deleted)", name),
+        Some(FileChange::Modified) | None => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DEFAULT_MARKER;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_open_parses_annotations() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("a.rs");
+        fs::write(&path, "// [ANNOTATION] note\nfn a() {}\n").unwrap();
+
+        let buffer = Buffer::open(path.to_string_lossy().to_string()).unwrap();
+        assert_eq!(buffer.lines.len(), 1);
+        assert_eq!(buffer.lines[0].annotations, vec!["note"]);
+        assert_eq!(buffer.lang_comment, "//");
+        assert!(!buffer.is_modified());
+    }
+
+    #[test]
+    fn test_save_clears_modified() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("b.py");
+        fs::write(&path, "x = 1\n").unwrap();
+
+        let mut buffer = Buffer::open(path.to_string_lossy().to_string()).unwrap();
+        buffer.lines[0].annotations = vec!["check".into()];
+        assert!(buffer.is_modified());
+
+        buffer.save(DEFAULT_MARKER, false).unwrap();
+        assert!(!buffer.is_modified());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "# [ANNOTATION] check\nx = 1\n");
+    }
+
+    #[test]
+    fn test_save_keeps_line_endings_and_bom() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("w.py");
+        fs::write(&path, "\u{feff}x = 1\r\ny = 2").unwrap();
+
+        let mut buffer = Buffer::open(path.to_string_lossy().to_string()).unwrap();
+        buffer.lines[1].annotations = vec!["check".into()];
+        buffer.save(DEFAULT_MARKER, false).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "\u{feff}x = 1\r\n# [ANNOTATION] check\r\ny = 2"
+        );
+
+        buffer.lines[1].annotations.clear();
+        buffer.save(DEFAULT_MARKER, false).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "\u{feff}x = 1\r\ny = 2");
+    }
+
+    #[test]
+    fn test_unsafe_files_save_to_sidecar() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("data.json");
+        fs::write(&path, "{\n  \"a\": 1\n}\n").unwrap();
+        let path = path.to_string_lossy().to_string();
+
+        let mut buffer = Buffer::open(path.clone()).unwrap();
+        assert!(buffer.sidecar.is_some());
+        buffer.lines[1].annotations = vec!["rename".into()];
+        buffer.save(DEFAULT_MARKER, false).unwrap();
+        assert!(!buffer.is_modified());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{\n  \"a\": 1\n}\n");
+        assert!(sidecar::path_for(&path).is_file());
+
+        let reopened = Buffer::open(path).unwrap();
+        assert_eq!(reopened.lines[1].annotations, vec!["rename"]);
+    }
+
+    #[test]
+    fn test_open_with_sidecar_on_request() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("a.rs");
+        fs::write(&path, "fn a() {}\n").unwrap();
+        let path = path.to_string_lossy().to_string();
+
+        assert!(Buffer::open(path.clone()).unwrap().sidecar.is_none());
+        let mut buffer = Buffer::open_with_sidecar(path.clone(), true).unwrap();
+        buffer.lines[0].annotations = vec!["check".into()];
+        buffer.save(DEFAULT_MARKER, false).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fn a() {}\n");
+        assert_eq!(Buffer::open(path).unwrap().lines[0].annotations, vec!["check"]);
+    }
+
+    #[test]
+    fn test_display_name_unnamed() {
+        assert_eq!(display_name(&None, None), "[No Name]");
+        assert_eq!(display_name(&Some("src/a.rs".to_string()), None), "src/a.rs");
+    }
+
+    #[test]
+    fn test_display_name_tags_review_status() {
+        let path = Some("src/a.rs".to_string());
+        assert_eq!(display_name(&path, Some(FileChange::Added)), "src/a.rs (added)");
+        assert_eq!(display_name(&path, Some(FileChange::Deleted)), "src/a.rs (deleted)");
+        assert_eq!(display_name(&path, Some(FileChange::Modified)), "src/a.rs");
+    }
+
+    #[test]
+    fn test_go_to_line_centers_and_clamps() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("long.txt");
+        let content: String = (1..=100).map(|i| format!("line {}\n", i)).collect();
+        fs::write(&path, content).unwrap();
+        let mut buffer = Buffer::open(path.to_string_lossy().to_string()).unwrap();
+
+        buffer.go_to_line(42, 20, 80);
+        assert_eq!(buffer.cursor_line, 41);
+        assert_eq!(buffer.scroll_offset, 31);
+
+        buffer.go_to_line(500, 20, 80);
+        assert_eq!(buffer.cursor_line, 99);
+
+        buffer.go_to_line(0, 20, 80);
+        assert_eq!(buffer.cursor_line, 0);
+        assert_eq!(buffer.scroll_offset, 0);
+    }
+
+    #[test]
+    fn test_open_patch_file_saves_annotated_patch() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("fix.patch");
+        fs::write(&path, "--- a/x.rs\n+++ b/x.rs\n@@ -1 +1 @@\n-old\n+new\n").unwrap();
+
+        let mut buffer = Buffer::open(path.to_string_lossy().to_string()).unwrap();
+        assert!(buffer.patch.is_some());
+        assert_eq!(buffer.lang_comment, "#");
+
+        buffer.lines[4].annotations = vec!["better".into()];
+        buffer.save(DEFAULT_MARKER, false).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "--- a/x.rs\n+++ b/x.rs\n@@ -1 +1 @@\n-old\n# [ANNOTATION] better\n+new\n"
+        );
+    }
+
+    #[test]
+    fn test_open_patch_saves_json() {
+        let dir = TempDir::new().unwrap();
+        let output = dir.path().join("review.json");
+
+        let mut buffer = Buffer::open_patch(
+            "--- a/x.rs\n+++ b/x.rs\n@@ -1 +1 @@\n-old\n+new\n",
+            output.to_string_lossy().to_string(),
+        );
+        buffer.lines[4].annotations = vec!["better".into()];
+        buffer.save(DEFAULT_MARKER, false).unwrap();
+
+        let value: serde_json::Value = serde_json::from_str(&fs::read_to_string(&output).unwrap()).unwrap();
+        assert_eq!(value["x.rs"]["1"][0]["change"], "added");
+        assert_eq!(value["x.rs"]["1"][0]["annotation"], "better");
+    }
+
+    fn changed_file(dir: &TempDir, name: &str, change: FileChange, binary: bool) -> ChangedFile {
+        ChangedFile {
+            repo_root: dir.path().to_path_buf(),
+            relative_path: name.to_string(),
+            change,
+            binary,
+        }
+    }
+
+    #[test]
+    fn test_open_changed_modified_starts_in_diff() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("a.rs"), "fn a() {}\n").unwrap();
+
+        let buffer = Buffer::open_changed(&changed_file(&dir, "a.rs", FileChange::Modified, false), None, false).unwrap();
+        assert!(buffer.pending_diff);
+        assert!(buffer.read_only.is_none());
+        assert_eq!(buffer.lines[0].content, "fn a() {}");
+    }
+
+    #[test]
+    fn test_open_changed_with_sidecar() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("a.rs"), "fn a() {}\n").unwrap();
+        let file = changed_file(&dir, "a.rs", FileChange::Modified, false);
+
+        assert!(Buffer::open_changed(&file, None, false).unwrap().sidecar.is_none());
+        let mut buffer = Buffer::open_changed(&file, None, true).unwrap();
+        assert!(buffer.sidecar.is_some());
+        buffer.lines[0].annotations = vec!["check".into()];
+        buffer.save(DEFAULT_MARKER, false).unwrap();
+        assert_eq!(fs::read_to_string(dir.path().join("a.rs")).unwrap(), "fn a() {}\n");
+    }
+
+    #[test]
+    fn test_open_changed_binary_is_read_only() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("img.png"), [0u8, 1, 2]).unwrap();
+
+        let mut buffer = Buffer::open_changed(&changed_file(&dir, "img.png", FileChange::Added, true), None, false).unwrap();
+        assert_eq!(buffer.read_only.as_deref(), Some(BINARY_READ_ONLY));
+        assert!(!buffer.pending_diff);
+
+        // Saving a read-only buffer never touches the file
+        buffer.lines[0].annotations = vec!["note".into()];
+        buffer.save(DEFAULT_MARKER, false).unwrap();
+        assert_eq!(fs::read(dir.path().join("img.png")).unwrap(), vec![0u8, 1, 2]);
+    }
+}
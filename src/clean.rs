@@ -0,0 +1,190 @@
+//! Removing every annotation from files headlessly (`nanot clean`).
+
+use crate::cli::CleanOptions;
+use crate::file;
+use crate::scan::{self, ScannedFile};
+use crate::sidecar;
+use similar::TextDiff;
+use std::fs;
+use std::io;
+
+/// What `nanot clean` printed and whether any annotation was found.
+pub struct CleanOutcome {
+    pub output: String,
+    /// True if at least one annotation existed under the scanned paths
+    pub found_annotations: bool,
+}
+
+/// Removes annotations under `options.paths`.
+///
+/// Only lines the parser recognises as markers are removed, so annotated code
+/// lines and markers inside Markdown code fences are left untouched. Files
+/// that keep their annotations in a sidecar file lose that file instead. With
+/// `--check` the annotations are listed instead, and with `--dry-run` the
+/// would-be diff is printed; neither writes anything.
+pub fn run(options: &CleanOptions) -> io::Result<CleanOutcome> {
+    let files = scan::scan(&options.paths)?;
+    let found_annotations = !files.is_empty();
+
+    let output = if options.check {
+        check_output(&files)
+    } else {
+        let mut output = String::new();
+        for file in &files {
+            output.push_str(&clean_file(file, options.dry_run)?);
+        }
+        output
+    };
+
+    Ok(CleanOutcome { output, found_annotations })
+}
+
+fn check_output(files: &[ScannedFile]) -> String {
+    if files.is_empty() {
+        return String::new();
+    }
+    let count: usize = files
+        .iter()
+        .map(|f| f.annotations().count() + f.orphans.iter().map(|orphan| orphan.thread.len()).sum::<usize>())
+        .sum();
+    format!(
+        "{}{} annotation(s) remaining in {} file(s)\n",
+        scan::format_list(files),
+        count,
+        files.len()
+    )
+}
+
+fn clean_file(scanned: &ScannedFile, dry_run: bool) -> io::Result<String> {
+    let lang_comment = &scanned.lang_comment;
+
+    let mut lines = scanned.lines.clone();
+    let mut removed: usize = scanned.orphans.iter().map(|orphan| orphan.thread.len()).sum();
+    for line in &mut lines {
+        removed += std::mem::take(&mut line.annotations).len();
+    }
+
+    if scanned.sidecar {
+        let sidecar_path = sidecar::path_for(&scanned.path);
+        if dry_run {
+            let header = sidecar::display_path(&sidecar_path);
+            let current = fs::read_to_string(&sidecar_path)?;
+            return Ok(TextDiff::from_lines(current.as_str(), "")
+                .unified_diff()
+                .header(&header, &header)
+                .to_string());
+        }
+        sidecar::save(&scanned.path, &lines, &[])?;
+        return Ok(format!("{}: removed {} annotation(s)\n", scanned.path, removed));
+    }
+
+    let cleaned = file::render_file(&lines, lang_comment, scanned.format);
+
+    if dry_run {
+        let current = fs::read_to_string(&scanned.path)?;
+        return Ok(TextDiff::from_lines(&current, &cleaned)
+            .unified_diff()
+            .header(&scanned.path, &scanned.path)
+            .to_string());
+    }
+
+    file::save_file(&scanned.path, &lines, lang_comment, scanned.format, false)?;
+    Ok(format!("{}: removed {} annotation(s)\n", scanned.path, removed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn options(dir: &TempDir, dry_run: bool, check: bool) -> CleanOptions {
+        CleanOptions {
+            paths: vec![dir.path().to_string_lossy().to_string()],
+            dry_run,
+            check,
+        }
+    }
+
+    fn create_files(dir: &TempDir) {
+        fs::write(dir.path().join("a.rs"), "// [ANNOTATION] fix\nfn a() {}\nfn b() {}\n").unwrap();
+        fs::write(
+            dir.path().join("notes.md"),
+            "[ANNOTATION] reword\nIntro\n```\n[ANNOTATION] example\n```\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("clean.py"), "x = 1\n").unwrap();
+    }
+
+    #[test]
+    fn test_clean_removes_only_marker_lines() {
+        let dir = TempDir::new().unwrap();
+        create_files(&dir);
+
+        let outcome = run(&options(&dir, false, false)).unwrap();
+        assert!(outcome.found_annotations);
+        assert_eq!(fs::read_to_string(dir.path().join("a.rs")).unwrap(), "fn a() {}\nfn b() {}\n");
+        // The marker inside the code fence is example text, not an annotation
+        assert_eq!(
+            fs::read_to_string(dir.path().join("notes.md")).unwrap(),
+            "Intro\n```\n[ANNOTATION] example\n```\n"
+        );
+        assert_eq!(fs::read_to_string(dir.path().join("clean.py")).unwrap(), "x = 1\n");
+
+        let outcome = run(&options(&dir, false, true)).unwrap();
+        assert!(!outcome.found_annotations);
+        assert!(outcome.output.is_empty());
+    }
+
+    #[test]
+    fn test_clean_removes_sidecar_files() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("data.json").to_string_lossy().to_string();
+        fs::write(&path, "{}\n").unwrap();
+        let mut lines = file::parse_plain("{}\n");
+        lines[0].annotations.push("empty?".into());
+        sidecar::save(&path, &lines, &[]).unwrap();
+
+        let outcome = run(&options(&dir, true, false)).unwrap();
+        assert!(outcome.output.lines().any(|line| line.starts_with('-') && line.contains("\"text\": \"empty?\"")));
+        assert!(sidecar::path_for(&path).is_file());
+
+        let outcome = run(&options(&dir, false, false)).unwrap();
+        assert_eq!(outcome.output, format!("{}: removed 1 annotation(s)\n", path));
+        assert!(!sidecar::path_for(&path).exists());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{}\n");
+    }
+
+    #[test]
+    fn test_clean_keeps_crlf_and_missing_final_newline() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("win.rs");
+        fs::write(&path, "// [ANNOTATION] fix\r\nfn a() {}\r\nfn b() {}").unwrap();
+
+        run(&options(&dir, false, false)).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fn a() {}\r\nfn b() {}");
+    }
+
+    #[test]
+    fn test_clean_dry_run_leaves_files() {
+        let dir = TempDir::new().unwrap();
+        create_files(&dir);
+
+        let outcome = run(&options(&dir, true, false)).unwrap();
+        assert!(outcome.output.contains("-// [ANNOTATION] fix\n"));
+        assert!(outcome.output.contains("-[ANNOTATION] reword\n"));
+        assert!(!outcome.output.contains("-[ANNOTATION] example"));
+        assert!(fs::read_to_string(dir.path().join("a.rs")).unwrap().contains("[ANNOTATION] fix"));
+    }
+
+    #[test]
+    fn test_clean_check_lists_remaining() {
+        let dir = TempDir::new().unwrap();
+        create_files(&dir);
+
+        let outcome = run(&options(&dir, false, true)).unwrap();
+        assert!(outcome.found_annotations);
+        assert!(outcome.output.contains("a.rs:1: fix\n"));
+        assert!(outcome.output.ends_with("2 annotation(s) remaining in 2 file(s)\n"));
+        assert!(fs::read_to_string(dir.path().join("a.rs")).unwrap().contains("[ANNOTATION] fix"));
+    }
+}
@@ -0,0 +1,924 @@
+//! Command line argument parsing.
+
+use crate::export::ExportFormat;
+use crate::models::Severity;
+use std::path::Path;
+
+/// What `nanot` was asked to do.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// Print usage and keyboard shortcuts
+    Help,
+    /// Open files in the interactive editor
+    Edit(EditOptions),
+    /// Print every annotation under the given paths
+    List(ListOptions),
+    /// Write annotations under the given paths in a structured format
+    Export(ExportOptions),
+    /// Set or remove the annotation on one line of a file
+    Annotate(AnnotateOptions),
+    /// Remove every annotation under the given paths
+    Clean(CleanOptions),
+    /// Rewrite annotation markers from one tag to another
+    Migrate(MigrateOptions),
+}
+
+/// Options for an interactive editing session.
+#[derive(Debug, Default, PartialEq)]
+pub struct EditOptions {
+    pub files: Vec<FileArg>,
+    /// Open every file changed according to git status as a review queue
+    pub changed: bool,
+    /// Revision to diff against (via its merge base with HEAD) instead of HEAD
+    pub base: Option<String>,
+    /// Where to save a reviewed patch (`.json` saves annotations as JSON)
+    pub output: Option<String>,
+    /// Where to write the JSON session report on exit
+    pub report: Option<String>,
+    /// Open every file read-only: no annotation edits and no saving
+    pub view: bool,
+    /// Keep the annotations of the named files in sidecar files
+    pub sidecar: bool,
+    /// Copy each file to `<file>~` before saving over it
+    pub backup: bool,
+}
+
+/// A file to open, optionally at a given line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileArg {
+    pub path: String,
+    /// 1-based line to place the cursor on
+    pub line: Option<usize>,
+}
+
+impl FileArg {
+    fn new(path: &str, line: Option<usize>) -> Self {
+        FileArg { path: path.to_string(), line }
+    }
+}
+
+/// Options for `nanot list`.
+#[derive(Debug, Default, PartialEq)]
+pub struct ListOptions {
+    /// Files and directories to scan (the current directory if none given)
+    pub paths: Vec<String>,
+}
+
+/// Options for `nanot export`.
+#[derive(Debug, PartialEq)]
+pub struct ExportOptions {
+    /// Files and directories to scan (the current directory if none given)
+    pub paths: Vec<String>,
+    pub format: ExportFormat,
+    /// Write to this file instead of stdout
+    pub output: Option<String>,
+}
+
+/// Options for `nanot annotate`.
+#[derive(Debug, PartialEq)]
+pub struct AnnotateOptions {
+    pub file: String,
+    /// 1-based line number as shown in the editor
+    pub line: usize,
+    /// Number of lines the annotation covers, from a `<file>:<start>-<end>`
+    /// target
+    pub span: Option<usize>,
+    /// New annotation text, or `None` to remove the annotation
+    pub text: Option<String>,
+    /// Append the text to the line's thread instead of replacing it
+    pub reply: bool,
+    /// Severity to write into the marker (`--severity`)
+    pub severity: Option<Severity>,
+    /// Author to write into the marker (`--by`), instead of git `user.name`
+    pub author: Option<String>,
+    /// Don't record the author and date on new text (`--no-stamp`)
+    pub no_stamp: bool,
+    /// Mark every entry of the thread resolved (`--resolve`) or open again
+    /// (`--reopen`). Without text only the status changes.
+    pub resolve: Option<bool>,
+    /// Print the resulting diff instead of writing the file
+    pub dry_run: bool,
+}
+
+/// Options for `nanot clean`.
+#[derive(Debug, Default, PartialEq)]
+pub struct CleanOptions {
+    /// Files and directories to clean (the current directory if none given)
+    pub paths: Vec<String>,
+    /// Print the diff instead of writing files
+    pub dry_run: bool,
+    /// Only report remaining annotations; fail if there are any
+    pub check: bool,
+}
+
+/// Options for `nanot migrate`.
+#[derive(Debug, Default, PartialEq)]
+pub struct MigrateOptions {
+    /// Files and directories to migrate (the current directory if none given)
+    pub paths: Vec<String>,
+    /// Tag the existing markers use
+    pub from: String,
+    /// Tag to rewrite them to (the configured tag if not given)
+    pub to: Option<String>,
+    /// Print the diff instead of writing files
+    pub dry_run: bool,
+}
+
+/// Removes the global `--marker TAG` option (valid before any subcommand
+/// argument and before `--`) and returns its value.
+pub fn take_marker_option(args: &mut Vec<String>) -> Result<Option<String>, String> {
+    let mut marker = None;
+    let mut i = 0;
+    while i < args.len() {
+        let arg = args[i].as_str();
+        if arg == "--" {
+            break;
+        }
+        if arg == "--marker" {
+            if i + 1 >= args.len() {
+                return Err("Option '--marker' requires a tag".to_string());
+            }
+            marker = Some(args.remove(i + 1));
+            args.remove(i);
+        } else if let Some(value) = arg.strip_prefix("--marker=") {
+            marker = Some(value.to_string());
+            args.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+    Ok(marker)
+}
+
+/// Parses arguments (without the program name) into a command.
+pub fn parse_args(args: &[String]) -> Result<Command, String> {
+    match args.first().map(String::as_str) {
+        Some("list") => parse_list_args(&args[1..]),
+        Some("export") => parse_export_args(&args[1..]),
+        Some("annotate") => parse_annotate_args(&args[1..]),
+        Some("clean") => parse_clean_args(&args[1..]),
+        Some("migrate") => parse_migrate_args(&args[1..]),
+        _ => parse_edit_args(args),
+    }
+}
+
+fn parse_list_args(args: &[String]) -> Result<Command, String> {
+    let mut options = ListOptions::default();
+    let mut only_paths = false;
+
+    for arg in args {
+        if only_paths {
+            options.paths.push(arg.clone());
+            continue;
+        }
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--" => only_paths = true,
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return Err(format!("Unknown option '{}'", arg));
+            }
+            _ => options.paths.push(arg.clone()),
+        }
+    }
+
+    if options.paths.is_empty() {
+        options.paths.push(".".to_string());
+    }
+
+    Ok(Command::List(options))
+}
+
+fn parse_clean_args(args: &[String]) -> Result<Command, String> {
+    let mut options = CleanOptions::default();
+    let mut only_paths = false;
+
+    for arg in args {
+        if only_paths {
+            options.paths.push(arg.clone());
+            continue;
+        }
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-n" | "--dry-run" => options.dry_run = true,
+            "--check" => options.check = true,
+            "--" => only_paths = true,
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return Err(format!("Unknown option '{}'", arg));
+            }
+            _ => options.paths.push(arg.clone()),
+        }
+    }
+
+    if options.paths.is_empty() {
+        options.paths.push(".".to_string());
+    }
+
+    Ok(Command::Clean(options))
+}
+
+fn parse_migrate_args(args: &[String]) -> Result<Command, String> {
+    let mut options = MigrateOptions::default();
+    let mut from = None;
+    let mut only_paths = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if only_paths {
+            options.paths.push(arg.clone());
+            continue;
+        }
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-n" | "--dry-run" => options.dry_run = true,
+            "--from" => from = Some(args.next().ok_or("Option '--from' requires a tag")?.clone()),
+            _ if arg.starts_with("--from=") => from = Some(arg["--from=".len()..].to_string()),
+            "--to" => options.to = Some(args.next().ok_or("Option '--to' requires a tag")?.clone()),
+            _ if arg.starts_with("--to=") => options.to = Some(arg["--to=".len()..].to_string()),
+            "--" => only_paths = true,
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return Err(format!("Unknown option '{}'", arg));
+            }
+            _ => options.paths.push(arg.clone()),
+        }
+    }
+
+    options.from = from.ok_or("Option '--from' is required")?;
+    if options.paths.is_empty() {
+        options.paths.push(".".to_string());
+    }
+
+    Ok(Command::Migrate(options))
+}
+
+fn parse_export_args(args: &[String]) -> Result<Command, String> {
+    let mut paths = Vec::new();
+    let mut format = ExportFormat::Json;
+    let mut output = None;
+    let mut only_paths = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if only_paths {
+            paths.push(arg.clone());
+            continue;
+        }
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-f" | "--format" => {
+                let name = args.next().ok_or("Option '--format' requires a value")?;
+                format = parse_format(name)?;
+            }
+            _ if arg.starts_with("--format=") => {
+                format = parse_format(&arg["--format=".len()..])?;
+            }
+            "-o" | "--output" => {
+                let path = args.next().ok_or("Option '--output' requires a file")?;
+                output = Some(path.clone());
+            }
+            _ if arg.starts_with("--output=") => {
+                output = Some(arg["--output=".len()..].to_string());
+            }
+            "--" => only_paths = true,
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return Err(format!("Unknown option '{}'", arg));
+            }
+            _ => paths.push(arg.clone()),
+        }
+    }
+
+    if paths.is_empty() {
+        paths.push(".".to_string());
+    }
+
+    Ok(Command::Export(ExportOptions { paths, format, output }))
+}
+
+fn parse_format(name: &str) -> Result<ExportFormat, String> {
+    ExportFormat::parse(name)
+        .ok_or_else(|| format!("Unknown export format '{}' (expected json, markdown or sarif)", name))
+}
+
+fn parse_severity(name: &str) -> Result<Severity, String> {
+    Severity::parse(name)
+        .ok_or_else(|| format!("Unknown severity '{}' (expected blocker, major, minor or nit)", name))
+}
+
+fn parse_annotate_args(args: &[String]) -> Result<Command, String> {
+    let mut positional = Vec::new();
+    let mut dry_run = false;
+    let mut remove = false;
+    let mut reply = false;
+    let mut severity = None;
+    let mut author = None;
+    let mut no_stamp = false;
+    let mut resolve = None;
+    let mut only_positional = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if only_positional {
+            positional.push(arg.clone());
+            continue;
+        }
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-n" | "--dry-run" => dry_run = true,
+            "-d" | "--remove" => remove = true,
+            "-r" | "--reply" => reply = true,
+            "--no-stamp" => no_stamp = true,
+            "--resolve" => resolve = Some(true),
+            "--reopen" => resolve = Some(false),
+            "-s" | "--severity" => {
+                let name = args.next().ok_or("Option '--severity' requires a value")?;
+                severity = Some(parse_severity(name)?);
+            }
+            _ if arg.starts_with("--severity=") => {
+                severity = Some(parse_severity(&arg["--severity=".len()..])?);
+            }
+            "--by" => {
+                let name = args.next().ok_or("Option '--by' requires a name")?;
+                author = Some(name.clone());
+            }
+            _ if arg.starts_with("--by=") => {
+                author = Some(arg["--by=".len()..].to_string());
+            }
+            "--" => only_positional = true,
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return Err(format!("Unknown option '{}'", arg));
+            }
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    let Some((target, words)) = positional.split_first() else {
+        return Err("Usage: nanot annotate <file>:<line> <text>".to_string());
+    };
+    let (file, line, span) = parse_file_range(target)
+        .map(|(file, start, end)| (file, start, Some(end - start + 1)))
+        .or_else(|| parse_file_line(target).map(|(file, line)| (file, line, None)))
+        .ok_or_else(|| format!("Expected <file>:<line> or <file>:<start>-<end>, got '{}'", target))?;
+
+    if remove && (reply || severity.is_some() || author.is_some() || resolve.is_some()) {
+        return Err("--remove can't be combined with --reply, --severity, --by, --resolve or --reopen".to_string());
+    }
+    let text = match (remove, words.is_empty()) {
+        (true, true) => None,
+        (true, false) => return Err("--remove does not take annotation text".to_string()),
+        (false, true) if resolve.is_some() && !reply && severity.is_none() && author.is_none() => None,
+        (false, true) => return Err("Missing annotation text (use --remove to delete)".to_string()),
+        (false, false) => Some(words.join(" ")),
+    };
+    if span.is_some() && text.is_none() {
+        return Err("A line range needs annotation text (--remove and status changes take one line)".to_string());
+    }
+
+    Ok(Command::Annotate(AnnotateOptions {
+        file,
+        line,
+        span,
+        text,
+        reply,
+        severity,
+        author,
+        no_stamp,
+        resolve,
+        dry_run,
+    }))
+}
+
+/// Splits `path:42` (or compiler-style `path:42:7`) into the path and a
+/// 1-based line number. The column is accepted but not used.
+fn parse_file_line(target: &str) -> Option<(String, usize)> {
+    let (rest, last) = target.rsplit_once(':')?;
+    let (file, line) = match rest.rsplit_once(':') {
+        Some((file, line)) if is_number(line) && is_number(last) => (file, line),
+        _ => (rest, last),
+    };
+    let line = line.parse::<usize>().ok().filter(|&n| n > 0)?;
+    if file.is_empty() {
+        return None;
+    }
+    Some((file.to_string(), line))
+}
+
+/// Splits `path:10-20` into the path and the first and last 1-based line
+/// numbers of the range.
+fn parse_file_range(target: &str) -> Option<(String, usize, usize)> {
+    let (file, range) = target.rsplit_once(':')?;
+    let (start, end) = range.split_once('-')?;
+    if file.is_empty() || !is_number(start) || !is_number(end) {
+        return None;
+    }
+    let start = start.parse::<usize>().ok().filter(|&n| n > 0)?;
+    let end = end.parse::<usize>().ok().filter(|&n| n >= start)?;
+    Some((file.to_string(), start, end))
+}
+
+/// Parses nano-style `+LINE` or `+LINE,COLUMN`. The column is accepted but not used.
+fn parse_plus_line(arg: &str) -> Option<usize> {
+    let position = arg.strip_prefix('+')?;
+    let line = match position.split_once(',') {
+        Some((line, column)) if is_number(column) => line,
+        Some(_) => return None,
+        None => position,
+    };
+    line.parse::<usize>().ok()
+}
+
+fn is_number(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// A file argument, which may carry a `:LINE` suffix. A file that really
+/// exists under the full name (colon included) always wins.
+fn parse_file_arg(arg: &str, line: Option<usize>) -> FileArg {
+    if line.is_none()
+        && !Path::new(arg).exists()
+        && let Some((path, line)) = parse_file_line(arg)
+    {
+        return FileArg::new(&path, Some(line));
+    }
+    FileArg::new(arg, line)
+}
+
+fn parse_edit_args(args: &[String]) -> Result<Command, String> {
+    let mut options = EditOptions::default();
+    let mut only_files = false;
+    // Line from a preceding `+LINE`, applied to the next file
+    let mut pending_line: Option<(&String, usize)> = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if only_files {
+            let line = pending_line.take().map(|(_, line)| line);
+            options.files.push(FileArg::new(arg, line));
+            continue;
+        }
+        if let Some(line) = parse_plus_line(arg) {
+            pending_line = Some((arg, line));
+            continue;
+        }
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--changed" => options.changed = true,
+            "-v" | "--view" => options.view = true,
+            "--sidecar" => options.sidecar = true,
+            "-B" | "--backup" => options.backup = true,
+            "--base" => {
+                let base = args.next().ok_or("Option '--base' requires a revision")?;
+                options.base = Some(base.clone());
+            }
+            _ if arg.starts_with("--base=") => {
+                options.base = Some(arg["--base=".len()..].to_string());
+            }
+            "-o" | "--output" => {
+                let path = args.next().ok_or("Option '--output' requires a file")?;
+                options.output = Some(path.clone());
+            }
+            _ if arg.starts_with("--output=") => {
+                options.output = Some(arg["--output=".len()..].to_string());
+            }
+            "--report" => {
+                let path = args.next().ok_or("Option '--report' requires a file")?;
+                options.report = Some(path.clone());
+            }
+            _ if arg.starts_with("--report=") => {
+                options.report = Some(arg["--report=".len()..].to_string());
+            }
+            "--" => only_files = true,
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return Err(format!("Unknown option '{}'", arg));
+            }
+            _ => {
+                let line = pending_line.take().map(|(_, line)| line);
+                options.files.push(parse_file_arg(arg, line));
+            }
+        }
+    }
+
+    if let Some((arg, _)) = pending_line {
+        return Err(format!("'{}' must be followed by a file", arg));
+    }
+
+    if options.files.is_empty() && !options.changed {
+        return Err("No file specified.".to_string());
+    }
+
+    Ok(Command::Edit(options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn paths(options: &EditOptions) -> Vec<&str> {
+        options.files.iter().map(|f| f.path.as_str()).collect()
+    }
+
+    #[test]
+    fn test_parse_single_file() {
+        let command = parse_args(&args(&["a.rs"])).unwrap();
+        assert_eq!(
+            command,
+            Command::Edit(EditOptions {
+                files: vec![FileArg::new("a.rs", None)],
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_multiple_files() {
+        let Command::Edit(options) = parse_args(&args(&["a.rs", "b.rs", "c.md"])).unwrap() else {
+            panic!("Expected Command::Edit");
+        };
+        assert_eq!(paths(&options), vec!["a.rs", "b.rs", "c.md"]);
+    }
+
+    #[test]
+    fn test_parse_help() {
+        assert_eq!(parse_args(&args(&["--help"])).unwrap(), Command::Help);
+        assert_eq!(parse_args(&args(&["a.rs", "-h"])).unwrap(), Command::Help);
+    }
+
+    #[test]
+    fn test_parse_changed_without_files() {
+        let Command::Edit(options) = parse_args(&args(&["--changed"])).unwrap() else {
+            panic!("Expected Command::Edit");
+        };
+        assert!(options.changed);
+        assert!(options.files.is_empty());
+    }
+
+    #[test]
+    fn test_parse_no_files_is_error() {
+        assert!(parse_args(&[]).is_err());
+    }
+
+    #[test]
+    fn test_parse_unknown_option_is_error() {
+        assert!(parse_args(&args(&["--bogus", "a.rs"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_base() {
+        let Command::Edit(options) = parse_args(&args(&["--changed", "--base", "main"])).unwrap() else {
+            panic!("Expected Command::Edit");
+        };
+        assert_eq!(options.base.as_deref(), Some("main"));
+        assert!(options.changed);
+
+        let Command::Edit(options) = parse_args(&args(&["--base=HEAD~3", "a.rs"])).unwrap() else {
+            panic!("Expected Command::Edit");
+        };
+        assert_eq!(options.base.as_deref(), Some("HEAD~3"));
+        assert_eq!(paths(&options), vec!["a.rs"]);
+    }
+
+    #[test]
+    fn test_parse_base_without_revision_is_error() {
+        assert!(parse_args(&args(&["a.rs", "--base"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_list_defaults_to_current_dir() {
+        assert_eq!(
+            parse_args(&args(&["list"])).unwrap(),
+            Command::List(ListOptions { paths: vec![".".to_string()] })
+        );
+    }
+
+    #[test]
+    fn test_parse_list_paths() {
+        assert_eq!(
+            parse_args(&args(&["list", "src", "README.md"])).unwrap(),
+            Command::List(ListOptions { paths: vec!["src".to_string(), "README.md".to_string()] })
+        );
+        assert!(parse_args(&args(&["list", "--bogus"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_export_defaults() {
+        assert_eq!(
+            parse_args(&args(&["export"])).unwrap(),
+            Command::Export(ExportOptions {
+                paths: vec![".".to_string()],
+                format: ExportFormat::Json,
+                output: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_stdin_patch_with_output() {
+        let Command::Edit(options) = parse_args(&args(&["-", "-o", "review.json"])).unwrap() else {
+            panic!("expected edit command");
+        };
+        assert_eq!(paths(&options), vec!["-"]);
+        assert_eq!(options.output.as_deref(), Some("review.json"));
+
+        let Command::Edit(options) = parse_args(&args(&["--output=out.patch", "fix.patch"])).unwrap() else {
+            panic!("expected edit command");
+        };
+        assert_eq!(options.output.as_deref(), Some("out.patch"));
+        assert!(parse_args(&args(&["fix.patch", "-o"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_view() {
+        for flag in ["-v", "--view"] {
+            let Command::Edit(options) = parse_args(&args(&[flag, "PLAN.md"])).unwrap() else {
+                panic!("expected edit command");
+            };
+            assert!(options.view);
+            assert_eq!(paths(&options), vec!["PLAN.md"]);
+        }
+    }
+
+    #[test]
+    fn test_parse_sidecar() {
+        let Command::Edit(options) = parse_args(&args(&["--sidecar", "gen.rs"])).unwrap() else {
+            panic!("expected edit command");
+        };
+        assert!(options.sidecar);
+        assert_eq!(paths(&options), vec!["gen.rs"]);
+    }
+
+    #[test]
+    fn test_parse_backup() {
+        let Command::Edit(options) = parse_args(&args(&["-B", "a.rs", "--backup"])).unwrap() else {
+            panic!("expected edit command");
+        };
+        assert!(options.backup);
+        assert_eq!(paths(&options), vec!["a.rs"]);
+    }
+
+    #[test]
+    fn test_parse_report() {
+        let Command::Edit(options) = parse_args(&args(&["--report", "out.json", "a.rs"])).unwrap() else {
+            panic!("expected edit command");
+        };
+        assert_eq!(options.report.as_deref(), Some("out.json"));
+        assert_eq!(paths(&options), vec!["a.rs"]);
+        assert!(parse_args(&args(&["a.rs", "--report"])).is_err());
+    }
+
+    #[test]
+    fn test_take_marker_option() {
+        let mut list = args(&["--marker", "[REVIEW]", "list", "src"]);
+        assert_eq!(take_marker_option(&mut list), Ok(Some("[REVIEW]".to_string())));
+        assert_eq!(list, args(&["list", "src"]));
+
+        let mut list = args(&["a.rs", "--marker=[NANOT]", "--", "--marker"]);
+        assert_eq!(take_marker_option(&mut list), Ok(Some("[NANOT]".to_string())));
+        assert_eq!(list, args(&["a.rs", "--", "--marker"]));
+
+        assert!(take_marker_option(&mut args(&["a.rs", "--marker"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_migrate_options() {
+        assert_eq!(
+            parse_args(&args(&["migrate", "--from", "[ANNOTATION]", "--to=[REVIEW]", "-n", "docs"])).unwrap(),
+            Command::Migrate(MigrateOptions {
+                paths: vec!["docs".to_string()],
+                from: "[ANNOTATION]".to_string(),
+                to: Some("[REVIEW]".to_string()),
+                dry_run: true,
+            })
+        );
+
+        let Command::Migrate(options) = parse_args(&args(&["migrate", "--from=[OLD]"])).unwrap() else {
+            panic!("expected migrate command");
+        };
+        assert_eq!(options.paths, vec!["."]);
+        assert!(options.to.is_none());
+        assert!(parse_args(&args(&["migrate", "src"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_export_options() {
+        assert_eq!(
+            parse_args(&args(&["export", "--format", "sarif", "-o", "review.sarif", "src"])).unwrap(),
+            Command::Export(ExportOptions {
+                paths: vec!["src".to_string()],
+                format: ExportFormat::Sarif,
+                output: Some("review.sarif".to_string()),
+            })
+        );
+        let Command::Export(options) = parse_args(&args(&["export", "--format=md"])).unwrap() else {
+            panic!("Expected Command::Export");
+        };
+        assert_eq!(options.format, ExportFormat::Markdown);
+    }
+
+    #[test]
+    fn test_parse_export_invalid_format() {
+        assert!(parse_args(&args(&["export", "--format", "xml"])).is_err());
+        assert!(parse_args(&args(&["export", "--format"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_annotate() {
+        assert_eq!(
+            parse_args(&args(&["annotate", "src/a.rs:12", "rename", "this"])).unwrap(),
+            Command::Annotate(AnnotateOptions {
+                file: "src/a.rs".to_string(),
+                line: 12,
+                text: Some("rename this".to_string()),
+                reply: false,
+                severity: None,
+                author: None,
+                no_stamp: false,
+                span: None,
+                resolve: None,
+                dry_run: false,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_annotate_remove_dry_run() {
+        assert_eq!(
+            parse_args(&args(&["annotate", "--dry-run", "--remove", "a.rs:3"])).unwrap(),
+            Command::Annotate(AnnotateOptions {
+                file: "a.rs".to_string(),
+                line: 3,
+                text: None,
+                reply: false,
+                severity: None,
+                author: None,
+                no_stamp: false,
+                span: None,
+                resolve: None,
+                dry_run: true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_annotate_errors() {
+        assert!(parse_args(&args(&["annotate"])).is_err());
+        assert!(parse_args(&args(&["annotate", "a.rs", "text"])).is_err());
+        assert!(parse_args(&args(&["annotate", "a.rs:0", "text"])).is_err());
+        assert!(parse_args(&args(&["annotate", "a.rs:3"])).is_err());
+        assert!(parse_args(&args(&["annotate", "--remove", "a.rs:3", "text"])).is_err());
+        assert!(parse_args(&args(&["annotate", "--remove", "--reply", "a.rs:3"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_annotate_text_starting_with_dash() {
+        let Command::Annotate(options) = parse_args(&args(&["annotate", "a.rs:1", "--", "-1 is wrong"])).unwrap() else {
+            panic!("Expected Command::Annotate");
+        };
+        assert_eq!(options.text.as_deref(), Some("-1 is wrong"));
+    }
+
+    #[test]
+    fn test_parse_annotate_reply() {
+        let Command::Annotate(options) = parse_args(&args(&["annotate", "-r", "a.rs:1", "agreed"])).unwrap() else {
+            panic!("Expected Command::Annotate");
+        };
+        assert!(options.reply);
+        assert_eq!(options.text.as_deref(), Some("agreed"));
+    }
+
+    #[test]
+    fn test_parse_annotate_metadata() {
+        let Command::Annotate(options) =
+            parse_args(&args(&["annotate", "--severity", "Blocker", "--by=Jane Doe", "a.rs:1", "fix"])).unwrap()
+        else {
+            panic!("Expected Command::Annotate");
+        };
+        assert_eq!(options.severity, Some(Severity::Blocker));
+        assert_eq!(options.author.as_deref(), Some("Jane Doe"));
+        assert!(!options.no_stamp);
+        let Command::Annotate(options) = parse_args(&args(&["annotate", "--no-stamp", "a.rs:1", "fix"])).unwrap() else {
+            panic!("Expected Command::Annotate");
+        };
+        assert!(options.no_stamp);
+
+        assert!(parse_args(&args(&["annotate", "--severity", "urgent", "a.rs:1", "fix"])).is_err());
+        assert!(parse_args(&args(&["annotate", "--remove", "--by", "me", "a.rs:1"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_annotate_status() {
+        let Command::Annotate(options) = parse_args(&args(&["annotate", "--resolve", "a.rs:1"])).unwrap() else {
+            panic!("Expected Command::Annotate");
+        };
+        assert_eq!((options.resolve, options.text), (Some(true), None));
+
+        let Command::Annotate(options) =
+            parse_args(&args(&["annotate", "--reopen", "--reply", "a.rs:1", "not", "yet"])).unwrap()
+        else {
+            panic!("Expected Command::Annotate");
+        };
+        assert_eq!((options.resolve, options.text.as_deref()), (Some(false), Some("not yet")));
+
+        assert!(parse_args(&args(&["annotate", "--resolve", "--reply", "a.rs:1"])).is_err());
+        assert!(parse_args(&args(&["annotate", "--resolve", "--remove", "a.rs:1"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_annotate_range() {
+        let Command::Annotate(options) = parse_args(&args(&["annotate", "src/a.rs:10-14", "extract"])).unwrap() else {
+            panic!("Expected Command::Annotate");
+        };
+        assert_eq!((options.file.as_str(), options.line, options.span), ("src/a.rs", 10, Some(5)));
+
+        assert!(parse_args(&args(&["annotate", "a.rs:14-10", "text"])).is_err());
+        assert!(parse_args(&args(&["annotate", "a.rs:0-3", "text"])).is_err());
+        assert!(parse_args(&args(&["annotate", "--remove", "a.rs:1-3"])).is_err());
+        assert!(parse_args(&args(&["annotate", "--resolve", "a.rs:1-3"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_clean() {
+        assert_eq!(
+            parse_args(&args(&["clean", "--check", "src"])).unwrap(),
+            Command::Clean(CleanOptions {
+                paths: vec!["src".to_string()],
+                dry_run: false,
+                check: true,
+            })
+        );
+        let Command::Clean(options) = parse_args(&args(&["clean", "-n"])).unwrap() else {
+            panic!("Expected Command::Clean");
+        };
+        assert!(options.dry_run);
+        assert_eq!(options.paths, vec!["."]);
+    }
+
+    #[test]
+    fn test_parse_file_named_list() {
+        let Command::Edit(options) = parse_args(&args(&["--", "list"])).unwrap() else {
+            panic!("Expected Command::Edit");
+        };
+        assert_eq!(paths(&options), vec!["list"]);
+    }
+
+    #[test]
+    fn test_parse_double_dash_allows_dash_filenames() {
+        let Command::Edit(options) = parse_args(&args(&["--", "-notes.md"])).unwrap() else {
+            panic!("Expected Command::Edit");
+        };
+        assert_eq!(paths(&options), vec!["-notes.md"]);
+    }
+
+    #[test]
+    fn test_parse_path_line_suffix() {
+        let Command::Edit(options) = parse_args(&args(&["/nonexistent/a.rs:42", "b.rs:7:3"])).unwrap() else {
+            panic!("Expected Command::Edit");
+        };
+        assert_eq!(
+            options.files,
+            vec![FileArg::new("/nonexistent/a.rs", Some(42)), FileArg::new("b.rs", Some(7))]
+        );
+    }
+
+    #[test]
+    fn test_parse_existing_file_with_colon_is_literal() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("notes:12");
+        std::fs::write(&path, "").unwrap();
+        let name = path.to_string_lossy().to_string();
+
+        let Command::Edit(options) = parse_args(std::slice::from_ref(&name)).unwrap() else {
+            panic!("Expected Command::Edit");
+        };
+        assert_eq!(options.files, vec![FileArg::new(&name, None)]);
+    }
+
+    #[test]
+    fn test_parse_plus_line() {
+        let Command::Edit(options) = parse_args(&args(&["+42", "a.rs", "b.rs", "+3,5", "c.rs"])).unwrap() else {
+            panic!("Expected Command::Edit");
+        };
+        assert_eq!(
+            options.files,
+            vec![
+                FileArg::new("a.rs", Some(42)),
+                FileArg::new("b.rs", None),
+                FileArg::new("c.rs", Some(3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_plus_line_without_file_is_error() {
+        assert!(parse_args(&args(&["a.rs", "+42"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_plus_prefixed_filename() {
+        let Command::Edit(options) = parse_args(&args(&["+notes.md"])).unwrap() else {
+            panic!("Expected Command::Edit");
+        };
+        assert_eq!(options.files, vec![FileArg::new("+notes.md", None)]);
+    }
+}
@@ -0,0 +1,369 @@
+//! User and project settings read from `nanot.toml` config files.
+//!
+//! Settings are looked up in this order, later ones winning:
+//! `$XDG_CONFIG_HOME/nanot/config.toml` (or `~/.config/nanot/config.toml`),
+//! then the nearest `.nanot.toml` in the current directory or its parents,
+//! then command-line flags.
+
+use crate::highlighting;
+use globset::{Glob, GlobMatcher};
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Marker tag used when no config file or flag sets one
+pub const DEFAULT_MARKER: &str = "[ANNOTATION]";
+
+/// Name of the per-project config file
+pub const PROJECT_CONFIG_FILE: &str = ".nanot.toml";
+
+/// Effective settings for this run.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Tag that marks a comment line as an annotation, e.g. `[ANNOTATION]`
+    pub marker: String,
+    /// User language mappings, checked before the built-in detection. The
+    /// first matching rule wins; project rules come before user ones.
+    pub languages: Vec<LanguageRule>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            marker: DEFAULT_MARKER.to_string(),
+            languages: Vec::new(),
+        }
+    }
+}
+
+/// Files matched by a `[[languages]]` entry and the language to use for them.
+#[derive(Debug, Clone)]
+pub struct LanguageRule {
+    extensions: Vec<String>,
+    patterns: Vec<GlobMatcher>,
+    /// Comment style in `lang_comment` form (see `file::CommentStyle`)
+    pub comment: Option<String>,
+    /// Name of a syntect syntax, as spelled by syntect
+    pub syntax: Option<String>,
+}
+
+impl LanguageRule {
+    /// True if the rule applies to `path`. Patterns without a `/` are matched
+    /// against the file name, others against the end of the path.
+    pub fn matches(&self, path: &str) -> bool {
+        let path = Path::new(path);
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        if self.extensions.iter().any(|ext| ext.eq_ignore_ascii_case(extension)) {
+            return true;
+        }
+
+        let components: Vec<_> = path.components().collect();
+        self.patterns.iter().any(|pattern| {
+            if !pattern.glob().glob().contains('/') {
+                return path.file_name().is_some_and(|name| pattern.is_match(name));
+            }
+            (0..components.len()).any(|i| pattern.is_match(components[i..].iter().collect::<PathBuf>()))
+        })
+    }
+}
+
+/// Contents of one config file; unset keys leave earlier values alone.
+#[derive(Debug, Default, Deserialize)]
+struct ConfigFile {
+    marker: Option<String>,
+    #[serde(default)]
+    languages: Vec<LanguageEntry>,
+}
+
+/// One `[[languages]]` table as written in a config file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LanguageEntry {
+    #[serde(default)]
+    extensions: Vec<String>,
+    #[serde(default)]
+    patterns: Vec<String>,
+    comment: Option<String>,
+    block_comment: Option<[String; 2]>,
+    syntax: Option<String>,
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Settings in use. Falls back to the defaults until `init` is called.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+/// The annotation marker tag in use.
+pub fn marker_tag() -> &'static str {
+    &get().marker
+}
+
+/// Makes `config` the settings for the rest of the run. Only the first
+/// call has an effect.
+pub fn init(config: Config) {
+    let _ = CONFIG.set(config);
+}
+
+/// Loads the user and project config files and applies a marker given on
+/// the command line.
+pub fn load(marker_override: Option<String>) -> Result<Config, String> {
+    let mut config = Config::default();
+
+    let project = env::current_dir().ok().and_then(|dir| find_project_config(&dir));
+    for path in user_config_path().into_iter().chain(project) {
+        if path.is_file() {
+            apply_file(&mut config, &path)?;
+        }
+    }
+
+    if let Some(marker) = marker_override {
+        validate_marker(&marker)?;
+        config.marker = marker;
+    }
+    Ok(config)
+}
+
+/// Applies the settings in one config file on top of `config`.
+pub fn apply_file(config: &mut Config, path: &Path) -> Result<(), String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Cannot read '{}': {}", path.display(), e))?;
+    let file: ConfigFile = toml::from_str(&content).map_err(|e| format!("Invalid config '{}': {}", path.display(), e))?;
+
+    if let Some(marker) = file.marker {
+        validate_marker(&marker).map_err(|e| format!("{} (in '{}')", e, path.display()))?;
+        config.marker = marker;
+    }
+
+    let rules = file
+        .languages
+        .into_iter()
+        .map(language_rule)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("{} (in '{}')", e, path.display()))?;
+    // Later files win, so their rules are checked first
+    config.languages.splice(0..0, rules);
+    Ok(())
+}
+
+fn language_rule(entry: LanguageEntry) -> Result<LanguageRule, String> {
+    if entry.extensions.is_empty() && entry.patterns.is_empty() {
+        return Err("Language mapping needs 'extensions' or 'patterns'".to_string());
+    }
+
+    let patterns = entry
+        .patterns
+        .iter()
+        .map(|pattern| {
+            Glob::new(pattern)
+                .map(|glob| glob.compile_matcher())
+                .map_err(|e| format!("Invalid pattern '{}': {}", pattern, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let fits = |token: &str| !token.chars().any(char::is_whitespace);
+    let comment = match (entry.comment, entry.block_comment) {
+        (Some(_), Some(_)) => return Err("Language mapping sets both 'comment' and 'block_comment'".to_string()),
+        (Some(line), None) if fits(&line) => Some(line),
+        (None, Some([open, close])) if !open.is_empty() && !close.is_empty() && fits(&open) && fits(&close) => {
+            Some(format!("{} {}", open, close))
+        }
+        (None, None) => None,
+        _ => return Err("Comment tokens must not contain spaces".to_string()),
+    };
+
+    let syntax = match entry.syntax {
+        Some(name) => Some(
+            highlighting::syntax_set()
+                .syntaxes()
+                .iter()
+                .find(|syntax| syntax.name.eq_ignore_ascii_case(&name))
+                .map(|syntax| syntax.name.clone())
+                .ok_or_else(|| format!("Unknown syntax '{}'", name))?,
+        ),
+        None => None,
+    };
+    if comment.is_none() && syntax.is_none() {
+        return Err("Language mapping needs 'comment', 'block_comment' or 'syntax'".to_string());
+    }
+
+    Ok(LanguageRule {
+        extensions: entry
+            .extensions
+            .into_iter()
+            .map(|ext| ext.trim_start_matches('.').to_string())
+            .collect(),
+        patterns,
+        comment,
+        syntax,
+    })
+}
+
+/// A marker tag must be a single non-empty word so it can be told apart
+/// from the annotation text that follows it. Tags such as `TODO:` are
+/// valid but can't hold attributes; see `holds_attributes`.
+pub fn validate_marker(marker: &str) -> Result<(), String> {
+    if marker.is_empty() || marker.chars().any(char::is_whitespace) {
+        return Err(format!("Invalid marker tag '{}': it must be one word without spaces", marker));
+    }
+    Ok(())
+}
+
+/// True if `marker` has room for attributes (severity, author, status,
+/// line range), which go before its closing `]`. Anything that writes
+/// attributes into a file must check this first.
+pub fn holds_attributes(marker: &str) -> bool {
+    marker.ends_with(']')
+}
+
+/// Why attributes can't be written with `marker`.
+pub fn no_attributes_error(marker: &str) -> String {
+    format!(
+        "The marker tag '{}' can't hold a severity, author, status or line range (it must end in ']')",
+        marker
+    )
+}
+
+fn user_config_path() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("nanot").join("config.toml"))
+}
+
+/// Finds the nearest `.nanot.toml` in `start` or one of its parents.
+fn find_project_config(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_apply_file_sets_marker() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(PROJECT_CONFIG_FILE);
+        fs::write(&path, "marker = \"[REVIEW]\"\n").unwrap();
+
+        let mut config = Config::default();
+        apply_file(&mut config, &path).unwrap();
+        assert_eq!(config.marker, "[REVIEW]");
+    }
+
+    #[test]
+    fn test_apply_file_without_marker_keeps_default() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(PROJECT_CONFIG_FILE);
+        fs::write(&path, "# nothing set\n").unwrap();
+
+        let mut config = Config::default();
+        apply_file(&mut config, &path).unwrap();
+        assert_eq!(config.marker, DEFAULT_MARKER);
+    }
+
+    #[test]
+    fn test_apply_file_rejects_bad_input() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(PROJECT_CONFIG_FILE);
+
+        fs::write(&path, "marker = \"TO DO\"\n").unwrap();
+        assert!(apply_file(&mut Config::default(), &path).is_err());
+
+        fs::write(&path, "marker = [\n").unwrap();
+        assert!(apply_file(&mut Config::default(), &path).is_err());
+    }
+
+    fn rules(toml: &str) -> Result<Vec<LanguageRule>, String> {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(PROJECT_CONFIG_FILE);
+        fs::write(&path, toml).unwrap();
+        let mut config = Config::default();
+        apply_file(&mut config, &path)?;
+        Ok(config.languages)
+    }
+
+    #[test]
+    fn test_language_rules() {
+        let rules = rules(
+            r##"
+[[languages]]
+extensions = ["flow", ".gql"]
+comment = "#"
+
+[[languages]]
+patterns = ["BUILD.*", "tools/**/*.def"]
+block_comment = ["/*", "*/"]
+syntax = "python"
+"##,
+        )
+        .unwrap();
+
+        assert_eq!(rules[0].comment.as_deref(), Some("#"));
+        assert!(rules[0].matches("src/app.flow"));
+        assert!(rules[0].matches("schema.GQL"));
+        assert!(!rules[0].matches("flow.rs"));
+
+        assert_eq!(rules[1].comment.as_deref(), Some("/* */"));
+        assert_eq!(rules[1].syntax.as_deref(), Some("Python"));
+        assert!(rules[1].matches("/repo/pkg/BUILD.gen"));
+        assert!(rules[1].matches("/repo/tools/gen/x.def"));
+        assert!(!rules[1].matches("/repo/src/x.def"));
+    }
+
+    #[test]
+    fn test_language_rules_reject_bad_entries() {
+        assert!(rules("[[languages]]\ncomment = \"#\"\n").is_err());
+        assert!(rules("[[languages]]\nextensions = [\"x\"]\n").is_err());
+        assert!(rules("[[languages]]\nextensions = [\"x\"]\nsyntax = \"Nope\"\n").is_err());
+        assert!(rules("[[languages]]\nextensions = [\"x\"]\ncomment = \"# #\"\n").is_err());
+        assert!(rules("[[languages]]\npatterns = [\"a[\"]\ncomment = \"#\"\n").is_err());
+    }
+
+    #[test]
+    fn test_later_files_rules_come_first() {
+        let dir = TempDir::new().unwrap();
+        let user = dir.path().join("config.toml");
+        let project = dir.path().join(PROJECT_CONFIG_FILE);
+        fs::write(&user, "[[languages]]\nextensions = [\"x\"]\ncomment = \"#\"\n").unwrap();
+        fs::write(&project, "[[languages]]\nextensions = [\"x\"]\ncomment = \"--\"\n").unwrap();
+
+        let mut config = Config::default();
+        apply_file(&mut config, &user).unwrap();
+        apply_file(&mut config, &project).unwrap();
+        assert_eq!(config.languages[0].comment.as_deref(), Some("--"));
+    }
+
+    #[test]
+    fn test_validate_marker() {
+        assert!(validate_marker("[NANOT]").is_ok());
+        assert!(validate_marker("REVIEW:").is_ok());
+        assert!(validate_marker("").is_err());
+        assert!(validate_marker("[MY TAG]").is_err());
+    }
+
+    #[test]
+    fn test_holds_attributes() {
+        assert!(holds_attributes("[NANOT]"));
+        assert!(holds_attributes(DEFAULT_MARKER));
+        assert!(!holds_attributes("REVIEW:"));
+        assert!(!holds_attributes("TODO"));
+    }
+
+    #[test]
+    fn test_find_project_config_walks_up() {
+        let dir = TempDir::new().unwrap();
+        let nested = dir.path().join("a/b");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(dir.path().join(PROJECT_CONFIG_FILE), "").unwrap();
+
+        assert_eq!(find_project_config(&nested), Some(dir.path().join(PROJECT_CONFIG_FILE)));
+    }
+}
@@ -2,6 +2,8 @@
 
 use similar::{ChangeTag, TextDiff};
 
+use crate::config;
+use crate::file;
 use crate::models::Line;
 
 /// Type of change for a word or line
@@ -379,14 +381,15 @@ pub fn calculate_diff(working: &[Line], head_content: &str, comment_style: &str)
 }
 
 /// Strip annotation from a line content.
-/// Only recognizes [ANNOTATION] when:
+/// Only recognizes the marker tag (`[ANNOTATION]` by default) when:
 /// 1. For markdown: at the start of the line (after whitespace)
-/// 2. For other languages: after comment prefix + space (inline comment at end of line)
+/// 2. For other languages: after comment prefix + space (inline comment at end of line),
+///    up to the closing token for block comments
 pub fn strip_annotation(content: &str, comment_style: &str) -> String {
     if comment_style.is_empty() {
-        // For markdown, [ANNOTATION] must be at the start of the trimmed line
+        // For markdown, the tag must be at the start of the trimmed line
         let trimmed = content.trim_start();
-        if trimmed.starts_with("[ANNOTATION]") {
+        if trimmed.starts_with(config::marker_tag()) {
             let leading_ws_len = content.len() - trimmed.len();
             return content[..leading_ws_len].to_string();
         }
@@ -394,9 +397,9 @@ pub fn strip_annotation(content: &str, comment_style: &str) -> String {
     }
 
     // For other languages: strip inline annotations
-    // Format: <code> <comment_prefix> [ANNOTATION] <text>
+    // Format: <code> <comment_prefix> <tag> <text>
     // The annotation must follow the comment prefix + space
-    let annotation_marker = format!("{} [ANNOTATION]", comment_style);
+    let annotation_marker = file::annotation_marker(comment_style, config::marker_tag());
 
     if let Some(pos) = content.find(&annotation_marker) {
         // Check what comes before the marker
@@ -417,8 +420,16 @@ pub fn strip_annotation(content: &str, comment_style: &str) -> String {
             return content.to_string();
         }
 
-        // Strip the annotation
-        return content[..pos].trim_end().to_string();
+        // Strip the annotation, keeping any code after a block comment's close
+        let before = content[..pos].trim_end();
+        let close = file::CommentStyle::parse(comment_style).close;
+        if !close.is_empty()
+            && let Some(end) = content[pos..].find(close)
+        {
+            let after = &content[pos + end + close.len()..];
+            return format!("{}{}", before, after).trim_end().to_string();
+        }
+        return before.to_string();
     }
 
     content.to_string()
@@ -705,14 +716,14 @@ mod alignment_tests {
     fn line(content: &str) -> Line {
         Line {
             content: content.to_string(),
-            annotation: None,
+            annotations: Vec::new(),
         }
     }
 
     fn line_with_annotation(content: &str, annotation: &str) -> Line {
         Line {
             content: content.to_string(),
-            annotation: Some(annotation.to_string()),
+            annotations: vec![annotation.into()],
         }
     }
 
@@ -830,10 +841,10 @@ pub fn cursor_to_diff_index(diff_result: &DiffResult, cursor_line: usize) -> Opt
     let target_line_num = cursor_line + 1; // DiffLine uses 1-indexed line numbers
 
     for (idx, diff_line) in diff_result.lines.iter().enumerate() {
-        if let Some((line_num, _, _)) = &diff_line.working {
-            if *line_num == target_line_num {
-                return Some(idx);
-            }
+        if let Some((line_num, _, _)) = &diff_line.working
+            && *line_num == target_line_num
+        {
+            return Some(idx);
         }
     }
     None
@@ -927,6 +938,13 @@ mod strip_annotation_tests {
         assert_eq!(result, "let x = 5;");
     }
 
+    #[test]
+    fn test_strip_annotation_block_comment() {
+        assert_eq!(strip_annotation("<p>Hi</p> <!-- [ANNOTATION] fix -->", "<!-- -->"), "<p>Hi</p>");
+        assert_eq!(strip_annotation("a { /* [ANNOTATION] x */ color: red; }", "/* */"), "a { color: red; }");
+        assert_eq!(strip_annotation("<!-- [ANNOTATION] note -->", "<!-- -->"), "");
+    }
+
     #[test]
     fn test_strip_annotation_readme_table_example() {
         // Exact case from README.md - should NOT strip
@@ -953,7 +971,7 @@ mod scroll_sync_tests {
     fn create_test_diff(working_lines: &[&str], head_lines: &[&str]) -> DiffResult {
         let working: Vec<Line> = working_lines.iter().map(|s| Line {
             content: s.to_string(),
-            annotation: None,
+            annotations: Vec::new(),
         }).collect();
         calculate_diff(&working, &head_lines.join("\n"), "//")
     }
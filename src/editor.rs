@@ -1,8 +1,15 @@
+use crate::atomic;
+use crate::buffer::{self, Buffer};
+use crate::config;
 use crate::diff::{calculate_diff, strip_annotation};
 use crate::event_handler;
-use crate::file;
-use crate::git;
-use crate::models::{EditorState, Line, ViewMode};
+use crate::export::{self, ExportFormat, ExportedAnnotation};
+use crate::file::{self, TextFormat};
+use crate::git::{self, FileChange};
+use crate::models::{Annotation, BufferInfo, EditorState, Line, ViewMode};
+use crate::patch::Patch;
+use crate::report::{SessionOutcome, SessionReport};
+use crate::sidecar::Sidecar;
 use crate::theme::Theme;
 use crate::ui;
 use crossterm::{
@@ -11,10 +18,9 @@ use crossterm::{
     execute,
     terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use std::collections::hash_map::DefaultHasher;
-use std::fs;
-use std::hash::{Hash, Hasher};
 use std::io;
+use std::mem;
+use std::path::Path;
 
 /// Minimum terminal width required for diff mode (100 columns)
 const MIN_DIFF_WIDTH: u16 = 100;
@@ -25,9 +31,15 @@ pub const DIFF_WIDTH_ERROR: &str = "Terminal too narrow for diff view (min 100 c
 /// Error message when file is not tracked in git
 pub const DIFF_NOT_TRACKED_ERROR: &str = "File is not tracked in git";
 
+/// Status message when annotating a patch line outside any hunk
+pub const PATCH_ANNOTATION_ERROR: &str = "Only lines inside a hunk can be annotated";
+
 /// Error message when file is not in a git repository
 pub const DIFF_NO_REPO_ERROR: &str = "Not a git repository";
 
+/// Error message when the `--base` revision can't be resolved
+pub const DIFF_BASE_ERROR: &str = "Base revision not found";
+
 pub struct Editor {
     pub lines: Vec<Line>,
     pub cursor_line: usize,
@@ -44,95 +56,357 @@ pub struct Editor {
     pub search_matches: Vec<usize>,
     pub current_match: Option<usize>,
     pub annotation_scroll: usize,
+    /// Entry of the cursor line's annotation thread that Enter and Delete
+    /// act on (clamped to the thread when used)
+    pub selected_entry: usize,
     pub history: Vec<crate::models::Action>,
     pub history_index: usize,
     pub highlighter: crate::highlighting::SyntaxHighlighter,
     /// Error message to display in status bar (clears on next action)
     pub status_message: Option<String>,
+    /// All open buffers. The slot at `active_buffer` is an empty placeholder
+    /// while its state is loaded into the fields above.
+    pub buffers: Vec<Buffer>,
+    pub active_buffer: usize,
+    /// Why the active buffer can't be annotated or saved
+    pub read_only: Option<String>,
+    /// Git status of the active buffer when opened from a review queue
+    pub file_change: Option<FileChange>,
+    /// Enter diff view on the next event loop iteration
+    pending_diff: bool,
+    /// Revision diffs are taken against (via its merge base) instead of HEAD
+    pub diff_base: Option<String>,
+    /// Set when the active buffer is a unified diff under review
+    pub patch: Option<Patch>,
+    /// Syntect syntax the active buffer is highlighted with
+    pub syntax: Option<String>,
+    /// Line endings, final newline and BOM the active buffer is saved with
+    pub format: TextFormat,
+    /// Annotations of every buffer when it was opened, by buffer index
+    session_start: Vec<Vec<Vec<Annotation>>>,
+    /// Where `run` writes the session report on exit
+    pub report_path: Option<String>,
+    /// Name recorded as the author of new annotations (git `user.name`)
+    pub author: Option<String>,
+    /// How the active buffer's annotations are kept in a sidecar file
+    pub sidecar: Option<Sidecar>,
+    /// Copy files to `<file>~` before saving over them (`-B`)
+    pub backup: bool,
+    /// Tag annotation markers are saved with
+    pub marker: String,
 }
 
 impl Editor {
+    #[cfg(test)]
     pub fn new(file_path: String) -> io::Result<Self> {
-        let content = fs::read_to_string(&file_path)?;
-        let lang_comment = file::detect_comment_style(&file_path);
-        let lines = file::parse_file(&content, &lang_comment);
+        Self::open(vec![file_path])
+    }
+
+    /// Opens every file as its own buffer and activates the first one.
+    #[cfg(test)]
+    pub fn open(file_paths: Vec<String>) -> io::Result<Self> {
+        let buffers = file_paths
+            .into_iter()
+            .map(Buffer::open)
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(Self::with_buffers(buffers))
+    }
+
+    /// Builds an editor over already-loaded buffers and activates the first one.
+    pub fn with_buffers(mut buffers: Vec<Buffer>) -> Self {
+        if buffers.is_empty() {
+            buffers.push(Buffer {
+                lines: file::parse_file("", ""),
+                ..Default::default()
+            });
+        }
         let theme = Theme::Dark;
         let highlighter =
             crate::highlighting::SyntaxHighlighter::new(matches!(theme, Theme::Dark));
-        let saved_content_hash = Self::compute_content_hash(&lines);
+        let session_start = buffers.iter().map(|b| annotations_of(&b.lines)).collect();
 
-        Ok(Editor {
-            lines,
+        let mut editor = Editor {
+            lines: Vec::new(),
             cursor_line: 0,
             scroll_offset: 0,
             view_mode: ViewMode::Normal,
             editor_state: EditorState::Idle,
-            file_path: Some(file_path),
-            saved_content_hash,
+            file_path: None,
+            saved_content_hash: 0,
             theme,
-            lang_comment,
+            lang_comment: String::new(),
             search_matches: Vec::new(),
             current_match: None,
             annotation_scroll: 0,
+            selected_entry: 0,
             history: Vec::new(),
             history_index: 0,
             highlighter,
             status_message: None,
-        })
+            buffers,
+            active_buffer: 0,
+            read_only: None,
+            file_change: None,
+            pending_diff: false,
+            diff_base: None,
+            patch: None,
+            syntax: None,
+            format: TextFormat::default(),
+            session_start,
+            report_path: None,
+            author: None,
+            sidecar: None,
+            backup: false,
+            marker: config::marker_tag().to_string(),
+        };
+        editor.load_buffer(0);
+        editor
+    }
+
+    /// Check if content has been modified since last save
+    pub fn is_modified(&self) -> bool {
+        buffer::compute_content_hash(&self.lines) != self.saved_content_hash
     }
 
-    /// Compute a hash of the current content (lines + annotations)
-    fn compute_content_hash(lines: &[Line]) -> u64 {
-        let mut hasher = DefaultHasher::new();
-        for line in lines {
-            line.content.hash(&mut hasher);
-            line.annotation.hash(&mut hasher);
+    /// Moves the active buffer's state from the editor back into its slot.
+    fn park_active_buffer(&mut self) {
+        let slot = &mut self.buffers[self.active_buffer];
+        slot.lines = mem::take(&mut self.lines);
+        slot.file_path = self.file_path.take();
+        slot.lang_comment = mem::take(&mut self.lang_comment);
+        slot.cursor_line = self.cursor_line;
+        slot.scroll_offset = self.scroll_offset;
+        slot.view_mode = mem::take(&mut self.view_mode);
+        slot.history = mem::take(&mut self.history);
+        slot.history_index = self.history_index;
+        slot.saved_content_hash = self.saved_content_hash;
+        slot.read_only = self.read_only.take();
+        slot.file_change = self.file_change;
+        slot.pending_diff = self.pending_diff;
+        slot.patch = self.patch.take();
+        slot.syntax = self.syntax.take();
+        slot.format = self.format;
+        slot.sidecar = self.sidecar.take();
+    }
+
+    /// Moves a parked buffer's state into the editor and makes it active.
+    fn load_buffer(&mut self, index: usize) {
+        let slot = mem::take(&mut self.buffers[index]);
+        self.lines = slot.lines;
+        self.file_path = slot.file_path;
+        self.lang_comment = slot.lang_comment;
+        self.cursor_line = slot.cursor_line;
+        self.scroll_offset = slot.scroll_offset;
+        self.view_mode = slot.view_mode;
+        self.history = slot.history;
+        self.history_index = slot.history_index;
+        self.saved_content_hash = slot.saved_content_hash;
+        self.read_only = slot.read_only;
+        self.file_change = slot.file_change;
+        self.pending_diff = slot.pending_diff;
+        self.patch = slot.patch;
+        self.syntax = slot.syntax;
+        self.format = slot.format;
+        self.sidecar = slot.sidecar;
+        self.active_buffer = index;
+        if let Some(reason) = self.read_only.clone().or_else(|| self.sidecar.as_ref().map(Sidecar::status)) {
+            self.status_message = Some(reason);
         }
-        hasher.finish()
     }
 
-    /// Check if content has been modified since last save
-    pub fn is_modified(&self) -> bool {
-        Self::compute_content_hash(&self.lines) != self.saved_content_hash
+    /// Switches to another open buffer, keeping each buffer's cursor,
+    /// scroll position and undo history.
+    pub fn switch_buffer(&mut self, index: usize) {
+        if index == self.active_buffer || index >= self.buffers.len() {
+            return;
+        }
+        self.park_active_buffer();
+        self.load_buffer(index);
+        self.search_matches.clear();
+        self.current_match = None;
+        self.annotation_scroll = 0;
     }
 
-    /// Try to enter diff mode. Returns error message if not possible.
-    /// Only changes view_mode, does not affect editor_state.
-    pub fn enter_diff_mode(&mut self) -> Result<(), &'static str> {
-        // Check if diff is available
+    /// Switches to the next buffer, wrapping around at the end.
+    pub fn next_buffer(&mut self) {
+        let next = (self.active_buffer + 1) % self.buffers.len();
+        self.switch_buffer(next);
+    }
+
+    /// Switches to the previous buffer, wrapping around at the start.
+    pub fn prev_buffer(&mut self) {
+        let count = self.buffers.len();
+        let prev = (self.active_buffer + count - 1) % count;
+        self.switch_buffer(prev);
+    }
+
+    /// Name and modified state of every open buffer, in order.
+    pub fn buffer_infos(&self) -> Vec<BufferInfo> {
+        self.buffers
+            .iter()
+            .enumerate()
+            .map(|(i, b)| {
+                if i == self.active_buffer {
+                    BufferInfo {
+                        name: buffer::display_name(&self.file_path, self.file_change),
+                        modified: self.is_modified(),
+                    }
+                } else {
+                    BufferInfo {
+                        name: b.display_name(),
+                        modified: b.is_modified(),
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Annotations of every open buffer, in buffer order.
+    pub fn collect_annotations(&self) -> Vec<ExportedAnnotation> {
+        let mut annotations = Vec::new();
+        for (i, b) in self.buffers.iter().enumerate() {
+            let (file_path, lines, sidecar) = if i == self.active_buffer {
+                (&self.file_path, &self.lines, &self.sidecar)
+            } else {
+                (&b.file_path, &b.lines, &b.sidecar)
+            };
+            match file_path {
+                Some(path) if sidecar.is_some() => annotations.extend(export::collect_sidecar_annotations(path, lines)),
+                Some(path) => annotations.extend(export::collect_annotations(path, lines)),
+                None => {}
+            }
+        }
+        annotations
+    }
+
+    /// Writes the annotations of every open buffer to the format's default
+    /// file in the current directory and reports where they went.
+    fn export_annotations(&mut self, format: ExportFormat) {
+        self.export_annotations_to(format.default_file_name(), format);
+    }
+
+    /// Writes the annotations of every open buffer to `path` atomically,
+    /// saying in the status bar when an existing file was replaced.
+    fn export_annotations_to(&mut self, path: &str, format: ExportFormat) {
+        let annotations = self.collect_annotations();
+        let replaced = Path::new(path).exists();
+        self.status_message = Some(match atomic::write(path, export::render(&annotations, format)) {
+            Ok(()) if replaced => format!("Exported {} annotation(s) to {} (replaced the existing file)", annotations.len(), path),
+            Ok(()) => format!("Exported {} annotation(s) to {}", annotations.len(), path),
+            Err(e) => format!("Export to {} failed: {}", path, e),
+        });
+    }
+
+    /// Check if any open buffer has unsaved changes
+    pub fn has_unsaved_buffers(&self) -> bool {
+        self.is_modified()
+            || self
+                .buffers
+                .iter()
+                .enumerate()
+                .any(|(i, b)| i != self.active_buffer && b.is_modified())
+    }
+
+    /// How the session ends if the editor exits now, with every annotation
+    /// added, changed or removed since the buffers were opened.
+    /// Annotations that were already there and only looked at (e.g. with
+    /// `--view`) don't make the session `Annotated`.
+    pub fn session_report(&self) -> SessionReport {
+        let mut report = SessionReport::new(SessionOutcome::Clean);
+        for (i, b) in self.buffers.iter().enumerate() {
+            let (file_path, lines) = if i == self.active_buffer {
+                (&self.file_path, &self.lines)
+            } else {
+                (&b.file_path, &b.lines)
+            };
+            if let Some(path) = file_path {
+                report.add_file(path, &self.session_start[i], &annotations_of(lines));
+            }
+        }
+
+        if self.has_unsaved_buffers() {
+            report.set_outcome(SessionOutcome::Discarded);
+        } else if report.has_changes() && !self.collect_annotations().is_empty() {
+            report.set_outcome(SessionOutcome::Annotated);
+        }
+        report
+    }
+
+    /// Saves every buffer with unsaved changes, stopping at the first that
+    /// fails with a status message naming it.
+    pub fn save_all(&mut self) -> Result<(), String> {
+        if self.is_modified() {
+            self.save().map_err(|e| save_failed(self.file_path.as_deref(), &e))?;
+        }
+        for (i, b) in self.buffers.iter_mut().enumerate() {
+            if i != self.active_buffer && b.is_modified() {
+                b.save(&self.marker, self.backup)
+                    .map_err(|e| save_failed(b.file_path.as_deref(), &e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Content the working copy is compared against in diff view: the HEAD
+    /// version, the merge-base version when reviewing against `diff_base`,
+    /// or nothing for files added in a review queue.
+    fn diff_base_content(&self) -> Result<String, &'static str> {
+        if self.file_change == Some(FileChange::Added) {
+            return Ok(String::new());
+        }
+
         let file_path = self.file_path.as_ref().ok_or(DIFF_NO_REPO_ERROR)?;
 
         if !git::is_git_available(file_path) {
             return Err(DIFF_NO_REPO_ERROR);
         }
 
-        if !git::is_file_tracked(file_path) {
-            return Err(DIFF_NOT_TRACKED_ERROR);
+        if let Some(ref base) = self.diff_base {
+            // Files that didn't exist at the merge base were added on the branch
+            return match git::get_base_content(file_path, base) {
+                Ok(content) => Ok(content),
+                Err(git::GitError::NotInHead) => Ok(String::new()),
+                Err(git::GitError::InvalidBase(_)) => Err(DIFF_BASE_ERROR),
+                Err(_) => Err(DIFF_NO_REPO_ERROR),
+            };
         }
 
-        // Check terminal width
-        let (width, _) = terminal::size().map_err(|_| DIFF_WIDTH_ERROR)?;
-        if width < MIN_DIFF_WIDTH {
-            return Err(DIFF_WIDTH_ERROR);
+        if !git::is_file_tracked(file_path) {
+            return Err(DIFF_NOT_TRACKED_ERROR);
         }
 
-        // Get HEAD content
-        let head_content = git::get_head_content(file_path).map_err(|e| match e {
+        git::get_head_content(file_path).map_err(|e| match e {
             git::GitError::NotARepo => DIFF_NO_REPO_ERROR,
             git::GitError::NotTracked => DIFF_NOT_TRACKED_ERROR,
             git::GitError::NotInHead => DIFF_NOT_TRACKED_ERROR,
+            git::GitError::InvalidBase(_) => DIFF_BASE_ERROR,
             git::GitError::Git(_) => DIFF_NO_REPO_ERROR,
-        })?;
+        })
+    }
 
-        // Check if there are actual changes between working copy and HEAD
-        let working_content: String = self
-            .lines
+    /// Working copy content with annotations stripped, for comparing against the base.
+    fn working_content(&self) -> String {
+        self.lines
             .iter()
             .map(|line| strip_annotation(&line.content, &self.lang_comment))
             .collect::<Vec<_>>()
-            .join("\n");
-        let head_trimmed = head_content.trim_end();
-        if working_content == head_trimmed {
+            .join("\n")
+    }
+
+    /// Try to enter diff mode. Returns error message if not possible.
+    /// Only changes view_mode, does not affect editor_state.
+    pub fn enter_diff_mode(&mut self) -> Result<(), &'static str> {
+        // Get HEAD content (also checks that diff is available)
+        let head_content = self.diff_base_content()?;
+
+        // Check terminal width
+        let (width, _) = terminal::size().map_err(|_| DIFF_WIDTH_ERROR)?;
+        if width < MIN_DIFF_WIDTH {
+            return Err(DIFF_WIDTH_ERROR);
+        }
+
+        // Check if there are actual changes between working copy and HEAD
+        if self.working_content() == head_content.trim_end() {
             return Err("No changes to show");
         }
 
@@ -165,11 +439,33 @@ impl Editor {
         }
     }
 
+    /// Saves the active buffer for Ctrl+O. A failure is shown in the status
+    /// bar instead of ending the session, which would lose the unsaved
+    /// changes in every other open buffer.
+    fn save_active(&mut self) {
+        if let Err(e) = self.save() {
+            self.status_message = Some(save_failed(self.file_path.as_deref(), &e));
+        }
+    }
+
     pub fn save(&mut self) -> io::Result<()> {
+        if let Some(reason) = &self.read_only {
+            self.status_message = Some(reason.clone());
+            return Ok(());
+        }
         if let Some(ref path) = self.file_path {
-            file::save_file(path, &self.lines, &self.lang_comment)?;
+            buffer::write_file(
+                path,
+                &self.lines,
+                &self.lang_comment,
+                self.format,
+                self.patch.as_ref(),
+                self.sidecar.as_ref(),
+                &self.marker,
+                self.backup,
+            )?;
             // Update hash to reflect saved state
-            self.saved_content_hash = Self::compute_content_hash(&self.lines);
+            self.saved_content_hash = buffer::compute_content_hash(&self.lines);
         }
         Ok(())
     }
@@ -184,31 +480,87 @@ impl Editor {
         // No need to set modified flag - is_modified() uses hash comparison
     }
 
+    /// Applies an action from idle mode and pushes it to history, unless it
+    /// gives an entry attributes the marker tag would drop on save
+    /// (resolving under a tag such as `TODO:`).
+    fn apply_action(&mut self, action: crate::models::Action) {
+        if let crate::models::Action::EditAnnotation { new: Some(annotation), .. } = &action
+            && !annotation.attributes.is_empty()
+            && !self.holds_attributes()
+        {
+            self.status_message = Some(config::no_attributes_error(&self.marker));
+            return;
+        }
+        action.apply(&mut self.lines);
+        self.perform_action(action);
+    }
+
+    /// Applies an added, edited or removed entry from the annotation input
+    /// and pushes it to history, keeping the entry selected. The text is
+    /// stored as it will read back from the file, so the session never shows
+    /// a note that a reload would change; an entry left empty by that is
+    /// removed.
+    fn commit_annotation(&mut self, mut action: crate::models::Action, entry: usize) {
+        if let crate::models::Action::EditAnnotation { old, new, .. } = &mut action {
+            if self.sidecar.is_none()
+                && let Some(annotation) = new
+            {
+                annotation.text = file::saved_annotation_text(&annotation.text, &self.lang_comment);
+            }
+            if new.as_ref().is_some_and(|annotation| annotation.text.is_empty()) {
+                *new = None;
+            }
+            if old == new {
+                return;
+            }
+        }
+        if self.holds_attributes() {
+            self.stamp_new_annotation(&mut action);
+        }
+        action.apply(&mut self.lines);
+        self.perform_action(action);
+        self.selected_entry = entry;
+    }
+
+    /// True if annotations in this buffer can keep attributes (severity,
+    /// author, status, line range) when saved: sidecars always can, comment
+    /// markers only if the tag has room for them.
+    fn holds_attributes(&self) -> bool {
+        self.sidecar.is_some() || config::holds_attributes(&self.marker)
+    }
+
+    /// Records the author and today's date on an entry being added; edits
+    /// to existing entries keep the attributes they already have.
+    fn stamp_new_annotation(&self, action: &mut crate::models::Action) {
+        if let crate::models::Action::EditAnnotation {
+            old: None,
+            new: Some(annotation),
+            ..
+        } = action
+        {
+            annotation.stamp(self.author.as_deref());
+        }
+    }
+
     pub fn undo(&mut self) {
         if self.history_index > 0 {
             self.history_index -= 1;
-            match &self.history[self.history_index] {
-                crate::models::Action::EditAnnotation { line_index, old_text, .. } => {
-                    self.lines[*line_index].annotation = old_text.clone();
-                }
-            }
+            self.history[self.history_index].undo(&mut self.lines);
             // No need to set modified flag - is_modified() uses hash comparison
         }
     }
 
     pub fn redo(&mut self) {
         if self.history_index < self.history.len() {
-            match &self.history[self.history_index] {
-                crate::models::Action::EditAnnotation { line_index, new_text, .. } => {
-                    self.lines[*line_index].annotation = new_text.clone();
-                }
-            }
+            self.history[self.history_index].apply(&mut self.lines);
             self.history_index += 1;
             // No need to set modified flag - is_modified() uses hash comparison
         }
     }
 
-    pub fn run(&mut self) -> io::Result<()> {
+    /// Runs the editor until the user quits and reports how the session
+    /// ended, writing the report to `report_path` when one is set.
+    pub fn run(&mut self) -> io::Result<SessionReport> {
         terminal::enable_raw_mode()?;
         execute!(io::stdout(), EnterAlternateScreen, Hide)?;
 
@@ -217,30 +569,30 @@ impl Editor {
         execute!(io::stdout(), LeaveAlternateScreen, Show)?;
         terminal::disable_raw_mode()?;
 
-        result
+        result?;
+        let report = self.session_report();
+        if let Some(path) = &self.report_path {
+            report.write(path)?;
+        }
+        Ok(report)
     }
 
     fn event_loop(&mut self) -> io::Result<()> {
         loop {
-            // Check if diff is available (git repo + tracked file + has actual changes)
-            let diff_available = self.file_path.as_ref().map_or(false, |path| {
-                if !git::is_git_available(path) || !git::is_file_tracked(path) {
-                    return false;
-                }
-                // Check if there are actual changes between working copy and HEAD
-                if let Ok(head_content) = git::get_head_content(path) {
-                    let working_content: String = self
-                        .lines
-                        .iter()
-                        .map(|line| strip_annotation(&line.content, &self.lang_comment))
-                        .collect::<Vec<_>>()
-                        .join("\n");
-                    let head_trimmed = head_content.trim_end();
-                    working_content != head_trimmed
-                } else {
-                    false
+            // Review queue entries open in diff view when they are first shown
+            if self.pending_diff {
+                self.pending_diff = false;
+                if let Err(msg) = self.enter_diff_mode() {
+                    self.status_message = Some(msg.to_string());
                 }
-            });
+            }
+
+            // Check if diff is available (git repo + tracked file + has actual changes)
+            let diff_available = self
+                .diff_base_content()
+                .is_ok_and(|head_content| self.working_content() != head_content.trim_end());
+
+            let buffer_infos = self.buffer_infos();
 
             // Render using both view_mode and editor_state
             ui::render(
@@ -255,16 +607,27 @@ impl Editor {
                 &self.search_matches,
                 self.current_match,
                 self.annotation_scroll,
+                self.selected_entry,
                 &self.highlighter,
                 self.status_message.as_deref(),
                 &self.lang_comment,
                 diff_available,
+                &buffer_infos,
+                self.active_buffer,
+                self.diff_base.as_deref(),
+                self.read_only.is_some(),
+                self.patch.as_ref(),
+                self.syntax.as_deref(),
             )?;
 
             // Clear status message after displaying
             self.status_message = None;
 
             if let Event::Key(key) = event::read()? {
+                // A new line (or buffer) starts with the first entry of its thread selected
+                let position = (self.active_buffer, self.cursor_line);
+                let holds_attributes = self.holds_attributes();
+
                 // Handle input based on editor_state (NOT view_mode)
                 // view_mode only affects rendering, not input handling
                 match &mut self.editor_state {
@@ -273,7 +636,7 @@ impl Editor {
                         if key.code == KeyCode::Char('o')
                             && key.modifiers.contains(crossterm::event::KeyModifiers::CONTROL)
                         {
-                            self.save()?;
+                            self.save_active();
                             continue;
                         }
 
@@ -283,6 +646,7 @@ impl Editor {
                             key,
                             &mut self.lines,
                             &mut self.cursor_line,
+                            &mut self.selected_entry,
                             &self.view_mode,
                             &mut self.theme,
                             &mut self.annotation_scroll,
@@ -290,28 +654,39 @@ impl Editor {
                         )? {
                             event_handler::IdleModeResult::Exit => break,
                             event_handler::IdleModeResult::ShowQuitPrompt => {
-                                if self.is_modified() {
+                                if self.has_unsaved_buffers() {
                                     self.editor_state = EditorState::QuitPrompt;
                                 } else {
                                     break;
                                 }
                             }
-                            event_handler::IdleModeResult::Action(action) => {
-                                // Apply and push history
-                                match &action {
-                                    crate::models::Action::EditAnnotation { line_index, new_text, .. } => {
-                                        self.lines[*line_index].annotation = new_text.clone();
-                                    }
-                                }
-                                self.perform_action(action);
+                            event_handler::IdleModeResult::Action(_)
+                            | event_handler::IdleModeResult::EnterAnnotation { .. }
+                                if self.read_only.is_some() =>
+                            {
+                                self.status_message = self.read_only.clone();
                             }
+                            event_handler::IdleModeResult::EnterAnnotation { .. }
+                                if self.patch.as_ref().is_some_and(|p| !p.can_annotate(self.cursor_line)) =>
+                            {
+                                self.status_message = Some(PATCH_ANNOTATION_ERROR.to_string());
+                            }
+                            event_handler::IdleModeResult::Action(action) => self.apply_action(action),
                             event_handler::IdleModeResult::Undo => self.undo(),
                             event_handler::IdleModeResult::Redo => self.redo(),
-                            event_handler::IdleModeResult::EnterAnnotation { initial_text } => {
+                            event_handler::IdleModeResult::EnterAnnotation {
+                                initial_text,
+                                entry,
+                                severity,
+                                span,
+                            } => {
                                 let cursor_pos = initial_text.len();
                                 self.editor_state = EditorState::Annotating {
                                     buffer: initial_text,
                                     cursor_pos,
+                                    entry,
+                                    severity,
+                                    span,
                                 };
                                 // view_mode stays unchanged!
                             }
@@ -334,6 +709,16 @@ impl Editor {
                                 self.view_mode = ViewMode::Normal;
                                 // editor_state stays Idle!
                             }
+                            event_handler::IdleModeResult::NextBuffer => self.next_buffer(),
+                            event_handler::IdleModeResult::PrevBuffer => self.prev_buffer(),
+                            event_handler::IdleModeResult::ShowBufferPicker => {
+                                self.editor_state = EditorState::BufferPicker {
+                                    selected: self.active_buffer,
+                                };
+                            }
+                            event_handler::IdleModeResult::ShowExportPrompt => {
+                                self.editor_state = EditorState::ExportPrompt;
+                            }
                             event_handler::IdleModeResult::Continue => {
                                 // Check if theme changed
                                 let new_theme_is_dark = matches!(self.theme, crate::theme::Theme::Dark);
@@ -344,23 +729,31 @@ impl Editor {
                         }
                     }
 
-                    EditorState::Annotating { buffer, cursor_pos } => {
+                    EditorState::Annotating {
+                        buffer,
+                        cursor_pos,
+                        entry,
+                        severity,
+                        span,
+                    } => {
+                        let entry = *entry;
+                        if !holds_attributes && event_handler::sets_attributes(&key) {
+                            self.status_message = Some(config::no_attributes_error(&self.marker));
+                            continue;
+                        }
                         match event_handler::handle_annotation_input(
                             key,
                             buffer,
                             cursor_pos,
                             &self.lines,
                             self.cursor_line,
+                            entry,
+                            severity,
+                            span,
                             &mut self.annotation_scroll,
                         )? {
                             event_handler::AnnotationModeResult::Save(action) => {
-                                // Apply and push history
-                                match &action {
-                                    crate::models::Action::EditAnnotation { line_index, new_text, .. } => {
-                                        self.lines[*line_index].annotation = new_text.clone();
-                                    }
-                                }
-                                self.perform_action(action);
+                                self.commit_annotation(action, entry);
                                 self.editor_state = EditorState::Idle;
                                 // view_mode stays unchanged!
                             }
@@ -404,10 +797,14 @@ impl Editor {
 
                     EditorState::QuitPrompt => {
                         match event_handler::handle_quit_prompt(key) {
-                            event_handler::QuitPromptResult::SaveAndExit => {
-                                self.save()?;
-                                break;
-                            }
+                            event_handler::QuitPromptResult::SaveAndExit => match self.save_all() {
+                                Ok(()) => break,
+                                // Stay in the session so nothing unsaved is lost
+                                Err(message) => {
+                                    self.status_message = Some(message);
+                                    self.editor_state = EditorState::Idle;
+                                }
+                            },
                             event_handler::QuitPromptResult::Exit => {
                                 break;
                             }
@@ -420,6 +817,40 @@ impl Editor {
                             }
                         }
                     }
+
+                    EditorState::ExportPrompt => {
+                        match event_handler::handle_export_prompt(key) {
+                            event_handler::ExportPromptResult::Export(format) => {
+                                self.editor_state = EditorState::Idle;
+                                self.export_annotations(format);
+                            }
+                            event_handler::ExportPromptResult::Cancel => {
+                                self.editor_state = EditorState::Idle;
+                            }
+                            event_handler::ExportPromptResult::Continue => {
+                                // Stay in export prompt
+                            }
+                        }
+                    }
+
+                    EditorState::BufferPicker { selected } => {
+                        match event_handler::handle_buffer_picker(key, selected, self.buffers.len()) {
+                            event_handler::BufferPickerResult::Select(index) => {
+                                self.editor_state = EditorState::Idle;
+                                self.switch_buffer(index);
+                            }
+                            event_handler::BufferPickerResult::Cancel => {
+                                self.editor_state = EditorState::Idle;
+                            }
+                            event_handler::BufferPickerResult::Continue => {
+                                // Stay in buffer picker
+                            }
+                        }
+                    }
+                }
+
+                if (self.active_buffer, self.cursor_line) != position {
+                    self.selected_entry = 0;
                 }
             }
         }
@@ -428,6 +859,15 @@ impl Editor {
     }
 }
 
+/// Status message for a save of `path` that failed.
+fn save_failed(path: Option<&str>, e: &io::Error) -> String {
+    format!("Saving {} failed: {}", path.unwrap_or("[No Name]"), e)
+}
+
+fn annotations_of(lines: &[Line]) -> Vec<Vec<Annotation>> {
+    lines.iter().map(|line| line.annotations.clone()).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -445,9 +885,9 @@ mod tests {
         std::fs::remove_file(test_file).unwrap(); // Cleanup
         
         editor.lines = vec![
-            Line { content: "hello world".to_string(), annotation: None },
-            Line { content: "foo bar".to_string(), annotation: None },
-            Line { content: "hello again".to_string(), annotation: None },
+            Line { content: "hello world".to_string(), annotations: Vec::new() },
+            Line { content: "foo bar".to_string(), annotations: Vec::new() },
+            Line { content: "hello again".to_string(), annotations: Vec::new() },
         ];
 
         // Simulate search
@@ -486,36 +926,36 @@ mod tests {
         editor.cursor_line = 0;
 
         // Next match
-        if let Some(idx) = editor.current_match {
-            if !editor.search_matches.is_empty() {
-                let next = (idx + 1) % editor.search_matches.len();
-                editor.current_match = Some(next);
-                editor.cursor_line = editor.search_matches[next];
-            }
+        if let Some(idx) = editor.current_match
+            && !editor.search_matches.is_empty()
+        {
+            let next = (idx + 1) % editor.search_matches.len();
+            editor.current_match = Some(next);
+            editor.cursor_line = editor.search_matches[next];
         }
 
         assert_eq!(editor.current_match, Some(1));
         assert_eq!(editor.cursor_line, 2);
 
         // Next match again
-        if let Some(idx) = editor.current_match {
-            if !editor.search_matches.is_empty() {
-                let next = (idx + 1) % editor.search_matches.len();
-                editor.current_match = Some(next);
-                editor.cursor_line = editor.search_matches[next];
-            }
+        if let Some(idx) = editor.current_match
+            && !editor.search_matches.is_empty()
+        {
+            let next = (idx + 1) % editor.search_matches.len();
+            editor.current_match = Some(next);
+            editor.cursor_line = editor.search_matches[next];
         }
 
         assert_eq!(editor.current_match, Some(2));
         assert_eq!(editor.cursor_line, 4);
 
         // Cycle back to first
-        if let Some(idx) = editor.current_match {
-            if !editor.search_matches.is_empty() {
-                let next = (idx + 1) % editor.search_matches.len();
-                editor.current_match = Some(next);
-                editor.cursor_line = editor.search_matches[next];
-            }
+        if let Some(idx) = editor.current_match
+            && !editor.search_matches.is_empty()
+        {
+            let next = (idx + 1) % editor.search_matches.len();
+            editor.current_match = Some(next);
+            editor.cursor_line = editor.search_matches[next];
         }
 
         assert_eq!(editor.current_match, Some(0));
@@ -530,33 +970,69 @@ mod tests {
         std::fs::remove_file(test_file).unwrap();
 
         // Initial state
-        assert_eq!(editor.lines[0].annotation, None);
+        assert!(editor.lines[0].annotations.is_empty());
         // History initialized empty in new()
 
         // Perform action: Add annotation
         let action1 = crate::models::Action::EditAnnotation {
             line_index: 0,
-            old_text: None,
-            new_text: Some("note1".to_string()),
+            entry: 0,
+            old: None,
+            new: Some("note1".into()),
         };
-        editor.lines[0].annotation = Some("note1".to_string());
+        editor.lines[0].annotations = vec!["note1".into()];
         editor.perform_action(action1);
 
-        assert_eq!(editor.lines[0].annotation, Some("note1".to_string()));
+        assert_eq!(editor.lines[0].annotations, vec!["note1"]);
         assert_eq!(editor.history.len(), 1);
         assert_eq!(editor.history_index, 1);
 
         // Undo
         editor.undo();
-        assert_eq!(editor.lines[0].annotation, None);
+        assert!(editor.lines[0].annotations.is_empty());
         assert_eq!(editor.history_index, 0);
 
         // Redo
         editor.redo();
-        assert_eq!(editor.lines[0].annotation, Some("note1".to_string()));
+        assert_eq!(editor.lines[0].annotations, vec!["note1"]);
         assert_eq!(editor.history_index, 1);
     }
 
+    #[test]
+    fn test_undo_redo_thread_entries() {
+        let test_file = "test_undo_thread.txt";
+        std::fs::write(test_file, "// [ANNOTATION] why?\nline1\n").unwrap();
+        let mut editor = Editor::new(test_file.to_string()).unwrap();
+        std::fs::remove_file(test_file).unwrap();
+
+        let reply = crate::models::Action::EditAnnotation {
+            line_index: 0,
+            entry: 1,
+            old: None,
+            new: Some("because".into()),
+        };
+        reply.apply(&mut editor.lines);
+        editor.perform_action(reply);
+        let remove_first = crate::models::Action::EditAnnotation {
+            line_index: 0,
+            entry: 0,
+            old: Some("why?".into()),
+            new: None,
+        };
+        remove_first.apply(&mut editor.lines);
+        editor.perform_action(remove_first);
+        assert_eq!(editor.lines[0].annotations, vec!["because"]);
+
+        // Undoing the removal puts the entry back in front of the reply
+        editor.undo();
+        assert_eq!(editor.lines[0].annotations, vec!["why?", "because"]);
+        editor.undo();
+        assert_eq!(editor.lines[0].annotations, vec!["why?"]);
+        editor.redo();
+        editor.redo();
+        assert_eq!(editor.lines[0].annotations, vec!["because"]);
+    }
+
     #[test]
     fn test_undo_at_beginning() {
         let test_file = "test_undo_begin.txt";
@@ -583,10 +1059,11 @@ mod tests {
         // Perform an action
         let action = crate::models::Action::EditAnnotation {
             line_index: 0,
-            old_text: None,
-            new_text: Some("note".to_string()),
+            entry: 0,
+            old: None,
+            new: Some("note".into()),
         };
-        editor.lines[0].annotation = Some("note".to_string());
+        editor.lines[0].annotations = vec!["note".into()];
         editor.perform_action(action);
 
         // Already at end of history
@@ -595,7 +1072,131 @@ mod tests {
         // Redo should have no effect
         editor.redo();
         assert_eq!(editor.history_index, 1);
-        assert_eq!(editor.lines[0].annotation, Some("note".to_string()));
+        assert_eq!(editor.lines[0].annotations, vec!["note"]);
+    }
+
+    #[test]
+    fn test_new_annotations_are_stamped() {
+        let test_file = "test_stamp_new.txt";
+        std::fs::write(test_file, "line1").unwrap();
+        let mut editor = Editor::new(test_file.to_string()).unwrap();
+        std::fs::remove_file(test_file).unwrap();
+        editor.author = Some("Jane Doe".to_string());
+
+        let mut added = crate::models::Action::EditAnnotation {
+            line_index: 0,
+            entry: 0,
+            old: None,
+            new: Some("note".into()),
+        };
+        editor.stamp_new_annotation(&mut added);
+        let crate::models::Action::EditAnnotation { new: Some(new), .. } = &added else {
+            panic!("Expected EditAnnotation");
+        };
+        assert_eq!(new.author(), Some("Jane Doe"));
+        assert_eq!(new.date(), Some(crate::models::today().as_str()));
+
+        // Edits to existing entries are left as they are
+        let mut reworded = crate::models::Action::EditAnnotation {
+            line_index: 0,
+            entry: 0,
+            old: Some(new.clone()),
+            new: Some("reworded".into()),
+        };
+        editor.stamp_new_annotation(&mut reworded);
+        let crate::models::Action::EditAnnotation { new: Some(new), .. } = &reworded else {
+            panic!("Expected EditAnnotation");
+        };
+        assert_eq!(*new, "reworded");
+    }
+
+    #[test]
+    fn test_tag_without_attributes_saves_what_is_shown() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("a.rs");
+        std::fs::write(&path, "fn a() {}\n").unwrap();
+        let mut editor = Editor::new(path.to_string_lossy().to_string()).unwrap();
+        editor.marker = "REVIEW:".to_string();
+        editor.author = Some("Jane Doe".to_string());
+
+        // `REVIEW:` has nowhere to keep a stamp, so new entries go without
+        editor.commit_annotation(
+            crate::models::Action::EditAnnotation {
+                line_index: 0,
+                entry: 0,
+                old: None,
+                new: Some("check".into()),
+            },
+            0,
+        );
+        assert!(editor.lines[0].annotations[0].attributes.is_empty());
+
+        // Resolving (^K) is refused rather than lost on save
+        let old = editor.lines[0].annotations[0].clone();
+        let mut resolved = old.clone();
+        resolved.set_resolved(true);
+        editor.apply_action(crate::models::Action::EditAnnotation {
+            line_index: 0,
+            entry: 0,
+            old: Some(old),
+            new: Some(resolved),
+        });
+        assert!(!editor.lines[0].annotations[0].is_resolved());
+        assert_eq!(editor.status_message, Some(config::no_attributes_error("REVIEW:")));
+
+        editor.save().unwrap();
+        let saved = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(saved, "// REVIEW: check\nfn a() {}\n");
+        let reloaded = file::parse_file_tagged(&saved, "//", "REVIEW:");
+        assert_eq!(annotations_of(&reloaded), annotations_of(&editor.lines));
+    }
+
+    #[test]
+    fn test_committed_text_matches_what_is_saved() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("page.html");
+        std::fs::write(&path, "<p>hi</p>\n").unwrap();
+        let mut editor = Editor::new(path.to_string_lossy().to_string()).unwrap();
+
+        // Like the annotation input, edits start from the old entry
+        let edit = |old: Option<Annotation>, text: &str| {
+            let mut new = old.clone().unwrap_or_default();
+            new.text = text.to_string();
+            crate::models::Action::EditAnnotation { line_index: 0, entry: 0, old, new: Some(new) }
+        };
+        editor.commit_annotation(edit(None, "ends here --> not  \n  next line "), 0);
+        let committed = editor.lines[0].annotations.clone();
+        assert_eq!(committed[0].text, "ends here - -> not\nnext line");
+        editor.save().unwrap();
+        let reloaded = Editor::new(path.to_string_lossy().to_string()).unwrap();
+        assert_eq!(reloaded.lines[0].annotations, committed);
+
+        // Only trailing spaces added: nothing to record
+        editor.commit_annotation(edit(Some(committed[0].clone()), "ends here - -> not\nnext line   "), 0);
+        assert_eq!(editor.history.len(), 1);
+
+        // Whitespace alone removes the entry
+        editor.commit_annotation(edit(Some(committed[0].clone()), "   "), 0);
+        assert!(editor.lines[0].annotations.is_empty());
+    }
+
+    #[test]
+    fn test_export_names_replaced_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let source = dir.path().join("a.rs");
+        std::fs::write(&source, "// [ANNOTATION] check\nfn a() {}\n").unwrap();
+        let mut editor = Editor::new(source.to_string_lossy().to_string()).unwrap();
+        let output = dir.path().join("annotations.json");
+        let output = output.to_string_lossy();
+
+        editor.export_annotations_to(&output, ExportFormat::Json);
+        assert_eq!(editor.status_message, Some(format!("Exported 1 annotation(s) to {}", output)));
+        editor.export_annotations_to(&output, ExportFormat::Json);
+        assert_eq!(
+            editor.status_message,
+            Some(format!("Exported 1 annotation(s) to {} (replaced the existing file)", output))
+        );
+        assert!(std::fs::read_to_string(output.as_ref()).unwrap().contains("\"check\""));
     }
 
     #[test]
@@ -606,27 +1207,30 @@ mod tests {
         std::fs::remove_file(test_file).unwrap();
 
         // Action 1: Add annotation to line 0
-        editor.lines[0].annotation = Some("note0".to_string());
+        editor.lines[0].annotations = vec!["note0".into()];
         editor.perform_action(crate::models::Action::EditAnnotation {
             line_index: 0,
-            old_text: None,
-            new_text: Some("note0".to_string()),
+            entry: 0,
+            old: None,
+            new: Some("note0".into()),
         });
 
         // Action 2: Add annotation to line 1
-        editor.lines[1].annotation = Some("note1".to_string());
+        editor.lines[1].annotations = vec!["note1".into()];
         editor.perform_action(crate::models::Action::EditAnnotation {
             line_index: 1,
-            old_text: None,
-            new_text: Some("note1".to_string()),
+            entry: 0,
+            old: None,
+            new: Some("note1".into()),
         });
 
         // Action 3: Add annotation to line 2
-        editor.lines[2].annotation = Some("note2".to_string());
+        editor.lines[2].annotations = vec!["note2".into()];
         editor.perform_action(crate::models::Action::EditAnnotation {
             line_index: 2,
-            old_text: None,
-            new_text: Some("note2".to_string()),
+            entry: 0,
+            old: None,
+            new: Some("note2".into()),
         });
 
         assert_eq!(editor.history.len(), 3);
@@ -634,25 +1238,25 @@ mod tests {
 
         // Undo all three
         editor.undo(); // Undo note2
-        assert_eq!(editor.lines[2].annotation, None);
+        assert!(editor.lines[2].annotations.is_empty());
 
         editor.undo(); // Undo note1
-        assert_eq!(editor.lines[1].annotation, None);
+        assert!(editor.lines[1].annotations.is_empty());
 
         editor.undo(); // Undo note0
-        assert_eq!(editor.lines[0].annotation, None);
+        assert!(editor.lines[0].annotations.is_empty());
 
         assert_eq!(editor.history_index, 0);
 
         // Redo all three
         editor.redo();
-        assert_eq!(editor.lines[0].annotation, Some("note0".to_string()));
+        assert_eq!(editor.lines[0].annotations, vec!["note0"]);
 
         editor.redo();
-        assert_eq!(editor.lines[1].annotation, Some("note1".to_string()));
+        assert_eq!(editor.lines[1].annotations, vec!["note1"]);
 
         editor.redo();
-        assert_eq!(editor.lines[2].annotation, Some("note2".to_string()));
+        assert_eq!(editor.lines[2].annotations, vec!["note2"]);
 
         assert_eq!(editor.history_index, 3);
     }
@@ -665,34 +1269,37 @@ mod tests {
         std::fs::remove_file(test_file).unwrap();
 
         // Action 1
-        editor.lines[0].annotation = Some("note1".to_string());
+        editor.lines[0].annotations = vec!["note1".into()];
         editor.perform_action(crate::models::Action::EditAnnotation {
             line_index: 0,
-            old_text: None,
-            new_text: Some("note1".to_string()),
+            entry: 0,
+            old: None,
+            new: Some("note1".into()),
         });
 
         // Action 2
-        editor.lines[0].annotation = Some("note2".to_string());
+        editor.lines[0].annotations = vec!["note2".into()];
         editor.perform_action(crate::models::Action::EditAnnotation {
             line_index: 0,
-            old_text: Some("note1".to_string()),
-            new_text: Some("note2".to_string()),
+            entry: 0,
+            old: Some("note1".into()),
+            new: Some("note2".into()),
         });
 
         assert_eq!(editor.history.len(), 2);
 
         // Undo once
         editor.undo();
-        assert_eq!(editor.lines[0].annotation, Some("note1".to_string()));
+        assert_eq!(editor.lines[0].annotations, vec!["note1"]);
         assert_eq!(editor.history_index, 1);
 
         // New action should truncate history
-        editor.lines[0].annotation = Some("note3".to_string());
+        editor.lines[0].annotations = vec!["note3".into()];
         editor.perform_action(crate::models::Action::EditAnnotation {
             line_index: 0,
-            old_text: Some("note1".to_string()),
-            new_text: Some("note3".to_string()),
+            entry: 0,
+            old: Some("note1".into()),
+            new: Some("note3".into()),
         });
 
         // History should be truncated: only action1 and action3 remain
@@ -701,10 +1308,10 @@ mod tests {
 
         // Verify we can't redo to note2 anymore
         editor.undo();
-        assert_eq!(editor.lines[0].annotation, Some("note1".to_string()));
+        assert_eq!(editor.lines[0].annotations, vec!["note1"]);
 
         editor.redo();
-        assert_eq!(editor.lines[0].annotation, Some("note3".to_string())); // Not note2!
+        assert_eq!(editor.lines[0].annotations, vec!["note3"]); // Not note2!
     }
 
     #[test]
@@ -717,11 +1324,12 @@ mod tests {
         assert!(!editor.is_modified());
 
         // Add annotation - now modified
-        editor.lines[0].annotation = Some("note".to_string());
+        editor.lines[0].annotations = vec!["note".into()];
         editor.perform_action(crate::models::Action::EditAnnotation {
             line_index: 0,
-            old_text: None,
-            new_text: Some("note".to_string()),
+            entry: 0,
+            old: None,
+            new: Some("note".into()),
         });
         assert!(editor.is_modified());
 
@@ -747,11 +1355,12 @@ mod tests {
         assert!(!editor.is_modified());
 
         // Add annotation - now modified
-        editor.lines[0].annotation = Some("note".to_string());
+        editor.lines[0].annotations = vec!["note".into()];
         editor.perform_action(crate::models::Action::EditAnnotation {
             line_index: 0,
-            old_text: None,
-            new_text: Some("note".to_string()),
+            entry: 0,
+            old: None,
+            new: Some("note".into()),
         });
         assert!(editor.is_modified());
 
@@ -773,20 +1382,22 @@ mod tests {
         assert!(!editor.is_modified());
 
         // Add annotation
-        editor.lines[0].annotation = Some("note".to_string());
+        editor.lines[0].annotations = vec!["note".into()];
         editor.perform_action(crate::models::Action::EditAnnotation {
             line_index: 0,
-            old_text: None,
-            new_text: Some("note".to_string()),
+            entry: 0,
+            old: None,
+            new: Some("note".into()),
         });
         assert!(editor.is_modified());
 
         // Delete annotation (back to original state)
-        editor.lines[0].annotation = None;
+        editor.lines[0].annotations.clear();
         editor.perform_action(crate::models::Action::EditAnnotation {
             line_index: 0,
-            old_text: Some("note".to_string()),
-            new_text: None,
+            entry: 0,
+            old: Some("note".into()),
+            new: None,
         });
         // Should NOT be modified - back to original state
         assert!(!editor.is_modified(), "Deleting annotation to match original should not be modified");
@@ -823,6 +1434,9 @@ mod tests {
         editor.editor_state = EditorState::Annotating {
             buffer: "test".to_string(),
             cursor_pos: 4,
+            entry: 0,
+            severity: None,
+            span: 1,
         };
         assert!(matches!(editor.editor_state, EditorState::Annotating { .. }));
 
@@ -838,6 +1452,393 @@ mod tests {
         assert!(matches!(editor.view_mode, ViewMode::Normal));
     }
 
+    fn open_two_buffers(dir: &tempfile::TempDir) -> Editor {
+        let a = dir.path().join("a.rs");
+        let b = dir.path().join("b.py");
+        std::fs::write(&a, "a1\na2\na3").unwrap();
+        std::fs::write(&b, "b1\nb2").unwrap();
+        Editor::open(vec![
+            a.to_string_lossy().to_string(),
+            b.to_string_lossy().to_string(),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_open_multiple_buffers() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let editor = open_two_buffers(&dir);
+
+        assert_eq!(editor.buffers.len(), 2);
+        assert_eq!(editor.active_buffer, 0);
+        assert_eq!(editor.lines.len(), 3);
+        assert_eq!(editor.lang_comment, "//");
+    }
+
+    #[test]
+    fn test_switch_buffer_keeps_per_buffer_state() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut editor = open_two_buffers(&dir);
+
+        editor.cursor_line = 2;
+        editor.lines[2].annotations = vec!["note".into()];
+        editor.perform_action(crate::models::Action::EditAnnotation {
+            line_index: 2,
+            entry: 0,
+            old: None,
+            new: Some("note".into()),
+        });
+
+        editor.next_buffer();
+        assert_eq!(editor.active_buffer, 1);
+        assert_eq!(editor.lines[0].content, "b1");
+        assert_eq!(editor.lang_comment, "#");
+        assert_eq!(editor.cursor_line, 0);
+        assert!(editor.history.is_empty());
+        assert!(!editor.is_modified());
+
+        editor.prev_buffer();
+        assert_eq!(editor.active_buffer, 0);
+        assert_eq!(editor.cursor_line, 2);
+        assert_eq!(editor.history.len(), 1);
+        assert!(editor.is_modified());
+
+        // Undo history belongs to the buffer it was recorded in
+        editor.undo();
+        assert!(editor.lines[2].annotations.is_empty());
+    }
+
+    #[test]
+    fn test_next_buffer_wraps_around() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut editor = open_two_buffers(&dir);
+
+        editor.next_buffer();
+        editor.next_buffer();
+        assert_eq!(editor.active_buffer, 0);
+
+        editor.prev_buffer();
+        assert_eq!(editor.active_buffer, 1);
+    }
+
+    #[test]
+    fn test_unsaved_changes_in_background_buffer() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut editor = open_two_buffers(&dir);
+
+        editor.lines[0].annotations = vec!["note".into()];
+        editor.switch_buffer(1);
+
+        assert!(!editor.is_modified());
+        assert!(editor.has_unsaved_buffers());
+        let infos = editor.buffer_infos();
+        assert!(infos[0].modified);
+        assert!(!infos[1].modified);
+        assert!(infos[1].name.ends_with("b.py"));
+    }
+
+    #[test]
+    fn test_save_all_writes_every_modified_buffer() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut editor = open_two_buffers(&dir);
+
+        editor.lines[0].annotations = vec!["first".into()];
+        editor.switch_buffer(1);
+        editor.lines[1].annotations = vec!["second".into()];
+
+        editor.save_all().unwrap();
+        assert!(!editor.has_unsaved_buffers());
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("a.rs")).unwrap(),
+            "// [ANNOTATION] first\na1\na2\na3"
+        );
+        // Neither file had a final newline, so none is added
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("b.py")).unwrap(),
+            "b1\n# [ANNOTATION] second\nb2"
+        );
+    }
+
+    /// Makes saving into `dir` fail by making it read-only, or by removing
+    /// it when running as root, which ignores directory permissions.
+    #[cfg(unix)]
+    fn lock_dir(dir: &Path) {
+        use std::os::unix::fs::PermissionsExt;
+
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o555)).unwrap();
+        if std::fs::write(dir.join("probe"), "").is_ok() {
+            std::fs::remove_dir_all(dir).unwrap();
+        }
+    }
+
+    /// Undoes `lock_dir` so the temporary directory can be cleaned up.
+    #[cfg(unix)]
+    fn unlock_dir(dir: &Path) {
+        use std::os::unix::fs::PermissionsExt;
+
+        if dir.exists() {
+            std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_failed_save_keeps_the_session() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let locked = dir.path().join("locked");
+        std::fs::create_dir(&locked).unwrap();
+        let a = locked.join("a.rs");
+        std::fs::write(&a, "fn a() {}\n").unwrap();
+        let b = dir.path().join("b.rs");
+        std::fs::write(&b, "fn b() {}\n").unwrap();
+        let mut editor =
+            Editor::open(vec![a.to_string_lossy().to_string(), b.to_string_lossy().to_string()]).unwrap();
+        editor.switch_buffer(1);
+        editor.lines[0].annotations = vec!["keep me".into()];
+        editor.switch_buffer(0);
+        editor.lines[0].annotations = vec!["locked out".into()];
+
+        lock_dir(&locked);
+        editor.save_active();
+        unlock_dir(&locked);
+        if locked.exists() {
+            assert_eq!(std::fs::read_to_string(&a).unwrap(), "fn a() {}\n");
+        }
+
+        let message = editor.status_message.clone().unwrap();
+        assert!(message.starts_with(&format!("Saving {} failed: ", a.display())), "{}", message);
+        assert!(editor.is_modified());
+        editor.switch_buffer(1);
+        assert_eq!(editor.lines[0].annotations, vec!["keep me"]);
+        assert!(editor.is_modified());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_failed_save_all_names_the_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let a = dir.path().join("a.rs");
+        std::fs::write(&a, "fn a() {}\n").unwrap();
+        let locked = dir.path().join("locked");
+        std::fs::create_dir(&locked).unwrap();
+        let b = locked.join("b.rs");
+        std::fs::write(&b, "fn b() {}\n").unwrap();
+        let mut editor =
+            Editor::open(vec![a.to_string_lossy().to_string(), b.to_string_lossy().to_string()]).unwrap();
+        editor.lines[0].annotations = vec!["saved".into()];
+        editor.switch_buffer(1);
+        editor.lines[0].annotations = vec!["locked out".into()];
+        editor.switch_buffer(0);
+
+        lock_dir(&locked);
+        let message = editor.save_all().unwrap_err();
+        unlock_dir(&locked);
+
+        assert!(message.starts_with(&format!("Saving {} failed: ", b.display())), "{}", message);
+        assert_eq!(std::fs::read_to_string(&a).unwrap(), "// [ANNOTATION] saved\nfn a() {}\n");
+        assert!(editor.has_unsaved_buffers());
+    }
+
+    #[test]
+    fn test_read_only_buffer_blocks_save() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("gone.rs");
+        let mut editor = Editor::with_buffers(vec![Buffer {
+            lines: vec![Line { content: "fn gone() {}".to_string(), annotations: Vec::new() }],
+            file_path: Some(path.to_string_lossy().to_string()),
+            lang_comment: "//".to_string(),
+            read_only: Some(buffer::DELETED_READ_ONLY.to_string()),
+            file_change: Some(FileChange::Deleted),
+            ..Default::default()
+        }]);
+
+        assert_eq!(editor.status_message.as_deref(), Some(buffer::DELETED_READ_ONLY));
+        editor.status_message = None;
+
+        editor.save().unwrap();
+        assert!(!path.exists(), "deleted file must not be recreated");
+        assert_eq!(editor.status_message.as_deref(), Some(buffer::DELETED_READ_ONLY));
+    }
+
+    #[test]
+    fn test_session_report_outcomes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut editor = open_two_buffers(&dir);
+        assert_eq!(editor.session_report().outcome, SessionOutcome::Clean);
+
+        editor.switch_buffer(1);
+        editor.lines[1].annotations = vec!["check".into()];
+        let report = editor.session_report();
+        assert_eq!(report.outcome, SessionOutcome::Discarded);
+        assert_eq!(report.added.len(), 1);
+        assert!(report.added[0].file.ends_with("b.py"));
+        assert_eq!(report.added[0].line, 2);
+
+        editor.save_all().unwrap();
+        editor.switch_buffer(0);
+        let report = editor.session_report();
+        assert_eq!(report.outcome, SessionOutcome::Annotated);
+        assert_eq!(report.added.len(), 1);
+    }
+
+    #[test]
+    fn test_session_without_edits_is_clean() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("a.rs");
+        std::fs::write(&path, "// [ANNOTATION] old\nfn a() {}\n").unwrap();
+
+        // Existing annotations only looked at
+        let editor = Editor::new(path.to_string_lossy().to_string()).unwrap();
+        assert_eq!(editor.session_report().outcome, SessionOutcome::Clean);
+
+        // --view
+        let mut buffer = Buffer::open(path.to_string_lossy().to_string()).unwrap();
+        buffer.read_only = Some(buffer::VIEW_READ_ONLY.to_string());
+        let mut editor = Editor::with_buffers(vec![buffer]);
+        editor.lines[0].annotations.push("ignored".into());
+        editor.save_all().unwrap();
+        editor.lines[0].annotations.pop();
+        let report = editor.session_report();
+        assert_eq!((report.outcome, report.exit_code), (SessionOutcome::Clean, 0));
+    }
+
+    #[test]
+    fn test_read_only_state_follows_buffer() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("a.rs");
+        std::fs::write(&path, "fn a() {}").unwrap();
+        let mut editor = Editor::with_buffers(vec![
+            Buffer::open(path.to_string_lossy().to_string()).unwrap(),
+            Buffer {
+                lines: vec![Line { content: "[Binary file]".to_string(), annotations: Vec::new() }],
+                file_path: Some("img.png".to_string()),
+                read_only: Some(buffer::BINARY_READ_ONLY.to_string()),
+                ..Default::default()
+            },
+        ]);
+
+        assert!(editor.read_only.is_none());
+        editor.next_buffer();
+        assert_eq!(editor.read_only.as_deref(), Some(buffer::BINARY_READ_ONLY));
+        editor.next_buffer();
+        assert!(editor.read_only.is_none());
+    }
+
+    #[test]
+    fn test_sidecar_buffer_saves_beside_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let json = dir.path().join("data.json");
+        let rs = dir.path().join("a.rs");
+        std::fs::write(&json, "{}\n").unwrap();
+        std::fs::write(&rs, "fn a() {}\n").unwrap();
+        let mut editor = Editor::open(vec![
+            rs.to_string_lossy().to_string(),
+            json.to_string_lossy().to_string(),
+        ])
+        .unwrap();
+        assert!(editor.sidecar.is_none());
+
+        editor.next_buffer();
+        let reason = editor.sidecar.as_ref().unwrap().reason.clone();
+        assert_eq!(editor.status_message.as_deref(), Some(reason.as_str()));
+        editor.lines[0].annotations = vec!["empty?".into()];
+        editor.next_buffer();
+        editor.lines[0].annotations = vec!["inline".into()];
+        editor.save_all().unwrap();
+
+        assert_eq!(std::fs::read_to_string(&json).unwrap(), "{}\n");
+        assert_eq!(std::fs::read_to_string(&rs).unwrap(), "// [ANNOTATION] inline\nfn a() {}\n");
+        assert!(crate::sidecar::path_for(&json.to_string_lossy()).is_file());
+    }
+
+    #[test]
+    fn test_backup_copies_every_saved_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let a = dir.path().join("a.rs");
+        let b = dir.path().join("b.py");
+        std::fs::write(&a, "fn a() {}\n").unwrap();
+        std::fs::write(&b, "x = 1\n").unwrap();
+        let mut editor = Editor::open(vec![a.to_string_lossy().to_string(), b.to_string_lossy().to_string()]).unwrap();
+        editor.backup = true;
+
+        editor.lines[0].annotations = vec!["first".into()];
+        editor.next_buffer();
+        editor.lines[0].annotations = vec!["second".into()];
+        editor.save_all().unwrap();
+
+        assert_eq!(std::fs::read_to_string(&a).unwrap(), "// [ANNOTATION] first\nfn a() {}\n");
+        assert_eq!(std::fs::read_to_string(dir.path().join("a.rs~")).unwrap(), "fn a() {}\n");
+        assert_eq!(std::fs::read_to_string(dir.path().join("b.py~")).unwrap(), "x = 1\n");
+    }
+
+    #[test]
+    fn test_added_file_diffs_against_empty_base() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("new.rs");
+        std::fs::write(&path, "fn new() {}").unwrap();
+        let mut buffer = Buffer::open(path.to_string_lossy().to_string()).unwrap();
+        buffer.file_change = Some(FileChange::Added);
+        let editor = Editor::with_buffers(vec![buffer]);
+
+        assert_eq!(editor.diff_base_content(), Ok(String::new()));
+    }
+
+    #[test]
+    fn test_collect_annotations_covers_all_buffers() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let a = dir.path().join("a.rs");
+        let b = dir.path().join("b.py");
+        std::fs::write(&a, "// [ANNOTATION] saved\nfn a() {}\n").unwrap();
+        std::fs::write(&b, "x = 1\n").unwrap();
+        let mut editor = Editor::open(vec![
+            a.to_string_lossy().to_string(),
+            b.to_string_lossy().to_string(),
+        ])
+        .unwrap();
+        editor.next_buffer();
+        // Unsaved annotation in the active buffer is included too
+        editor.lines[0].annotations = vec!["unsaved".into()];
+
+        let annotations = editor.collect_annotations();
+        let texts: Vec<_> = annotations.iter().map(|a| a.annotation.as_str()).collect();
+        assert_eq!(texts, vec!["saved", "unsaved"]);
+        assert_eq!(annotations[1].file, b.to_string_lossy());
+    }
+
+    #[test]
+    fn test_diff_base_compares_against_merge_base() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .args(args)
+                .current_dir(dir.path())
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success(), "git {:?} failed", args);
+        };
+        git(&["init", "-q", "-b", "main"]);
+        git(&["config", "user.email", "test@example.com"]);
+        git(&["config", "user.name", "Test"]);
+        let path = dir.path().join("a.rs");
+        std::fs::write(&path, "fn a() {}").unwrap();
+        git(&["add", "a.rs"]);
+        git(&["commit", "-q", "-m", "base"]);
+        git(&["checkout", "-q", "-b", "feature"]);
+        std::fs::write(&path, "fn a() { 1 }").unwrap();
+        git(&["commit", "-q", "-am", "feature"]);
+
+        let mut editor = Editor::new(path.to_string_lossy().to_string()).unwrap();
+        // Against HEAD the committed feature version is the base
+        assert_eq!(editor.diff_base_content(), Ok("fn a() { 1 }".to_string()));
+
+        editor.diff_base = Some("main".to_string());
+        assert_eq!(editor.diff_base_content(), Ok("fn a() {}".to_string()));
+
+        editor.diff_base = Some("no-such-branch".to_string());
+        assert_eq!(editor.diff_base_content(), Err(DIFF_BASE_ERROR));
+    }
+
     #[test]
     fn test_cursor_bounds() {
         let test_file = "test_cursor.txt";
@@ -1,9 +1,11 @@
 #![allow(clippy::too_many_arguments)]
 use crate::diff::adjust_diff_scroll;
-use crate::models::{Action, Line, ViewMode};
+use crate::export::ExportFormat;
+use crate::models::{Action, Line, Severity, ViewMode};
 use crate::navigation::{
     adjust_annotation_scroll_pure, adjust_normal_scroll, find_matches, find_next_annotation,
-    find_next_word_boundary, find_prev_annotation, find_prev_word_boundary,
+    find_next_open_annotation, find_next_word_boundary, find_prev_annotation, find_prev_open_annotation,
+    find_prev_word_boundary,
     move_cursor_down_in_wrapped, move_cursor_up_in_wrapped,
 };
 use crossterm::{
@@ -33,6 +35,19 @@ fn matches_ctrl_key(key: &KeyEvent, alternatives: &[char]) -> bool {
     }
 }
 
+/// Check if a key event matches Alt+<one of the alternatives>.
+/// Like `matches_ctrl_key`, alternatives cover the same physical key in other layouts.
+fn matches_alt_key(key: &KeyEvent, alternatives: &[char]) -> bool {
+    if !key.modifiers.contains(KeyModifiers::ALT) {
+        return false;
+    }
+    if let KeyCode::Char(c) = key.code {
+        alternatives.contains(&c)
+    } else {
+        false
+    }
+}
+
 /// Check if a key code matches one of the character alternatives.
 /// Case-insensitive: converts to lowercase before checking.
 fn matches_char(key_code: &KeyCode, alternatives: &[char]) -> bool {
@@ -65,8 +80,15 @@ pub enum IdleModeResult {
     Redo,
     /// An action was performed (e.g., delete annotation)
     Action(Action),
-    /// Enter annotation editing mode
-    EnterAnnotation { initial_text: String },
+    /// Enter annotation editing mode for one entry of the cursor line's
+    /// thread (`entry` equal to the thread length starts a new reply),
+    /// with the severity picker and covered range set from the entry
+    EnterAnnotation {
+        initial_text: String,
+        entry: usize,
+        severity: Option<Severity>,
+        span: usize,
+    },
     /// Enter search mode
     EnterSearch,
     /// Show help overlay
@@ -77,15 +99,26 @@ pub enum IdleModeResult {
     ToggleDiffView,
     /// Exit diff view (only valid when in diff view mode)
     ExitDiffView,
+    /// Switch to the next open buffer
+    NextBuffer,
+    /// Switch to the previous open buffer
+    PrevBuffer,
+    /// Show the buffer picker overlay
+    ShowBufferPicker,
+    /// Ask which format to export annotations in
+    ShowExportPrompt,
 }
 
 /// Handles key events in Idle state.
 /// This is the unified handler that works the same in both Normal and Diff view modes.
 /// The view_mode parameter is only used for scroll adjustment (diff needs synchronized scroll).
+/// `selected_entry` is the entry of the cursor line's thread that Enter and
+/// Delete act on; Tab and Shift+Tab move it.
 pub fn handle_idle_mode(
     key: KeyEvent,
     lines: &mut [Line],
     cursor_line: &mut usize,
+    selected_entry: &mut usize,
     view_mode: &ViewMode,
     theme: &mut crate::theme::Theme,
     annotation_scroll: &mut usize,
@@ -130,6 +163,22 @@ pub fn handle_idle_mode(
     if matches_ctrl_key(&key, &['y', 'н']) {
         return Ok(IdleModeResult::Redo);
     }
+    // Buffer picker (Ctrl+B): English 'b', Russian 'и'
+    if matches_ctrl_key(&key, &['b', 'и']) {
+        return Ok(IdleModeResult::ShowBufferPicker);
+    }
+    // Export annotations (Ctrl+E): English 'e', Russian 'у'
+    if matches_ctrl_key(&key, &['e', 'у']) {
+        return Ok(IdleModeResult::ShowExportPrompt);
+    }
+    // Next buffer (Alt+. or Alt+>): Russian 'ю' / 'Ю'
+    if matches_alt_key(&key, &['.', '>', 'ю', 'Ю']) {
+        return Ok(IdleModeResult::NextBuffer);
+    }
+    // Previous buffer (Alt+, or Alt+<): Russian 'б' / 'Б'
+    if matches_alt_key(&key, &[',', '<', 'б', 'Б']) {
+        return Ok(IdleModeResult::PrevBuffer);
+    }
     // Next annotation (Ctrl+N): English 'n', Russian 'т'
     if matches_ctrl_key(&key, &['n', 'т']) {
         if let Some(next) = find_next_annotation(lines, *cursor_line) {
@@ -148,19 +197,79 @@ pub fn handle_idle_mode(
         }
         return Ok(IdleModeResult::Continue);
     }
+    // Next / previous open annotation, skipping resolved threads (Alt+N / Alt+P)
+    if matches_alt_key(&key, &['n', 'N', 'т', 'Т', 'p', 'P', 'з', 'З']) {
+        let target = if matches_alt_key(&key, &['n', 'N', 'т', 'Т']) {
+            find_next_open_annotation(lines, *cursor_line)
+        } else {
+            find_prev_open_annotation(lines, *cursor_line)
+        };
+        if let Some(target) = target {
+            *cursor_line = target;
+            *annotation_scroll = 0;
+            adjust_scroll_unified(*cursor_line, scroll_offset, lines, view_mode)?;
+        }
+        return Ok(IdleModeResult::Continue);
+    }
+    // Remove every resolved entry in one undoable step (Alt+K): Russian 'л'
+    if matches_alt_key(&key, &['k', 'K', 'л', 'Л']) {
+        return Ok(match Action::purge_resolved(lines) {
+            Some(action) => IdleModeResult::Action(action),
+            None => IdleModeResult::Continue,
+        });
+    }
+
+    // Reply to the thread (Ctrl+R): English 'r', Russian 'к'
+    if matches_ctrl_key(&key, &['r', 'к']) {
+        *annotation_scroll = 0;
+        return Ok(IdleModeResult::EnterAnnotation {
+            initial_text: String::new(),
+            entry: lines[*cursor_line].annotations.len(),
+            severity: None,
+            span: 1,
+        });
+    }
+
+    let thread_len = lines[*cursor_line].annotations.len();
+    let entry = (*selected_entry).min(thread_len.saturating_sub(1));
+
+    // Toggle the selected entry between open and resolved (Ctrl+K): Russian 'л'
+    if matches_ctrl_key(&key, &['k', 'л']) {
+        if let Some(old) = lines[*cursor_line].annotations.get(entry) {
+            let mut new = old.clone();
+            new.set_resolved(!old.is_resolved());
+            return Ok(IdleModeResult::Action(Action::EditAnnotation {
+                line_index: *cursor_line,
+                entry,
+                old: Some(old.clone()),
+                new: Some(new),
+            }));
+        }
+        return Ok(IdleModeResult::Continue);
+    }
 
     // Non-Ctrl hotkeys use match as before
     match (key.code, key.modifiers) {
-        // Delete annotation (Delete or Backspace key)
+        // Delete the selected annotation entry (Delete or Backspace key)
         (KeyCode::Delete, _) | (KeyCode::Backspace, _) => {
-            if let Some(old_text) = &lines[*cursor_line].annotation {
+            if let Some(old) = lines[*cursor_line].annotations.get(entry) {
                 return Ok(IdleModeResult::Action(Action::EditAnnotation {
                     line_index: *cursor_line,
-                    old_text: Some(old_text.clone()),
-                    new_text: None,
+                    entry,
+                    old: Some(old.clone()),
+                    new: None,
                 }));
             }
         }
+        // Select the next / previous entry of the thread
+        (KeyCode::Tab, _) if thread_len > 1 => {
+            *selected_entry = (entry + 1) % thread_len;
+            *annotation_scroll = 0;
+        }
+        (KeyCode::BackTab, _) if thread_len > 1 => {
+            *selected_entry = (entry + thread_len - 1) % thread_len;
+            *annotation_scroll = 0;
+        }
         // Page Up
         (KeyCode::PageUp, _) | (KeyCode::Up, KeyModifiers::ALT) => {
             let (_, height) = terminal::size()?;
@@ -178,26 +287,27 @@ pub fn handle_idle_mode(
             adjust_scroll_unified(*cursor_line, scroll_offset, lines, view_mode)?;
         }
         // Up arrow
-        (KeyCode::Up, _) => {
-            if *cursor_line > 0 {
-                *cursor_line -= 1;
-                *annotation_scroll = 0;
-                adjust_scroll_unified(*cursor_line, scroll_offset, lines, view_mode)?;
-            }
+        (KeyCode::Up, _) if *cursor_line > 0 => {
+            *cursor_line -= 1;
+            *annotation_scroll = 0;
+            adjust_scroll_unified(*cursor_line, scroll_offset, lines, view_mode)?;
         }
         // Down arrow
-        (KeyCode::Down, _) => {
-            if *cursor_line < lines.len().saturating_sub(1) {
-                *cursor_line += 1;
-                *annotation_scroll = 0;
-                adjust_scroll_unified(*cursor_line, scroll_offset, lines, view_mode)?;
-            }
+        (KeyCode::Down, _) if *cursor_line < lines.len().saturating_sub(1) => {
+            *cursor_line += 1;
+            *annotation_scroll = 0;
+            adjust_scroll_unified(*cursor_line, scroll_offset, lines, view_mode)?;
         }
         // Enter annotation mode
         (KeyCode::Enter, _) => {
-            let initial_text = lines[*cursor_line].annotation.clone().unwrap_or_default();
+            let selected = lines[*cursor_line].annotations.get(entry);
             *annotation_scroll = 0;
-            return Ok(IdleModeResult::EnterAnnotation { initial_text });
+            return Ok(IdleModeResult::EnterAnnotation {
+                initial_text: selected.map(|a| a.text.clone()).unwrap_or_default(),
+                entry,
+                severity: selected.and_then(|a| a.severity()),
+                span: selected.map_or(1, |a| a.span()),
+            });
         }
         // Escape - only meaningful in diff view (exits diff)
         (KeyCode::Esc, _) => {
@@ -248,32 +358,67 @@ pub enum AnnotationModeResult {
     Cancel,
 }
 
+/// True if `key` changes the severity or line range of the entry being
+/// edited in `handle_annotation_input`, which only some marker tags can hold.
+pub fn sets_attributes(key: &KeyEvent) -> bool {
+    match key.code {
+        KeyCode::Tab | KeyCode::BackTab => true,
+        KeyCode::Up | KeyCode::Down => key.modifiers.contains(KeyModifiers::CONTROL),
+        _ => false,
+    }
+}
+
 /// Handles key events in Annotating state.
 /// Does NOT modify editor_state - returns a result that caller interprets.
+/// `entry` is the thread entry being edited; Tab and Shift+Tab move the
+/// severity picker, and Ctrl+Down / Ctrl+Up grow and shrink the number of
+/// lines (`span`) the entry covers.
 pub fn handle_annotation_input(
     key: KeyEvent,
     buffer: &mut String,
     cursor_pos: &mut usize,
     lines: &[Line],
     cursor_line: usize,
+    entry: usize,
+    severity: &mut Option<Severity>,
+    span: &mut usize,
     annotation_scroll: &mut usize,
 ) -> io::Result<AnnotationModeResult> {
     match key.code {
+        KeyCode::Down if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            *span = (*span + 1).min(lines.len() - cursor_line);
+        }
+        KeyCode::Up if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            *span = span.saturating_sub(1).max(1);
+        }
+        // Shift+Enter (where the terminal reports it) or Alt+Enter starts a new line
+        KeyCode::Enter if key.modifiers.intersects(KeyModifiers::SHIFT | KeyModifiers::ALT) => {
+            let byte_idx = buffer.chars().take(*cursor_pos).map(|c| c.len_utf8()).sum();
+            buffer.insert(byte_idx, '\n');
+            *cursor_pos += 1;
+            adjust_annotation_scroll(buffer, *cursor_pos, annotation_scroll)?;
+        }
         KeyCode::Enter => {
-            let old_text = lines[cursor_line].annotation.clone();
-            let new_text = if buffer.is_empty() {
-                None
-            } else {
-                Some(buffer.clone())
-            };
+            let old = lines[cursor_line].annotations.get(entry).cloned();
+            // Trailing blank lines would only add empty marker lines
+            let text = buffer.trim_end_matches('\n');
+            // Editing keeps the entry's other attributes (author, date)
+            let new = (!text.is_empty()).then(|| {
+                let mut annotation = old.clone().unwrap_or_default();
+                annotation.text = text.to_string();
+                annotation.set_severity(*severity);
+                annotation.set_span(*span);
+                annotation
+            });
 
             // Even if nothing changed, treat Enter as "save" (exits annotation mode)
-            if old_text != new_text {
+            if old != new {
                 *annotation_scroll = 0;
                 return Ok(AnnotationModeResult::Save(Action::EditAnnotation {
                     line_index: cursor_line,
-                    old_text,
-                    new_text,
+                    entry,
+                    old,
+                    new,
                 }));
             } else {
                 *annotation_scroll = 0;
@@ -285,6 +430,8 @@ pub fn handle_annotation_input(
             *annotation_scroll = 0;
             return Ok(AnnotationModeResult::Cancel);
         }
+        KeyCode::Tab => *severity = Severity::cycle(*severity, true),
+        KeyCode::BackTab => *severity = Severity::cycle(*severity, false),
         KeyCode::Up => {
             move_cursor_up(buffer, cursor_pos, annotation_scroll)?;
         }
@@ -298,14 +445,12 @@ pub fn handle_annotation_input(
             *cursor_pos += 1;
             adjust_annotation_scroll(buffer, *cursor_pos, annotation_scroll)?;
         }
-        KeyCode::Backspace => {
-            if *cursor_pos > 0 {
-                *cursor_pos -= 1;
-                // Convert character index to byte index for string operations
-                let byte_idx = buffer.chars().take(*cursor_pos).map(|c| c.len_utf8()).sum();
-                buffer.remove(byte_idx);
-                adjust_annotation_scroll(buffer, *cursor_pos, annotation_scroll)?;
-            }
+        KeyCode::Backspace if *cursor_pos > 0 => {
+            *cursor_pos -= 1;
+            // Convert character index to byte index for string operations
+            let byte_idx = buffer.chars().take(*cursor_pos).map(|c| c.len_utf8()).sum();
+            buffer.remove(byte_idx);
+            adjust_annotation_scroll(buffer, *cursor_pos, annotation_scroll)?;
         }
         KeyCode::Left if key.modifiers.contains(KeyModifiers::ALT) => {
             *cursor_pos = find_prev_word_boundary(buffer, *cursor_pos);
@@ -364,19 +509,15 @@ pub fn handle_search_input(
     view_mode: &ViewMode,
 ) -> io::Result<SearchModeResult> {
     match (key.code, key.modifiers) {
-        (KeyCode::Enter, KeyModifiers::SHIFT) => {
+        (KeyCode::Enter, KeyModifiers::SHIFT) if !search_matches.is_empty() => {
             // Shift+Enter: previous match
-            if !search_matches.is_empty() {
-                prev_search_match(search_matches, current_match, cursor_line);
-                adjust_scroll_unified(*cursor_line, scroll_offset, lines, view_mode)?;
-            }
+            prev_search_match(search_matches, current_match, cursor_line);
+            adjust_scroll_unified(*cursor_line, scroll_offset, lines, view_mode)?;
         }
-        (KeyCode::Enter, _) => {
+        (KeyCode::Enter, _) if !search_matches.is_empty() => {
             // Enter: next match
-            if !search_matches.is_empty() {
-                next_search_match(search_matches, current_match, cursor_line);
-                adjust_scroll_unified(*cursor_line, scroll_offset, lines, view_mode)?;
-            }
+            next_search_match(search_matches, current_match, cursor_line);
+            adjust_scroll_unified(*cursor_line, scroll_offset, lines, view_mode)?;
         }
         (KeyCode::Esc, _) => {
             search_matches.clear();
@@ -389,13 +530,11 @@ pub fn handle_search_input(
             perform_search(query, lines, search_matches, current_match, cursor_line);
             adjust_scroll_unified(*cursor_line, scroll_offset, lines, view_mode)?;
         }
-        (KeyCode::Backspace, _) => {
-            if *cursor_pos > 0 {
-                *cursor_pos -= 1;
-                query.remove(*cursor_pos);
-                perform_search(query, lines, search_matches, current_match, cursor_line);
-                adjust_scroll_unified(*cursor_line, scroll_offset, lines, view_mode)?;
-            }
+        (KeyCode::Backspace, _) if *cursor_pos > 0 => {
+            *cursor_pos -= 1;
+            query.remove(*cursor_pos);
+            perform_search(query, lines, search_matches, current_match, cursor_line);
+            adjust_scroll_unified(*cursor_line, scroll_offset, lines, view_mode)?;
         }
         _ => {}
     }
@@ -429,6 +568,79 @@ pub fn handle_quit_prompt(key: KeyEvent) -> QuitPromptResult {
     QuitPromptResult::Continue
 }
 
+// ============================================================================
+// Export Prompt Handler
+// ============================================================================
+
+pub enum ExportPromptResult {
+    Export(ExportFormat),
+    Cancel,
+    Continue,
+}
+
+pub fn handle_export_prompt(key: KeyEvent) -> ExportPromptResult {
+    // JSON: English 'j', Russian 'о' (QWERTY J key position)
+    if matches_char(&key.code, &['j', 'о']) {
+        return ExportPromptResult::Export(ExportFormat::Json);
+    }
+    // Markdown: English 'm', Russian 'ь' (QWERTY M key position)
+    if matches_char(&key.code, &['m', 'ь']) {
+        return ExportPromptResult::Export(ExportFormat::Markdown);
+    }
+    // SARIF: English 's', Russian 'ы' (QWERTY S key position)
+    if matches_char(&key.code, &['s', 'ы']) {
+        return ExportPromptResult::Export(ExportFormat::Sarif);
+    }
+    if key.code == KeyCode::Esc || matches_ctrl_key(&key, &['e', 'у']) {
+        return ExportPromptResult::Cancel;
+    }
+    ExportPromptResult::Continue
+}
+
+// ============================================================================
+// Buffer Picker Handler
+// ============================================================================
+
+pub enum BufferPickerResult {
+    /// Switch to the buffer at this index
+    Select(usize),
+    Cancel,
+    Continue,
+}
+
+/// Handles key events in the buffer picker overlay.
+/// Up/Down move the selection, Enter switches, digits 1-9 jump directly.
+pub fn handle_buffer_picker(
+    key: KeyEvent,
+    selected: &mut usize,
+    buffer_count: usize,
+) -> BufferPickerResult {
+    match key.code {
+        KeyCode::Up => {
+            *selected = selected.saturating_sub(1);
+        }
+        KeyCode::Down => {
+            *selected = (*selected + 1).min(buffer_count.saturating_sub(1));
+        }
+        KeyCode::Home => *selected = 0,
+        KeyCode::End => *selected = buffer_count.saturating_sub(1),
+        KeyCode::Enter => return BufferPickerResult::Select(*selected),
+        KeyCode::Esc => return BufferPickerResult::Cancel,
+        KeyCode::Char(c) if c.is_ascii_digit() && c != '0' => {
+            let index = c as usize - '1' as usize;
+            if index < buffer_count {
+                return BufferPickerResult::Select(index);
+            }
+        }
+        _ => {
+            if matches_ctrl_key(&key, &['b', 'и']) {
+                return BufferPickerResult::Cancel;
+            }
+        }
+    }
+    BufferPickerResult::Continue
+}
+
 // ============================================================================
 // Helper Functions
 // ============================================================================
@@ -527,7 +739,7 @@ fn adjust_annotation_scroll(
 mod tests {
     use super::*;
     use crate::text::wrap_text;
-    use crate::models::Line;
+    use crate::models::{Annotation, Line};
     use crate::diff::{DiffResult, DiffLine, LineChange};
     use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
@@ -667,14 +879,16 @@ mod tests {
     #[test]
     fn test_idle_mode_jump_to_next_annotation() {
         let mut lines = vec![
-            Line { content: "0".to_string(), annotation: None },
-            Line { content: "1".to_string(), annotation: Some("a1".to_string()) },
-            Line { content: "2".to_string(), annotation: None },
-            Line { content: "3".to_string(), annotation: Some("a2".to_string()) },
-            Line { content: "4".to_string(), annotation: None },
+            Line { content: "0".to_string(), annotations: Vec::new() },
+            Line { content: "1".to_string(), annotations: vec!["a1".into()] },
+            Line { content: "2".to_string(), annotations: Vec::new() },
+            Line { content: "3".to_string(), annotations: vec!["a2".into()] },
+            Line { content: "4".to_string(), annotations: Vec::new() },
         ];
 
         let mut cursor_line = 0;
+
+        let mut selected_entry = 0;
         let mut scroll_offset = 0;
         let mut annotation_scroll = 0;
         let view_mode = ViewMode::Normal;
@@ -685,6 +899,7 @@ mod tests {
             KeyEvent::new(KeyCode::Char('n'), KeyModifiers::CONTROL),
             &mut lines,
             &mut cursor_line,
+            &mut selected_entry,
             &view_mode,
             &mut theme,
             &mut annotation_scroll,
@@ -697,6 +912,7 @@ mod tests {
             KeyEvent::new(KeyCode::Char('n'), KeyModifiers::CONTROL),
             &mut lines,
             &mut cursor_line,
+            &mut selected_entry,
             &view_mode,
             &mut theme,
             &mut annotation_scroll,
@@ -709,6 +925,7 @@ mod tests {
             KeyEvent::new(KeyCode::Char('n'), KeyModifiers::CONTROL),
             &mut lines,
             &mut cursor_line,
+            &mut selected_entry,
             &view_mode,
             &mut theme,
             &mut annotation_scroll,
@@ -720,14 +937,16 @@ mod tests {
     #[test]
     fn test_idle_mode_jump_to_prev_annotation() {
         let mut lines = vec![
-            Line { content: "0".to_string(), annotation: None },
-            Line { content: "1".to_string(), annotation: Some("a1".to_string()) },
-            Line { content: "2".to_string(), annotation: None },
-            Line { content: "3".to_string(), annotation: Some("a2".to_string()) },
-            Line { content: "4".to_string(), annotation: None },
+            Line { content: "0".to_string(), annotations: Vec::new() },
+            Line { content: "1".to_string(), annotations: vec!["a1".into()] },
+            Line { content: "2".to_string(), annotations: Vec::new() },
+            Line { content: "3".to_string(), annotations: vec!["a2".into()] },
+            Line { content: "4".to_string(), annotations: Vec::new() },
         ];
 
         let mut cursor_line = 3;
+
+        let mut selected_entry = 0;
         let mut scroll_offset = 0;
         let mut annotation_scroll = 0;
         let view_mode = ViewMode::Normal;
@@ -738,6 +957,7 @@ mod tests {
             KeyEvent::new(KeyCode::Char('p'), KeyModifiers::CONTROL),
             &mut lines,
             &mut cursor_line,
+            &mut selected_entry,
             &view_mode,
             &mut theme,
             &mut annotation_scroll,
@@ -749,9 +969,10 @@ mod tests {
     #[test]
     fn test_idle_mode_ctrl_x_shows_quit_prompt() {
         let mut lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut cursor_line = 0;
+        let mut selected_entry = 0;
         let view_mode = ViewMode::Normal;
         let mut theme = crate::theme::Theme::Dark;
         let mut annotation_scroll = 0;
@@ -761,6 +982,7 @@ mod tests {
             KeyEvent::new(KeyCode::Char('x'), KeyModifiers::CONTROL),
             &mut lines,
             &mut cursor_line,
+            &mut selected_entry,
             &view_mode,
             &mut theme,
             &mut annotation_scroll,
@@ -773,9 +995,10 @@ mod tests {
     #[test]
     fn test_idle_mode_ctrl_g_shows_help() {
         let mut lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut cursor_line = 0;
+        let mut selected_entry = 0;
         let view_mode = ViewMode::Normal;
         let mut theme = crate::theme::Theme::Dark;
         let mut annotation_scroll = 0;
@@ -785,6 +1008,7 @@ mod tests {
             KeyEvent::new(KeyCode::Char('g'), KeyModifiers::CONTROL),
             &mut lines,
             &mut cursor_line,
+            &mut selected_entry,
             &view_mode,
             &mut theme,
             &mut annotation_scroll,
@@ -797,9 +1021,10 @@ mod tests {
     #[test]
     fn test_idle_mode_ctrl_w_enters_search() {
         let mut lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut cursor_line = 0;
+        let mut selected_entry = 0;
         let view_mode = ViewMode::Normal;
         let mut theme = crate::theme::Theme::Dark;
         let mut annotation_scroll = 0;
@@ -809,6 +1034,7 @@ mod tests {
             KeyEvent::new(KeyCode::Char('w'), KeyModifiers::CONTROL),
             &mut lines,
             &mut cursor_line,
+            &mut selected_entry,
             &view_mode,
             &mut theme,
             &mut annotation_scroll,
@@ -821,9 +1047,10 @@ mod tests {
     #[test]
     fn test_idle_mode_enter_enters_annotation() {
         let mut lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut cursor_line = 0;
+        let mut selected_entry = 0;
         let view_mode = ViewMode::Normal;
         let mut theme = crate::theme::Theme::Dark;
         let mut annotation_scroll = 0;
@@ -833,6 +1060,7 @@ mod tests {
             KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE),
             &mut lines,
             &mut cursor_line,
+            &mut selected_entry,
             &view_mode,
             &mut theme,
             &mut annotation_scroll,
@@ -845,9 +1073,10 @@ mod tests {
     #[test]
     fn test_idle_mode_delete_removes_annotation() {
         let mut lines = vec![
-            Line { content: "line1".to_string(), annotation: Some("test".to_string()) },
+            Line { content: "line1".to_string(), annotations: vec!["test".into()] },
         ];
         let mut cursor_line = 0;
+        let mut selected_entry = 0;
         let view_mode = ViewMode::Normal;
         let mut theme = crate::theme::Theme::Dark;
         let mut annotation_scroll = 0;
@@ -857,6 +1086,7 @@ mod tests {
             KeyEvent::new(KeyCode::Delete, KeyModifiers::NONE),
             &mut lines,
             &mut cursor_line,
+            &mut selected_entry,
             &view_mode,
             &mut theme,
             &mut annotation_scroll,
@@ -866,12 +1096,206 @@ mod tests {
         assert!(matches!(result, IdleModeResult::Action(Action::EditAnnotation { .. })));
     }
 
+    #[test]
+    fn test_idle_mode_thread_entries() {
+        let mut lines = vec![
+            Line { content: "line1".to_string(), annotations: vec!["why?".into(), "because".into()] },
+        ];
+        let mut cursor_line = 0;
+        let mut selected_entry = 0;
+        let view_mode = ViewMode::Normal;
+        let mut theme = crate::theme::Theme::Dark;
+        let mut annotation_scroll = 0;
+        let mut scroll_offset = 0;
+        let mut press = |key: KeyEvent, selected_entry: &mut usize| {
+            handle_idle_mode(
+                key,
+                &mut lines,
+                &mut cursor_line,
+                selected_entry,
+                &view_mode,
+                &mut theme,
+                &mut annotation_scroll,
+                &mut scroll_offset,
+            )
+            .unwrap()
+        };
+
+        // Tab cycles through the thread
+        press(KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE), &mut selected_entry);
+        assert_eq!(selected_entry, 1);
+        press(KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE), &mut selected_entry);
+        assert_eq!(selected_entry, 0);
+        press(KeyEvent::new(KeyCode::BackTab, KeyModifiers::SHIFT), &mut selected_entry);
+        assert_eq!(selected_entry, 1);
+
+        // Enter edits and Delete removes only the selected entry
+        let IdleModeResult::EnterAnnotation { initial_text, entry, .. } =
+            press(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE), &mut selected_entry)
+        else {
+            panic!("Expected EnterAnnotation");
+        };
+        assert_eq!((initial_text.as_str(), entry), ("because", 1));
+        let IdleModeResult::Action(Action::EditAnnotation { entry, old, new, .. }) =
+            press(KeyEvent::new(KeyCode::Delete, KeyModifiers::NONE), &mut selected_entry)
+        else {
+            panic!("Expected EditAnnotation");
+        };
+        assert_eq!((entry, old.as_ref().map(|a| a.text.as_str()), new), (1, Some("because"), None));
+
+        // Ctrl+R starts a new entry after the last one
+        let IdleModeResult::EnterAnnotation { initial_text, entry, .. } =
+            press(KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL), &mut selected_entry)
+        else {
+            panic!("Expected EnterAnnotation");
+        };
+        assert_eq!((initial_text.as_str(), entry), ("", 2));
+    }
+
+    #[test]
+    fn test_idle_mode_resolve_skip_and_purge() {
+        let mut lines = vec![
+            Line { content: "a".to_string(), annotations: vec!["why?".into(), "because".into()] },
+            Line { content: "b".to_string(), annotations: vec!["done".into()] },
+            Line { content: "c".to_string(), annotations: vec!["open".into()] },
+        ];
+        lines[1].annotations[0].set_resolved(true);
+        let mut cursor_line = 0;
+        let mut selected_entry = 1;
+        let view_mode = ViewMode::Normal;
+        let mut theme = crate::theme::Theme::Dark;
+        let mut annotation_scroll = 0;
+        let mut scroll_offset = 0;
+        let mut press = |key: KeyEvent, lines: &mut Vec<Line>, cursor_line: &mut usize| {
+            handle_idle_mode(
+                key,
+                lines,
+                cursor_line,
+                &mut selected_entry,
+                &view_mode,
+                &mut theme,
+                &mut annotation_scroll,
+                &mut scroll_offset,
+            )
+            .unwrap()
+        };
+
+        // Ctrl+K toggles the selected entry only
+        let IdleModeResult::Action(action) =
+            press(KeyEvent::new(KeyCode::Char('k'), KeyModifiers::CONTROL), &mut lines, &mut cursor_line)
+        else {
+            panic!("Expected Action");
+        };
+        action.apply(&mut lines);
+        assert!(!lines[0].annotations[0].is_resolved());
+        assert!(lines[0].annotations[1].is_resolved());
+
+        // Alt+N skips the fully resolved line, Ctrl+N doesn't
+        press(KeyEvent::new(KeyCode::Char('n'), KeyModifiers::ALT), &mut lines, &mut cursor_line);
+        assert_eq!(cursor_line, 2);
+        press(KeyEvent::new(KeyCode::Char('p'), KeyModifiers::ALT), &mut lines, &mut cursor_line);
+        assert_eq!(cursor_line, 0);
+        press(KeyEvent::new(KeyCode::Char('n'), KeyModifiers::CONTROL), &mut lines, &mut cursor_line);
+        assert_eq!(cursor_line, 1);
+
+        // Alt+K removes every resolved entry as one undoable action
+        let IdleModeResult::Action(purge) =
+            press(KeyEvent::new(KeyCode::Char('k'), KeyModifiers::ALT), &mut lines, &mut cursor_line)
+        else {
+            panic!("Expected Action");
+        };
+        let before = lines.clone();
+        purge.apply(&mut lines);
+        assert_eq!(lines[0].annotations, vec!["why?"]);
+        assert!(lines[1].annotations.is_empty());
+        assert!(matches!(
+            press(KeyEvent::new(KeyCode::Char('k'), KeyModifiers::ALT), &mut lines, &mut cursor_line),
+            IdleModeResult::Continue
+        ));
+        purge.undo(&mut lines);
+        assert_eq!(lines, before);
+    }
+
+    #[test]
+    fn test_annotation_input_saves_reply_as_new_entry() {
+        let lines = vec![
+            Line { content: "line1".to_string(), annotations: vec!["why?".into()] },
+        ];
+        let mut buffer = "because".to_string();
+        let mut cursor_pos = buffer.len();
+        let mut annotation_scroll = 0;
+
+        let result = handle_annotation_input(
+            KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE),
+            &mut buffer,
+            &mut cursor_pos,
+            &lines,
+            0,
+            1,
+            &mut None,
+            &mut 1,
+            &mut annotation_scroll,
+        ).unwrap();
+
+        let AnnotationModeResult::Save(Action::EditAnnotation { entry, old, new, .. }) = result else {
+            panic!("Expected Save");
+        };
+        assert_eq!((entry, old, new.as_ref().map(|a| a.text.as_str())), (1, None, Some("because")));
+    }
+
+    #[test]
+    fn test_annotation_input_tab_sets_severity_and_keeps_attributes() {
+        let mut existing = Annotation::new("check");
+        existing.set(crate::models::AUTHOR_KEY, Some("alice"));
+        let lines = vec![
+            Line { content: "line1".to_string(), annotations: vec![existing] },
+        ];
+        let mut buffer = "check this".to_string();
+        let mut cursor_pos = buffer.len();
+        let mut severity = None;
+        let mut annotation_scroll = 0;
+        let mut press = |key: KeyEvent, severity: &mut Option<Severity>| {
+            handle_annotation_input(key, &mut buffer, &mut cursor_pos, &lines, 0, 0, severity, &mut 1, &mut annotation_scroll)
+                .unwrap()
+        };
+
+        press(KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE), &mut severity);
+        assert_eq!(severity, Some(Severity::Blocker));
+        press(KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE), &mut severity);
+        assert_eq!(severity, Some(Severity::Major));
+        press(KeyEvent::new(KeyCode::BackTab, KeyModifiers::SHIFT), &mut severity);
+        press(KeyEvent::new(KeyCode::BackTab, KeyModifiers::SHIFT), &mut severity);
+        assert_eq!(severity, None);
+        press(KeyEvent::new(KeyCode::BackTab, KeyModifiers::SHIFT), &mut severity);
+        assert_eq!(severity, Some(Severity::Nit));
+
+        let AnnotationModeResult::Save(Action::EditAnnotation { new: Some(new), .. }) =
+            press(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE), &mut severity)
+        else {
+            panic!("Expected Save");
+        };
+        assert_eq!(new.text, "check this");
+        assert_eq!(new.severity(), Some(Severity::Nit));
+        assert_eq!(new.author(), Some("alice"));
+    }
+
+    #[test]
+    fn test_sets_attributes_covers_severity_and_range_keys() {
+        assert!(sets_attributes(&KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE)));
+        assert!(sets_attributes(&KeyEvent::new(KeyCode::BackTab, KeyModifiers::SHIFT)));
+        assert!(sets_attributes(&KeyEvent::new(KeyCode::Down, KeyModifiers::CONTROL)));
+        assert!(sets_attributes(&KeyEvent::new(KeyCode::Up, KeyModifiers::CONTROL)));
+        assert!(!sets_attributes(&KeyEvent::new(KeyCode::Up, KeyModifiers::NONE)));
+        assert!(!sets_attributes(&KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE)));
+    }
+
     #[test]
     fn test_idle_mode_ctrl_d_toggles_diff() {
         let mut lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut cursor_line = 0;
+        let mut selected_entry = 0;
         let view_mode = ViewMode::Normal;
         let mut theme = crate::theme::Theme::Dark;
         let mut annotation_scroll = 0;
@@ -881,6 +1305,7 @@ mod tests {
             KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL),
             &mut lines,
             &mut cursor_line,
+            &mut selected_entry,
             &view_mode,
             &mut theme,
             &mut annotation_scroll,
@@ -893,9 +1318,10 @@ mod tests {
     #[test]
     fn test_idle_mode_ctrl_z_undo() {
         let mut lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut cursor_line = 0;
+        let mut selected_entry = 0;
         let view_mode = ViewMode::Normal;
         let mut theme = crate::theme::Theme::Dark;
         let mut annotation_scroll = 0;
@@ -905,6 +1331,7 @@ mod tests {
             KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL),
             &mut lines,
             &mut cursor_line,
+            &mut selected_entry,
             &view_mode,
             &mut theme,
             &mut annotation_scroll,
@@ -917,9 +1344,10 @@ mod tests {
     #[test]
     fn test_idle_mode_ctrl_y_redo() {
         let mut lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut cursor_line = 0;
+        let mut selected_entry = 0;
         let view_mode = ViewMode::Normal;
         let mut theme = crate::theme::Theme::Dark;
         let mut annotation_scroll = 0;
@@ -929,6 +1357,7 @@ mod tests {
             KeyEvent::new(KeyCode::Char('y'), KeyModifiers::CONTROL),
             &mut lines,
             &mut cursor_line,
+            &mut selected_entry,
             &view_mode,
             &mut theme,
             &mut annotation_scroll,
@@ -945,9 +1374,10 @@ mod tests {
     #[test]
     fn test_diff_view_ctrl_x_shows_quit_prompt() {
         let mut lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut cursor_line = 0;
+        let mut selected_entry = 0;
         let view_mode = ViewMode::Diff {
             diff_result: DiffResult {
                 lines: vec![DiffLine {
@@ -964,6 +1394,7 @@ mod tests {
             KeyEvent::new(KeyCode::Char('x'), KeyModifiers::CONTROL),
             &mut lines,
             &mut cursor_line,
+            &mut selected_entry,
             &view_mode,
             &mut theme,
             &mut annotation_scroll,
@@ -976,9 +1407,10 @@ mod tests {
     #[test]
     fn test_diff_view_ctrl_g_shows_help() {
         let mut lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut cursor_line = 0;
+        let mut selected_entry = 0;
         let view_mode = ViewMode::Diff {
             diff_result: DiffResult {
                 lines: vec![DiffLine {
@@ -995,6 +1427,7 @@ mod tests {
             KeyEvent::new(KeyCode::Char('g'), KeyModifiers::CONTROL),
             &mut lines,
             &mut cursor_line,
+            &mut selected_entry,
             &view_mode,
             &mut theme,
             &mut annotation_scroll,
@@ -1007,9 +1440,10 @@ mod tests {
     #[test]
     fn test_diff_view_delete_removes_annotation() {
         let mut lines = vec![
-            Line { content: "line1".to_string(), annotation: Some("test".to_string()) },
+            Line { content: "line1".to_string(), annotations: vec!["test".into()] },
         ];
         let mut cursor_line = 0;
+        let mut selected_entry = 0;
         let view_mode = ViewMode::Diff {
             diff_result: DiffResult {
                 lines: vec![DiffLine {
@@ -1026,6 +1460,7 @@ mod tests {
             KeyEvent::new(KeyCode::Delete, KeyModifiers::NONE),
             &mut lines,
             &mut cursor_line,
+            &mut selected_entry,
             &view_mode,
             &mut theme,
             &mut annotation_scroll,
@@ -1038,9 +1473,10 @@ mod tests {
     #[test]
     fn test_diff_view_enter_enters_annotation() {
         let mut lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut cursor_line = 0;
+        let mut selected_entry = 0;
         let view_mode = ViewMode::Diff {
             diff_result: DiffResult {
                 lines: vec![DiffLine {
@@ -1057,6 +1493,7 @@ mod tests {
             KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE),
             &mut lines,
             &mut cursor_line,
+            &mut selected_entry,
             &view_mode,
             &mut theme,
             &mut annotation_scroll,
@@ -1069,9 +1506,10 @@ mod tests {
     #[test]
     fn test_diff_view_esc_exits_diff() {
         let mut lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut cursor_line = 0;
+        let mut selected_entry = 0;
         let view_mode = ViewMode::Diff {
             diff_result: DiffResult {
                 lines: vec![DiffLine {
@@ -1088,6 +1526,7 @@ mod tests {
             KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE),
             &mut lines,
             &mut cursor_line,
+            &mut selected_entry,
             &view_mode,
             &mut theme,
             &mut annotation_scroll,
@@ -1100,9 +1539,10 @@ mod tests {
     #[test]
     fn test_diff_view_ctrl_d_toggles_diff() {
         let mut lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut cursor_line = 0;
+        let mut selected_entry = 0;
         let view_mode = ViewMode::Diff {
             diff_result: DiffResult {
                 lines: vec![DiffLine {
@@ -1119,6 +1559,7 @@ mod tests {
             KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL),
             &mut lines,
             &mut cursor_line,
+            &mut selected_entry,
             &view_mode,
             &mut theme,
             &mut annotation_scroll,
@@ -1128,6 +1569,130 @@ mod tests {
         assert!(matches!(result, IdleModeResult::ToggleDiffView));
     }
 
+    // ========================================================================
+    // Buffer Switching Tests
+    // ========================================================================
+
+    fn idle_result(key: KeyEvent) -> IdleModeResult {
+        let mut lines = vec![Line { content: "0".to_string(), annotations: Vec::new() }];
+        let mut cursor_line = 0;
+        let mut selected_entry = 0;
+        let mut scroll_offset = 0;
+        let mut annotation_scroll = 0;
+        let mut theme = crate::theme::Theme::Dark;
+        handle_idle_mode(
+            key,
+            &mut lines,
+            &mut cursor_line,
+            &mut selected_entry,
+            &ViewMode::Normal,
+            &mut theme,
+            &mut annotation_scroll,
+            &mut scroll_offset,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_idle_mode_ctrl_b_shows_buffer_picker() {
+        let result = idle_result(KeyEvent::new(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        assert!(matches!(result, IdleModeResult::ShowBufferPicker));
+
+        let result = idle_result(KeyEvent::new(KeyCode::Char('и'), KeyModifiers::CONTROL));
+        assert!(matches!(result, IdleModeResult::ShowBufferPicker));
+    }
+
+    #[test]
+    fn test_idle_mode_alt_period_next_buffer() {
+        let result = idle_result(KeyEvent::new(KeyCode::Char('.'), KeyModifiers::ALT));
+        assert!(matches!(result, IdleModeResult::NextBuffer));
+
+        let result = idle_result(KeyEvent::new(
+            KeyCode::Char('>'),
+            KeyModifiers::ALT | KeyModifiers::SHIFT,
+        ));
+        assert!(matches!(result, IdleModeResult::NextBuffer));
+    }
+
+    #[test]
+    fn test_idle_mode_alt_comma_prev_buffer() {
+        let result = idle_result(KeyEvent::new(KeyCode::Char(','), KeyModifiers::ALT));
+        assert!(matches!(result, IdleModeResult::PrevBuffer));
+
+        let result = idle_result(KeyEvent::new(KeyCode::Char('б'), KeyModifiers::ALT));
+        assert!(matches!(result, IdleModeResult::PrevBuffer));
+    }
+
+    #[test]
+    fn test_buffer_picker_navigation() {
+        let mut selected = 0;
+
+        handle_buffer_picker(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE), &mut selected, 3);
+        assert_eq!(selected, 1);
+        handle_buffer_picker(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE), &mut selected, 3);
+        handle_buffer_picker(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE), &mut selected, 3);
+        assert_eq!(selected, 2, "selection stops at the last buffer");
+        handle_buffer_picker(KeyEvent::new(KeyCode::Home, KeyModifiers::NONE), &mut selected, 3);
+        assert_eq!(selected, 0);
+        handle_buffer_picker(KeyEvent::new(KeyCode::Up, KeyModifiers::NONE), &mut selected, 3);
+        assert_eq!(selected, 0);
+    }
+
+    #[test]
+    fn test_buffer_picker_enter_selects() {
+        let mut selected = 1;
+        let result = handle_buffer_picker(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE), &mut selected, 3);
+        assert!(matches!(result, BufferPickerResult::Select(1)));
+    }
+
+    #[test]
+    fn test_buffer_picker_digit_jumps() {
+        let mut selected = 0;
+        let result = handle_buffer_picker(KeyEvent::new(KeyCode::Char('3'), KeyModifiers::NONE), &mut selected, 3);
+        assert!(matches!(result, BufferPickerResult::Select(2)));
+
+        let result = handle_buffer_picker(KeyEvent::new(KeyCode::Char('4'), KeyModifiers::NONE), &mut selected, 3);
+        assert!(matches!(result, BufferPickerResult::Continue));
+    }
+
+    #[test]
+    fn test_buffer_picker_esc_cancels() {
+        let mut selected = 0;
+        let result = handle_buffer_picker(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE), &mut selected, 3);
+        assert!(matches!(result, BufferPickerResult::Cancel));
+    }
+
+    // ========================================================================
+    // Export Prompt Tests
+    // ========================================================================
+
+    #[test]
+    fn test_idle_mode_ctrl_e_shows_export_prompt() {
+        let result = idle_result(KeyEvent::new(KeyCode::Char('e'), KeyModifiers::CONTROL));
+        assert!(matches!(result, IdleModeResult::ShowExportPrompt));
+
+        let result = idle_result(KeyEvent::new(KeyCode::Char('у'), KeyModifiers::CONTROL));
+        assert!(matches!(result, IdleModeResult::ShowExportPrompt));
+    }
+
+    #[test]
+    fn test_export_prompt_formats() {
+        let result = handle_export_prompt(KeyEvent::new(KeyCode::Char('j'), KeyModifiers::NONE));
+        assert!(matches!(result, ExportPromptResult::Export(ExportFormat::Json)));
+        let result = handle_export_prompt(KeyEvent::new(KeyCode::Char('M'), KeyModifiers::SHIFT));
+        assert!(matches!(result, ExportPromptResult::Export(ExportFormat::Markdown)));
+        let result = handle_export_prompt(KeyEvent::new(KeyCode::Char('ы'), KeyModifiers::NONE));
+        assert!(matches!(result, ExportPromptResult::Export(ExportFormat::Sarif)));
+    }
+
+    #[test]
+    fn test_export_prompt_esc_cancels() {
+        let result = handle_export_prompt(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        assert!(matches!(result, ExportPromptResult::Cancel));
+        let result = handle_export_prompt(KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE));
+        assert!(matches!(result, ExportPromptResult::Continue));
+    }
+
     // ========================================================================
     // Quit Prompt Tests
     // ========================================================================
@@ -1202,7 +1767,7 @@ mod tests {
     #[test]
     fn test_annotation_input_enter_saves() {
         let lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut buffer = "new annotation".to_string();
         let mut cursor_pos = buffer.len();
@@ -1214,16 +1779,65 @@ mod tests {
             &mut cursor_pos,
             &lines,
             0,
+            0,
+            &mut None,
+            &mut 1,
             &mut annotation_scroll,
         ).unwrap();
 
         assert!(matches!(result, AnnotationModeResult::Save(_)));
     }
 
+    #[test]
+    fn test_annotation_input_alt_enter_inserts_newline() {
+        let lines = vec![
+            Line { content: "line1".to_string(), annotations: Vec::new() },
+        ];
+        let mut buffer = "first".to_string();
+        let mut cursor_pos = buffer.len();
+        let mut annotation_scroll = 0;
+
+        for modifiers in [KeyModifiers::ALT, KeyModifiers::SHIFT] {
+            let result = handle_annotation_input(
+                KeyEvent::new(KeyCode::Enter, modifiers),
+                &mut buffer,
+                &mut cursor_pos,
+                &lines,
+                0,
+                0,
+                &mut None,
+                &mut 1,
+                &mut annotation_scroll,
+            ).unwrap();
+            assert!(matches!(result, AnnotationModeResult::Continue));
+        }
+        assert_eq!(buffer, "first\n\n");
+        assert_eq!(cursor_pos, 7);
+
+        // Saving drops the trailing blank lines
+        let result = handle_annotation_input(
+            KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE),
+            &mut buffer,
+            &mut cursor_pos,
+            &lines,
+            0,
+            0,
+            &mut None,
+            &mut 1,
+            &mut annotation_scroll,
+        ).unwrap();
+        match result {
+            AnnotationModeResult::Save(Action::EditAnnotation { new, .. }) => {
+                assert_eq!(new.as_ref().map(|a| a.text.as_str()), Some("first"));
+            }
+            _ => panic!("Expected Save"),
+        }
+    }
+
     #[test]
     fn test_annotation_input_esc_cancels() {
         let lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut buffer = "new annotation".to_string();
         let mut cursor_pos = buffer.len();
@@ -1235,6 +1849,9 @@ mod tests {
             &mut cursor_pos,
             &lines,
             0,
+            0,
+            &mut None,
+            &mut 1,
             &mut annotation_scroll,
         ).unwrap();
 
@@ -1244,7 +1861,7 @@ mod tests {
     #[test]
     fn test_annotation_input_char_appends() {
         let lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut buffer = "test".to_string();
         let mut cursor_pos = buffer.len();
@@ -1256,6 +1873,9 @@ mod tests {
             &mut cursor_pos,
             &lines,
             0,
+            0,
+            &mut None,
+            &mut 1,
             &mut annotation_scroll,
         ).unwrap();
 
@@ -1267,7 +1887,7 @@ mod tests {
     #[test]
     fn test_annotation_input_backspace_removes() {
         let lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut buffer = "test".to_string();
         let mut cursor_pos = buffer.len();
@@ -1279,6 +1899,9 @@ mod tests {
             &mut cursor_pos,
             &lines,
             0,
+            0,
+            &mut None,
+            &mut 1,
             &mut annotation_scroll,
         ).unwrap();
 
@@ -1291,7 +1914,7 @@ mod tests {
     fn test_annotation_input_cyrillic_char() {
         // Test inserting Cyrillic character (multi-byte UTF-8)
         let lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut buffer = "Hello".to_string();
         let mut cursor_pos = buffer.chars().count(); // 5 characters
@@ -1304,6 +1927,9 @@ mod tests {
             &mut cursor_pos,
             &lines,
             0,
+            0,
+            &mut None,
+            &mut 1,
             &mut annotation_scroll,
         ).unwrap();
 
@@ -1316,7 +1942,7 @@ mod tests {
     fn test_annotation_input_emoji() {
         // Test inserting emoji (4 bytes in UTF-8)
         let lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut buffer = "Test".to_string();
         let mut cursor_pos = buffer.chars().count(); // 4 characters
@@ -1329,6 +1955,9 @@ mod tests {
             &mut cursor_pos,
             &lines,
             0,
+            0,
+            &mut None,
+            &mut 1,
             &mut annotation_scroll,
         ).unwrap();
 
@@ -1341,7 +1970,7 @@ mod tests {
     fn test_annotation_input_backspace_cyrillic() {
         // Test backspace with Cyrillic character
         let lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut buffer = "Helloв".to_string(); // 'в' is 2 bytes
         let mut cursor_pos = buffer.chars().count(); // 6 characters
@@ -1353,6 +1982,9 @@ mod tests {
             &mut cursor_pos,
             &lines,
             0,
+            0,
+            &mut None,
+            &mut 1,
             &mut annotation_scroll,
         ).unwrap();
 
@@ -1365,7 +1997,7 @@ mod tests {
     fn test_annotation_input_mixed_multibyte() {
         // Test inserting multiple multi-byte characters in sequence
         let lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut buffer = String::new();
         let mut cursor_pos = 0;
@@ -1379,6 +2011,9 @@ mod tests {
                 &mut cursor_pos,
                 &lines,
                 0,
+                0,
+                &mut None,
+                &mut 1,
                 &mut annotation_scroll,
             ).unwrap();
         }
@@ -1392,7 +2027,7 @@ mod tests {
     fn test_annotation_input_right_arrow_with_cyrillic() {
         // Test Right arrow key with Cyrillic text
         let lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut buffer = "Привет".to_string(); // 6 chars, 12 bytes
         let mut cursor_pos = 0;
@@ -1405,6 +2040,9 @@ mod tests {
             &mut cursor_pos,
             &lines,
             0,
+            0,
+            &mut None,
+            &mut 1,
             &mut annotation_scroll,
         ).unwrap();
 
@@ -1418,6 +2056,9 @@ mod tests {
             &mut cursor_pos,
             &lines,
             0,
+            0,
+            &mut None,
+            &mut 1,
             &mut annotation_scroll,
         ).unwrap();
 
@@ -1428,7 +2069,7 @@ mod tests {
     fn test_annotation_input_insert_middle_cyrillic() {
         // Test inserting character in middle of Cyrillic text
         let lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut buffer = "При".to_string(); // 3 Cyrillic chars
         let mut cursor_pos = 2; // After "Пр"
@@ -1441,6 +2082,9 @@ mod tests {
             &mut cursor_pos,
             &lines,
             0,
+            0,
+            &mut None,
+            &mut 1,
             &mut annotation_scroll,
         ).unwrap();
 
@@ -1456,7 +2100,7 @@ mod tests {
     #[test]
     fn test_annotation_input_alt_right_basic() {
         let lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut buffer = "hello world foo".to_string();
         let mut cursor_pos = 0;
@@ -1469,6 +2113,9 @@ mod tests {
             &mut cursor_pos,
             &lines,
             0,
+            0,
+            &mut None,
+            &mut 1,
             &mut annotation_scroll,
         ).unwrap();
 
@@ -1479,7 +2126,7 @@ mod tests {
     #[test]
     fn test_annotation_input_alt_left_basic() {
         let lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut buffer = "hello world foo".to_string();
         let mut cursor_pos = 15; // At end
@@ -1492,6 +2139,9 @@ mod tests {
             &mut cursor_pos,
             &lines,
             0,
+            0,
+            &mut None,
+            &mut 1,
             &mut annotation_scroll,
         ).unwrap();
 
@@ -1502,7 +2152,7 @@ mod tests {
     #[test]
     fn test_annotation_input_word_nav_cyrillic() {
         let lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut buffer = "Привет мир тест".to_string();
         let mut cursor_pos = 0;
@@ -1515,6 +2165,9 @@ mod tests {
             &mut cursor_pos,
             &lines,
             0,
+            0,
+            &mut None,
+            &mut 1,
             &mut annotation_scroll,
         ).unwrap();
 
@@ -1527,6 +2180,9 @@ mod tests {
             &mut cursor_pos,
             &lines,
             0,
+            0,
+            &mut None,
+            &mut 1,
             &mut annotation_scroll,
         ).unwrap();
 
@@ -1539,6 +2195,9 @@ mod tests {
             &mut cursor_pos,
             &lines,
             0,
+            0,
+            &mut None,
+            &mut 1,
             &mut annotation_scroll,
         ).unwrap();
 
@@ -1548,7 +2207,7 @@ mod tests {
     #[test]
     fn test_annotation_input_word_nav_with_punctuation() {
         let lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut buffer = "TODO: fix bug".to_string();
         let mut cursor_pos = 0;
@@ -1561,6 +2220,9 @@ mod tests {
             &mut cursor_pos,
             &lines,
             0,
+            0,
+            &mut None,
+            &mut 1,
             &mut annotation_scroll,
         ).unwrap();
 
@@ -1572,6 +2234,9 @@ mod tests {
             &mut cursor_pos,
             &lines,
             0,
+            0,
+            &mut None,
+            &mut 1,
             &mut annotation_scroll,
         ).unwrap();
 
@@ -1581,7 +2246,7 @@ mod tests {
     #[test]
     fn test_annotation_input_word_nav_mixed_unicode() {
         let lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut buffer = "Fix функцию get_data() error".to_string();
         let mut cursor_pos = 0;
@@ -1594,6 +2259,9 @@ mod tests {
             &mut cursor_pos,
             &lines,
             0,
+            0,
+            &mut None,
+            &mut 1,
             &mut annotation_scroll,
         ).unwrap();
 
@@ -1608,6 +2276,9 @@ mod tests {
                 &mut cursor_pos,
                 &lines,
                 0,
+                0,
+                &mut None,
+                &mut 1,
                 &mut annotation_scroll,
             ).unwrap();
             count += 1;
@@ -1620,7 +2291,7 @@ mod tests {
     #[test]
     fn test_annotation_input_word_nav_emoji() {
         let lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut buffer = "Done 🎉 success".to_string();
         let mut cursor_pos = 0;
@@ -1633,6 +2304,9 @@ mod tests {
             &mut cursor_pos,
             &lines,
             0,
+            0,
+            &mut None,
+            &mut 1,
             &mut annotation_scroll,
         ).unwrap();
 
@@ -1644,6 +2318,9 @@ mod tests {
             &mut cursor_pos,
             &lines,
             0,
+            0,
+            &mut None,
+            &mut 1,
             &mut annotation_scroll,
         ).unwrap();
 
@@ -1653,7 +2330,7 @@ mod tests {
     #[test]
     fn test_annotation_input_word_nav_at_boundaries() {
         let lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut buffer = "hello".to_string();
         let mut cursor_pos = 0;
@@ -1666,6 +2343,9 @@ mod tests {
             &mut cursor_pos,
             &lines,
             0,
+            0,
+            &mut None,
+            &mut 1,
             &mut annotation_scroll,
         ).unwrap();
 
@@ -1681,6 +2361,9 @@ mod tests {
             &mut cursor_pos,
             &lines,
             0,
+            0,
+            &mut None,
+            &mut 1,
             &mut annotation_scroll,
         ).unwrap();
 
@@ -1694,7 +2377,7 @@ mod tests {
     #[test]
     fn test_search_input_esc_exits() {
         let lines = vec![
-            Line { content: "test line".to_string(), annotation: None },
+            Line { content: "test line".to_string(), annotations: Vec::new() },
         ];
         let mut query = "test".to_string();
         let mut cursor_pos = query.len();
@@ -1724,7 +2407,7 @@ mod tests {
     #[test]
     fn test_search_input_char_updates_query() {
         let lines = vec![
-            Line { content: "test line".to_string(), annotation: None },
+            Line { content: "test line".to_string(), annotations: Vec::new() },
         ];
         let mut query = "tes".to_string();
         let mut cursor_pos = query.len();
@@ -1758,9 +2441,10 @@ mod tests {
     fn test_shortcuts_work_same_in_normal_and_diff_view() {
         // Test that the same shortcut produces the same result regardless of view mode
         let mut lines = vec![
-            Line { content: "line1".to_string(), annotation: None },
+            Line { content: "line1".to_string(), annotations: Vec::new() },
         ];
         let mut cursor_line = 0;
+        let mut selected_entry = 0;
         let mut theme = crate::theme::Theme::Dark;
         let mut annotation_scroll = 0;
         let mut scroll_offset = 0;
@@ -1771,6 +2455,7 @@ mod tests {
             KeyEvent::new(KeyCode::Char('g'), KeyModifiers::CONTROL),
             &mut lines,
             &mut cursor_line,
+            &mut selected_entry,
             &normal_view,
             &mut theme,
             &mut annotation_scroll,
@@ -1790,6 +2475,7 @@ mod tests {
             KeyEvent::new(KeyCode::Char('g'), KeyModifiers::CONTROL),
             &mut lines,
             &mut cursor_line,
+            &mut selected_entry,
             &diff_view,
             &mut theme,
             &mut annotation_scroll,
@@ -0,0 +1,482 @@
+//! Exporting annotations as JSON, Markdown or SARIF.
+
+use crate::file;
+use crate::models::{self, Line, Severity};
+use serde::Serialize;
+use serde_json::json;
+use std::path::Path;
+
+/// Lines of surrounding code included before and after each annotated line
+/// (or range of lines)
+pub const CONTEXT_LINES: usize = 2;
+
+/// Output formats supported by `nanot export` and the editor's export prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Markdown,
+    Sarif,
+}
+
+impl ExportFormat {
+    /// Parses a `--format` value.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "json" => Some(ExportFormat::Json),
+            "markdown" | "md" => Some(ExportFormat::Markdown),
+            "sarif" => Some(ExportFormat::Sarif),
+            _ => None,
+        }
+    }
+
+    /// File name used when exporting from the editor
+    pub fn default_file_name(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "annotations.json",
+            ExportFormat::Markdown => "annotations.md",
+            ExportFormat::Sarif => "annotations.sarif",
+        }
+    }
+}
+
+/// One annotation with enough of its surroundings to be read on its own.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExportedAnnotation {
+    pub file: String,
+    /// 1-based line number as shown in the editor
+    pub line: usize,
+    /// 1-based line number of the annotated code in the file on disk,
+    /// counting the marker lines above it
+    #[serde(skip)]
+    pub file_line: usize,
+    /// Last editor line a range annotation covers; absent for one line
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_line: Option<usize>,
+    /// `end_line` in the file on disk
+    #[serde(skip)]
+    pub file_end_line: Option<usize>,
+    pub annotation: String,
+    /// Severity, author and date from the marker's attributes, when set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub severity: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    /// `resolved` once the annotation has been addressed; absent while open
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<&'static str>,
+    /// Content of the annotated line
+    pub content: String,
+    pub context_before: Vec<String>,
+    /// Lines after the annotated one: the rest of the widest range on the
+    /// line, then the usual context
+    pub context_after: Vec<String>,
+}
+
+/// Collects every annotation in a file's lines, one per thread entry.
+pub fn collect_annotations(path: &str, lines: &[Line]) -> Vec<ExportedAnnotation> {
+    let mut annotations = Vec::new();
+
+    // 1-based line of each code line on disk, counting the markers above it
+    let file_lines: Vec<usize> = lines
+        .iter()
+        .scan(0, |markers_above, line| {
+            *markers_above += line.annotations.iter().map(|a| file::marker_line_count(&a.text)).sum::<usize>();
+            Some(*markers_above)
+        })
+        .enumerate()
+        .map(|(i, markers_above)| i + 1 + markers_above)
+        .collect();
+
+    for (i, line) in lines.iter().enumerate() {
+        let last_index = |annotation: &models::Annotation| (i + annotation.span() - 1).min(lines.len() - 1);
+        let thread_end = line.annotations.iter().map(last_index).max().unwrap_or(i);
+        let before_start = i.saturating_sub(CONTEXT_LINES);
+        let after_end = (thread_end + 1 + CONTEXT_LINES).min(lines.len());
+        for annotation in &line.annotations {
+            let end = Some(last_index(annotation)).filter(|&end| end > i);
+            annotations.push(ExportedAnnotation {
+                file: path.to_string(),
+                line: i + 1,
+                file_line: file_lines[i],
+                end_line: end.map(|end| end + 1),
+                file_end_line: end.map(|end| file_lines[end]),
+                annotation: annotation.text.clone(),
+                severity: annotation.severity().map(Severity::as_str),
+                author: annotation.author().map(str::to_string),
+                date: annotation.date().map(str::to_string),
+                status: annotation.is_resolved().then_some(models::RESOLVED),
+                content: line.content.clone(),
+                context_before: lines[before_start..i].iter().map(|l| l.content.clone()).collect(),
+                context_after: lines[i + 1..after_end].iter().map(|l| l.content.clone()).collect(),
+            });
+        }
+    }
+
+    annotations
+}
+
+/// Like `collect_annotations`, for a file that keeps its annotations in a
+/// sidecar file: with no marker lines in it, its line numbers on disk are
+/// the editor's.
+pub fn collect_sidecar_annotations(path: &str, lines: &[Line]) -> Vec<ExportedAnnotation> {
+    let mut annotations = collect_annotations(path, lines);
+    for annotation in &mut annotations {
+        annotation.file_line = annotation.line;
+        annotation.file_end_line = annotation.end_line;
+    }
+    annotations
+}
+
+/// Renders annotations in the requested format.
+pub fn render(annotations: &[ExportedAnnotation], format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Json => render_json(annotations),
+        ExportFormat::Markdown => render_markdown(annotations),
+        ExportFormat::Sarif => render_sarif(annotations),
+    }
+}
+
+fn render_json(annotations: &[ExportedAnnotation]) -> String {
+    let mut output = serde_json::to_string_pretty(annotations).unwrap_or_default();
+    output.push('\n');
+    output
+}
+
+fn render_markdown(annotations: &[ExportedAnnotation]) -> String {
+    let mut output = String::from("# Review annotations\n");
+    if annotations.is_empty() {
+        output.push_str("\nNo annotations.\n");
+        return output;
+    }
+
+    let same_line = |a: &ExportedAnnotation, b: &ExportedAnnotation| a.file == b.file && a.line == b.line;
+    let mut current_file: Option<&str> = None;
+    for (i, annotation) in annotations.iter().enumerate() {
+        if current_file != Some(annotation.file.as_str()) {
+            output.push_str(&format!("\n## {}\n", annotation.file));
+            current_file = Some(&annotation.file);
+        }
+
+        // Entries of a thread share one heading and one snippet, which
+        // spans the widest range in the thread
+        let thread_end = annotations[i..]
+            .iter()
+            .take_while(|next| same_line(annotation, next))
+            .chain(annotations[..i].iter().rev().take_while(|previous| same_line(annotation, previous)))
+            .filter_map(|entry| entry.end_line)
+            .max()
+            .unwrap_or(annotation.line);
+        let continues_thread = i > 0 && same_line(&annotations[i - 1], annotation);
+        if !continues_thread && thread_end > annotation.line {
+            output.push_str(&format!("\n### Lines {}-{}\n", annotation.line, thread_end));
+        } else if !continues_thread {
+            output.push_str(&format!("\n### Line {}\n", annotation.line));
+        }
+        output.push('\n');
+        let metadata: Vec<&str> = [
+            annotation.severity,
+            annotation.author.as_deref(),
+            annotation.date.as_deref(),
+            annotation.status,
+        ]
+            .into_iter()
+            .flatten()
+            .collect();
+        if !metadata.is_empty() {
+            output.push_str(&format!("> *{}*\n>\n", metadata.join(" · ")));
+        }
+        for text_line in annotation.annotation.lines() {
+            output.push_str(&format!("> {}\n", text_line));
+        }
+        if annotations.get(i + 1).is_some_and(|next| same_line(annotation, next)) {
+            continue;
+        }
+
+        // Use a longer fence if the snippet itself contains one
+        let snippet: Vec<&String> = annotation
+            .context_before
+            .iter()
+            .chain(std::iter::once(&annotation.content))
+            .chain(annotation.context_after.iter())
+            .collect();
+        let fence = if snippet.iter().any(|l| l.contains("```")) { "````" } else { "```" };
+        let language = Path::new(&annotation.file)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("");
+        let first_line = annotation.line - annotation.context_before.len();
+        let number_width = (first_line + snippet.len()).to_string().len();
+
+        output.push_str(&format!("\n{}{}\n", fence, language));
+        for (offset, content) in snippet.iter().enumerate() {
+            let number = first_line + offset;
+            let marker = if (annotation.line..=thread_end).contains(&number) { ">" } else { " " };
+            output.push_str(&format!("{} {:>width$} | {}\n", marker, number, content, width = number_width));
+        }
+        output.push_str(fence);
+        output.push('\n');
+    }
+
+    output
+}
+
+/// SARIF 2.1.0 log with one result per annotation: an `error` for
+/// blockers, a `warning` for major issues and a `note` otherwise, while
+/// resolved annotations become passing results without a level. Regions
+/// point at the annotated code as it sits in the file on disk, so viewers
+/// that open the file highlight the right line.
+fn render_sarif(annotations: &[ExportedAnnotation]) -> String {
+    let results: Vec<_> = annotations
+        .iter()
+        .map(|annotation| {
+            json!({
+                "ruleId": SARIF_RULE_ID,
+                "kind": if annotation.status.is_some() { "pass" } else { "fail" },
+                "level": if annotation.status.is_some() { "none" } else { sarif_level(annotation.severity) },
+                "message": { "text": annotation.annotation },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": sarif_uri(&annotation.file) },
+                        "region": {
+                            "startLine": annotation.file_line,
+                            "endLine": annotation.file_end_line.unwrap_or(annotation.file_line),
+                            "snippet": { "text": annotation.content },
+                        },
+                    },
+                }],
+            })
+        })
+        .collect();
+
+    let log = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "nanot",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": [{
+                        "id": SARIF_RULE_ID,
+                        "shortDescription": { "text": "Open review annotation" },
+                    }],
+                },
+            },
+            "results": results,
+        }],
+    });
+
+    let mut output = serde_json::to_string_pretty(&log).unwrap_or_default();
+    output.push('\n');
+    output
+}
+
+const SARIF_RULE_ID: &str = "nanot/annotation";
+
+fn sarif_level(severity: Option<&str>) -> &'static str {
+    match severity.and_then(Severity::parse) {
+        Some(Severity::Blocker) => "error",
+        Some(Severity::Major) => "warning",
+        _ => "note",
+    }
+}
+
+/// SARIF artifact URIs use forward slashes and no leading `./`
+fn sarif_uri(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    path.strip_prefix("./").unwrap_or(&path).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Annotation;
+
+    fn line(content: &str, annotation: Option<&str>) -> Line {
+        Line {
+            content: content.to_string(),
+            annotations: annotation.map(Annotation::from).into_iter().collect(),
+        }
+    }
+
+    fn sample_lines() -> Vec<Line> {
+        vec![
+            line("fn a() {", None),
+            line("    let x = 1;", Some("rename x")),
+            line("    x", None),
+            line("}", None),
+            line("fn b() {}", Some("unused")),
+        ]
+    }
+
+    #[test]
+    fn test_collect_annotations_with_context() {
+        let annotations = collect_annotations("src/a.rs", &sample_lines());
+        assert_eq!(annotations.len(), 2);
+
+        let first = &annotations[0];
+        assert_eq!(first.line, 2);
+        assert_eq!(first.file_line, 3);
+        assert_eq!(first.content, "    let x = 1;");
+        assert_eq!(first.context_before, vec!["fn a() {"]);
+        assert_eq!(first.context_after, vec!["    x", "}"]);
+
+        let second = &annotations[1];
+        assert_eq!(second.line, 5);
+        // Two marker lines sit above it on disk
+        assert_eq!(second.file_line, 7);
+        assert_eq!(second.context_before, vec!["    x", "}"]);
+        assert!(second.context_after.is_empty());
+    }
+
+    #[test]
+    fn test_file_line_counts_multi_line_markers() {
+        let lines = vec![line("a", Some("one\ntwo")), line("b", Some("three"))];
+        let annotations = collect_annotations("a.rs", &lines);
+        assert_eq!(annotations[0].file_line, 3);
+        assert_eq!(annotations[1].file_line, 5);
+    }
+
+    #[test]
+    fn test_sidecar_file_lines_skip_markers() {
+        let annotations = collect_sidecar_annotations("data.json", &sample_lines());
+        assert_eq!(annotations[0].file_line, 2);
+        assert_eq!(annotations[1].file_line, 5);
+    }
+
+    #[test]
+    fn test_render_json() {
+        let annotations = collect_annotations("src/a.rs", &sample_lines());
+        let value: serde_json::Value = serde_json::from_str(&render(&annotations, ExportFormat::Json)).unwrap();
+        assert_eq!(value[0]["file"], "src/a.rs");
+        assert_eq!(value[0]["line"], 2);
+        assert_eq!(value[0]["annotation"], "rename x");
+        assert_eq!(value[1]["context_before"][1], "}");
+        assert!(value[0].get("file_line").is_none());
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let annotations = collect_annotations("src/a.rs", &sample_lines()[..3]);
+        assert_eq!(
+            render(&annotations, ExportFormat::Markdown),
+            "# Review annotations\n\n## src/a.rs\n\n### Line 2\n\n> rename x\n\n```rs\n  1 | fn a() {\n> 2 |     let x = 1;\n  3 |     x\n```\n"
+        );
+    }
+
+    #[test]
+    fn test_render_markdown_groups_thread() {
+        let mut lines = vec![line("a", None), line("b", Some("why?"))];
+        lines[1].annotations.push("because".into());
+        let annotations = collect_annotations("a.rs", &lines);
+        assert_eq!(annotations.len(), 2);
+        assert_eq!(annotations[1].file_line, 4);
+        assert_eq!(
+            render(&annotations, ExportFormat::Markdown),
+            "# Review annotations\n\n## a.rs\n\n### Line 2\n\n> why?\n\n> because\n\n```rs\n  1 | a\n> 2 | b\n```\n"
+        );
+    }
+
+    #[test]
+    fn test_render_markdown_widens_fence() {
+        let lines = vec![line("```", None), line("code", Some("fence above"))];
+        let output = render(&collect_annotations("notes.md", &lines), ExportFormat::Markdown);
+        assert!(output.contains("\n````md\n"));
+    }
+
+    #[test]
+    fn test_render_markdown_empty() {
+        assert_eq!(render(&[], ExportFormat::Markdown), "# Review annotations\n\nNo annotations.\n");
+    }
+
+    #[test]
+    fn test_render_sarif() {
+        let annotations = collect_annotations("./src/a.rs", &sample_lines());
+        let value: serde_json::Value = serde_json::from_str(&render(&annotations, ExportFormat::Sarif)).unwrap();
+        assert_eq!(value["version"], "2.1.0");
+        let results = &value["runs"][0]["results"];
+        assert_eq!(results.as_array().unwrap().len(), 2);
+        assert_eq!(results[0]["level"], "note");
+        assert_eq!(results[0]["message"]["text"], "rename x");
+        let location = &results[1]["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "src/a.rs");
+        assert_eq!(location["region"]["startLine"], 7);
+    }
+
+    #[test]
+    fn test_export_includes_metadata() {
+        let mut annotation = Annotation::new("unsafe");
+        annotation.set_severity(Some(Severity::Blocker));
+        annotation.set(crate::models::AUTHOR_KEY, Some("alice"));
+        annotation.set(crate::models::DATE_KEY, Some("2026-10-16"));
+        let mut lines = vec![line("a", None), line("b", Some("tidy"))];
+        lines[0].annotations.push(annotation);
+        let annotations = collect_annotations("a.rs", &lines);
+
+        let json: serde_json::Value = serde_json::from_str(&render(&annotations, ExportFormat::Json)).unwrap();
+        assert_eq!(json[0]["severity"], "blocker");
+        assert_eq!(json[0]["author"], "alice");
+        assert_eq!(json[0]["date"], "2026-10-16");
+        assert!(json[1].get("severity").is_none());
+
+        assert!(render(&annotations, ExportFormat::Markdown).contains("> *blocker · alice · 2026-10-16*\n>\n> unsafe\n"));
+
+        let sarif: serde_json::Value = serde_json::from_str(&render(&annotations, ExportFormat::Sarif)).unwrap();
+        assert_eq!(sarif["runs"][0]["results"][0]["level"], "error");
+        assert_eq!(sarif["runs"][0]["results"][1]["level"], "note");
+    }
+
+    #[test]
+    fn test_export_resolved_status() {
+        let mut lines = vec![line("a", Some("done")), line("b", Some("open"))];
+        lines[0].annotations[0].set_resolved(true);
+        let annotations = collect_annotations("a.rs", &lines);
+
+        let json: serde_json::Value = serde_json::from_str(&render(&annotations, ExportFormat::Json)).unwrap();
+        assert_eq!(json[0]["status"], "resolved");
+        assert!(json[1].get("status").is_none());
+
+        assert!(render(&annotations, ExportFormat::Markdown).contains("> *resolved*\n>\n> done\n"));
+
+        let sarif: serde_json::Value = serde_json::from_str(&render(&annotations, ExportFormat::Sarif)).unwrap();
+        let results = &sarif["runs"][0]["results"];
+        assert_eq!((&results[0]["kind"], &results[0]["level"]), (&json!("pass"), &json!("none")));
+        assert_eq!((&results[1]["kind"], &results[1]["level"]), (&json!("fail"), &json!("note")));
+    }
+
+    #[test]
+    fn test_export_range() {
+        let mut lines = vec![line("a", None), line("b", Some("merge")), line("c", Some("why?")), line("d", None)];
+        lines[1].annotations[0].set_span(2);
+        let annotations = collect_annotations("a.rs", &lines);
+
+        let range = &annotations[0];
+        assert_eq!((range.line, range.end_line), (2, Some(3)));
+        assert_eq!((range.file_line, range.file_end_line), (3, Some(5)));
+        assert_eq!(range.context_after, vec!["c", "d"]);
+        assert_eq!(annotations[1].end_line, None);
+
+        let json: serde_json::Value = serde_json::from_str(&render(&annotations, ExportFormat::Json)).unwrap();
+        assert_eq!(json[0]["end_line"], 3);
+        assert!(json[1].get("end_line").is_none());
+
+        assert!(render(&annotations, ExportFormat::Markdown).contains(
+            "### Lines 2-3\n\n> merge\n\n```rs\n  1 | a\n> 2 | b\n> 3 | c\n  4 | d\n```\n"
+        ));
+
+        let sarif: serde_json::Value = serde_json::from_str(&render(&annotations, ExportFormat::Sarif)).unwrap();
+        let results = &sarif["runs"][0]["results"];
+        assert_eq!(results[0]["locations"][0]["physicalLocation"]["region"]["endLine"], 5);
+        assert_eq!(results[1]["locations"][0]["physicalLocation"]["region"]["endLine"], 5);
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(ExportFormat::parse("JSON"), Some(ExportFormat::Json));
+        assert_eq!(ExportFormat::parse("md"), Some(ExportFormat::Markdown));
+        assert_eq!(ExportFormat::parse("sarif"), Some(ExportFormat::Sarif));
+        assert_eq!(ExportFormat::parse("xml"), None);
+    }
+}
@@ -1,34 +1,309 @@
-use crate::models::Line;
-use std::fs;
+use crate::atomic;
+use crate::config;
+use crate::models::{Annotation, Line};
 use std::io;
 
-/// Detects the appropriate comment style based on file extension.
-pub fn detect_comment_style(path: &str) -> String {
-    let ext = path.split('.').last().unwrap_or("");
-    match ext {
-        "rs" | "go" | "java" | "kt" | "js" | "ts" | "c" | "cpp" | "h" | "cs" | "php" | "scala" | "dart" | "swift" => "//",
-        "py" | "sh" | "rb" | "yaml" | "yml" | "toml" | "pl" | "r" | "dockerfile" => "#",
-        "sql" | "lua" | "hs" | "ada" => "--",
-        "md" => "",
-        _ => {
-            if path.ends_with("Dockerfile") {
-                "#"
-            } else {
-                "//"
-            }
-        },
-    }.to_string()
+/// A comment syntax as stored in `lang_comment`: a line prefix such as
+/// `//`, or an open/close pair separated by a space such as `<!-- -->`.
+/// An empty style means Markdown, where markers are bare lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommentStyle<'a> {
+    pub open: &'a str,
+    /// Empty for line comments
+    pub close: &'a str,
+}
+
+impl<'a> CommentStyle<'a> {
+    pub fn parse(lang_comment: &'a str) -> Self {
+        match lang_comment.split_once(' ') {
+            Some((open, close)) => CommentStyle { open, close },
+            None => CommentStyle { open: lang_comment, close: "" },
+        }
+    }
+
+    /// Makes annotation text safe to put inside a block comment: a closing
+    /// token would end the comment early, and XML forbids `--` inside one.
+    fn escape_text(&self, text: &str) -> String {
+        let forbidden = match (self.open, self.close) {
+            (_, "") => return text.to_string(),
+            ("<!--", _) => "--",
+            (_, close) => close,
+        };
+        let mut split = forbidden.chars();
+        let first = split.next().map(String::from).unwrap_or_default();
+        let spaced = format!("{} {}", first, split.as_str());
+
+        let mut text = text.to_string();
+        while text.contains(forbidden) {
+            text = text.replace(forbidden, &spaced);
+        }
+        text
+    }
+}
+
+/// UTF-8 byte order mark, kept if a file starts with one
+const BOM: char = '\u{feff}';
+
+/// How a file's text is laid out on disk, so saving writes back the same
+/// line endings, final newline and byte order mark it was read with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextFormat {
+    /// Lines end in `\r\n` rather than `\n`
+    pub crlf: bool,
+    /// The last line ends with a line ending
+    pub final_newline: bool,
+    /// The file starts with a UTF-8 byte order mark
+    pub bom: bool,
+    /// Marker lines take the indentation of the line they annotate
+    pub indent_markers: bool,
+}
+
+impl Default for TextFormat {
+    /// Format for new files: `\n` endings, a final newline, no BOM and
+    /// indented markers.
+    fn default() -> Self {
+        TextFormat {
+            crlf: false,
+            final_newline: true,
+            bom: false,
+            indent_markers: true,
+        }
+    }
+}
+
+impl TextFormat {
+    /// Detects the format of `content`. A file mixing line endings is
+    /// written back with the ending of its first line.
+    pub fn detect(content: &str) -> Self {
+        TextFormat {
+            crlf: content.find('\n').is_some_and(|end| content[..end].ends_with('\r')),
+            final_newline: content.ends_with('\n'),
+            bom: content.starts_with(BOM),
+            indent_markers: true,
+        }
+    }
+
+    /// Converts text with a `\n` after every line into this format.
+    fn apply(&self, mut text: String) -> String {
+        if !self.final_newline && text.ends_with('\n') {
+            text.pop();
+        }
+        if self.crlf {
+            text = text.replace('\n', "\r\n");
+        }
+        if self.bom {
+            text.insert(0, BOM);
+        }
+        text
+    }
+}
+
+/// Start of an annotation marker line, e.g. `// [ANNOTATION]` or
+/// `<!-- [ANNOTATION]`.
+pub fn annotation_marker(comment: &str, tag: &str) -> String {
+    let style = CommentStyle::parse(comment);
+    if style.open.is_empty() {
+        tag.to_string()
+    } else {
+        format!("{} {}", style.open, tag)
+    }
+}
+
+/// Appended to the tag of a marker line that continues the annotation
+/// above it instead of starting a new entry in the thread, e.g.
+/// `// [ANNOTATION]+ second line`.
+pub const CONTINUATION: &str = "+";
+
+/// One parsed marker line.
+struct MarkerLine {
+    /// Continues the entry above instead of starting a new one
+    continues: bool,
+    attributes: Vec<(String, String)>,
+    text: String,
+}
+
+/// Parses a marker line, or returns `None` if the line isn't one.
+/// Block-comment markers must be closed on the same line.
+fn parse_marker_line(line: &str, style: CommentStyle, tag: &str) -> Option<MarkerLine> {
+    let mut rest = line.trim();
+    if !style.open.is_empty() {
+        rest = rest.strip_prefix(style.open)?.strip_prefix(' ')?;
+    }
+    let (attributes, rest) = parse_tag(rest, tag)?;
+    let (continues, rest) = match rest.strip_prefix(CONTINUATION) {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+    let rest = if style.close.is_empty() { rest } else { rest.strip_suffix(style.close)? };
+    Some(MarkerLine {
+        continues,
+        attributes,
+        text: rest.trim().to_string(),
+    })
+}
+
+/// Matches `tag` at the start of `text`, either bare or, for tags ending in
+/// `]`, with `key=value` attributes before the bracket, e.g.
+/// `[ANNOTATION sev=blocker by="Jane Doe"]`. Returns the attributes and
+/// the text after the tag. A bare tag must be followed by the end of the
+/// line, whitespace or a continuation `+`, so a tag like `TODO` doesn't
+/// match ordinary comments such as `TODOs later`.
+fn parse_tag<'a>(text: &'a str, tag: &str) -> Option<(Vec<(String, String)>, &'a str)> {
+    if let Some(rest) = text.strip_prefix(tag)
+        && (rest.is_empty() || rest.starts_with(char::is_whitespace) || rest.starts_with(CONTINUATION))
+    {
+        return Some((Vec::new(), rest));
+    }
+    let mut rest = text.strip_prefix(tag.strip_suffix(']')?)?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    let mut attributes = Vec::new();
+    loop {
+        rest = rest.trim_start();
+        if let Some(after) = rest.strip_prefix(']') {
+            return (!attributes.is_empty()).then_some((attributes, after));
+        }
+        let (key, after) = rest.split_once('=')?;
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return None;
+        }
+        let (value, after) = parse_attribute_value(after)?;
+        attributes.push((key.to_string(), value));
+        rest = after;
+    }
+}
+
+/// Reads a plain or double-quoted attribute value (with `\"` and `\\`
+/// escapes) and returns it with the text after it.
+fn parse_attribute_value(text: &str) -> Option<(String, &str)> {
+    let Some(quoted) = text.strip_prefix('"') else {
+        let end = text.find(|c: char| c.is_whitespace() || c == ']' || c == '"').unwrap_or(text.len());
+        return (end > 0).then(|| (text[..end].to_string(), &text[end..]));
+    };
+
+    let mut value = String::new();
+    let mut chars = quoted.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => value.push(chars.next()?.1),
+            '"' => return Some((value, &quoted[i + 1..])),
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+/// `tag` with `attributes` written before its closing bracket. Tags that
+/// can't hold attributes (see `config::holds_attributes`) are written bare,
+/// so callers must refuse attributes for them up front.
+pub fn tag_with_attributes(tag: &str, attributes: &[(String, String)]) -> String {
+    match tag.strip_suffix(']') {
+        Some(open) if !attributes.is_empty() => {
+            let attributes: Vec<String> = attributes
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, quote_attribute_value(value)))
+                .collect();
+            format!("{} {}]", open, attributes.join(" "))
+        }
+        _ => tag.to_string(),
+    }
+}
+
+fn quote_attribute_value(value: &str) -> String {
+    let plain = !value.is_empty() && !value.chars().any(|c| c.is_whitespace() || matches!(c, '"' | '\\' | ']'));
+    if plain {
+        value.to_string()
+    } else {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+/// A full marker line for one line of annotation text, e.g.
+/// `<!-- [ANNOTATION] text -->`.
+fn format_marker_line(style: CommentStyle, marker: &str, text: &str) -> String {
+    match (text.is_empty(), style.close.is_empty()) {
+        (true, true) => marker.to_string(),
+        (true, false) => format!("{} {}", marker, style.close),
+        (false, true) => format!("{} {}", marker, text),
+        (false, false) => format!("{} {} {}", marker, style.escape_text(text), style.close),
+    }
+}
+
+/// Annotation text as it will read back after saving with `comment`: each
+/// line is trimmed, and block comments can't contain their closing token.
+pub fn saved_annotation_text(text: &str, comment: &str) -> String {
+    let style = CommentStyle::parse(comment);
+    text.lines()
+        .map(|line| style.escape_text(line.trim()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Number of marker lines an annotation takes up in the file.
+pub fn marker_line_count(annotation: &str) -> usize {
+    annotation.split('\n').count()
 }
 
 /// Parses file content into lines with optional annotations.
 pub fn parse_file(content: &str, comment: &str) -> Vec<Line> {
+    parse_file_tagged(content, comment, config::marker_tag())
+}
+
+/// Parses file content, recognising markers that use `tag`.
+pub fn parse_file_tagged(content: &str, comment: &str, tag: &str) -> Vec<Line> {
+    parse_markers(content, comment, tag).0
+}
+
+/// Rewrites every marker line that uses the tag `from` to use `to`,
+/// leaving everything else as it was: indentation, line endings,
+/// attributes and annotation text. Markers are found as `parse_file_tagged`
+/// finds them.
+pub fn retag_markers(content: &str, comment: &str, from: &str, to: &str) -> String {
+    let style = CommentStyle::parse(comment);
+    let (_, marker_lines) = parse_markers(content, comment, from);
+    let (bom, body) = match content.strip_prefix(BOM) {
+        Some(body) => (true, body),
+        None => (false, content),
+    };
+
+    let mut output = String::with_capacity(content.len());
+    if bom {
+        output.push(BOM);
+    }
+    let mut markers = marker_lines.into_iter().peekable();
+    for (i, line) in body.split_inclusive('\n').enumerate() {
+        if markers.next_if_eq(&i).is_none() {
+            output.push_str(line);
+            continue;
+        }
+        // Skip the indentation and comment opener the parser accepted
+        let mut start = leading_whitespace(line).len();
+        if !style.open.is_empty() {
+            start += style.open.len() + 1;
+        }
+        let (head, rest) = line.split_at(start);
+        let (old, new) = match rest.strip_prefix(from) {
+            Some(_) => (from, to),
+            // `[TAG key=value]`: only the part before the attributes changes
+            None => (from.strip_suffix(']').unwrap_or(from), to.strip_suffix(']').unwrap_or(to)),
+        };
+        output.push_str(head);
+        output.push_str(new);
+        output.push_str(&rest[old.len()..]);
+    }
+    output
+}
+
+/// Parses file content like `parse_file_tagged`, also returning the
+/// 0-based indices (after any BOM) of the lines read as markers.
+fn parse_markers(content: &str, comment: &str, tag: &str) -> (Vec<Line>, Vec<usize>) {
     let mut lines = Vec::new();
+    let mut marker_lines = Vec::new();
+    let content = content.strip_prefix(BOM).unwrap_or(content);
     let raw_lines: Vec<&str> = content.lines().collect();
-    let annotation_marker = if comment.is_empty() {
-        "[ANNOTATION]".to_string()
-    } else {
-        format!("{} [ANNOTATION]", comment)
-    };
+    let style = CommentStyle::parse(comment);
 
     let mut i = 0;
     let mut in_code_block = false;
@@ -42,125 +317,122 @@ pub fn parse_file(content: &str, comment: &str) -> Vec<Line> {
             in_code_block = !in_code_block;
         }
 
-        if !in_code_block && line.trim().starts_with(&annotation_marker) {
-            let annotation_text = line.trim()
-                .strip_prefix(&annotation_marker)
-                .unwrap_or("")
-                .trim()
-                .to_string();
-            
-            if i + 1 < raw_lines.len() {
+        // Stacked marker lines form the annotation thread of the code line
+        // that follows them; continuation markers extend the entry above
+        let mut thread: Vec<Annotation> = Vec::new();
+        let mut marker_count = 0;
+        while !in_code_block
+            && let Some(marker) = raw_lines
+                .get(i + marker_count)
+                .and_then(|line| parse_marker_line(line, style, tag))
+        {
+            match thread.last_mut() {
+                Some(entry) if marker.continues => {
+                    entry.text.push('\n');
+                    entry.text.push_str(&marker.text);
+                }
+                _ => thread.push(Annotation {
+                    text: marker.text,
+                    attributes: marker.attributes,
+                }),
+            }
+            marker_count += 1;
+        }
+
+        match raw_lines.get(i + marker_count) {
+            Some(content) if marker_count > 0 => {
                 lines.push(Line {
-                    content: raw_lines[i + 1].to_string(),
-                    annotation: Some(annotation_text),
+                    content: content.to_string(),
+                    annotations: thread,
                 });
-                i += 2;
-            } else {
+                marker_lines.extend(i..i + marker_count);
+                i += marker_count + 1;
+            }
+            _ => {
+                // A marker with no code line after it stays plain content
                 lines.push(Line {
                     content: line.to_string(),
-                    annotation: None, // Or keep it as content? Logic below used None for end of file annotation mismatch
+                    annotations: Vec::new(),
                 });
-                // Actually, looking at original logic:
-                // If it's the last line and looks like an annotation, it consumes it but with no attached content?
-                // Original: 
-                // lines.push(Line { content: line.to_string(), annotation: None }); 
-                // i += 1;
-                // Since this block is "if it STARTS with annotation marker", 
-                // The original logic for the `else` (last line) was: treat it as a normal line because it has no following line to attach to.
-                // But wait, the original logic for `else` was finding the marker, then pushing `line` as content.
-                // Meaning it WASN'T treated as an annotation if it was the last line?
-                // Let's stick to the original behavior for that edge case, but wrapped in `!in_code_block`.
                 i += 1;
             }
-        } else {
-            lines.push(Line {
-                content: line.to_string(),
-                annotation: None,
-            });
-            i += 1;
         }
     }
 
     if lines.is_empty() {
-        lines.push(Line { content: String::new(), annotation: None });
+        lines.push(Line { content: String::new(), annotations: Vec::new() });
     }
 
+    (lines, marker_lines)
+}
+
+/// Splits file content into lines without looking for markers, for files
+/// whose annotations are kept in a sidecar file.
+pub fn parse_plain(content: &str) -> Vec<Line> {
+    let content = content.strip_prefix(BOM).unwrap_or(content);
+    let mut lines: Vec<Line> = content
+        .lines()
+        .map(|line| Line { content: line.to_string(), annotations: Vec::new() })
+        .collect();
+    if lines.is_empty() {
+        lines.push(Line { content: String::new(), annotations: Vec::new() });
+    }
     lines
 }
 
-/// Saves lines with annotations to a file.
-pub fn save_file(path: &str, lines: &[Line], lang_comment: &str) -> io::Result<()> {
+/// Saves lines with annotations to a file in `format`, replacing it
+/// atomically and backing it up to `<path>~` first when `backup` is set.
+pub fn save_file(path: &str, lines: &[Line], lang_comment: &str, format: TextFormat, backup: bool) -> io::Result<()> {
+    atomic::write_with_backup(path, render_file(lines, lang_comment, format), backup)
+}
+
+/// Serializes lines back into file content, writing each annotation entry
+/// as marker comments above its code, one per line of annotation text and
+/// indented like the code.
+pub fn render_file(lines: &[Line], lang_comment: &str, format: TextFormat) -> String {
+    render_file_tagged(lines, lang_comment, config::marker_tag(), format)
+}
+
+/// Serializes lines back into file content, writing markers with `tag`.
+pub fn render_file_tagged(lines: &[Line], lang_comment: &str, tag: &str, format: TextFormat) -> String {
     let mut output = String::new();
-    let annotation_marker = if lang_comment.is_empty() {
-        "[ANNOTATION]".to_string()
-    } else {
-        format!("{} [ANNOTATION]", lang_comment)
-    };
+    let style = CommentStyle::parse(lang_comment);
+    let continuation_marker = format!("{}{}", annotation_marker(lang_comment, tag), CONTINUATION);
 
     for line in lines {
-        if let Some(ref annotation) = line.annotation {
-            output.push_str(&format!("{} {}\n", annotation_marker, annotation));
+        let indent = if format.indent_markers { leading_whitespace(&line.content) } else { "" };
+        for annotation in &line.annotations {
+            let first_marker = annotation_marker(lang_comment, &tag_with_attributes(tag, &annotation.attributes));
+            for (i, text) in annotation.text.split('\n').enumerate() {
+                let marker = if i == 0 { &first_marker } else { &continuation_marker };
+                output.push_str(indent);
+                output.push_str(&format_marker_line(style, marker, text));
+                output.push('\n');
+            }
         }
         output.push_str(&line.content);
         output.push('\n');
     }
 
-    fs::write(path, output)?;
-    Ok(())
+    format.apply(output)
+}
+
+fn leading_whitespace(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-
-    #[test]
-    fn test_detect_comment_style_rust() {
-        assert_eq!(detect_comment_style("main.rs"), "//");
-    }
-
-    #[test]
-    fn test_detect_comment_style_python() {
-        assert_eq!(detect_comment_style("script.py"), "#");
-    }
-
-    #[test]
-    fn test_detect_comment_style_markdown() {
-        assert_eq!(detect_comment_style("README.md"), "");
-    }
-
-    #[test]
-    fn test_detect_comment_style_unknown() {
-        assert_eq!(detect_comment_style("file.xyz"), "//");
-    }
-
-    #[test]
-    fn test_detect_comment_style_sql() {
-        assert_eq!(detect_comment_style("query.sql"), "--");
-    }
-
-    #[test]
-    fn test_detect_comment_style_docker() {
-        assert_eq!(detect_comment_style("Dockerfile"), "#");
-        assert_eq!(detect_comment_style("dev.dockerfile"), "#");
-    }
-
-    #[test]
-    fn test_detect_comment_style_config() {
-        assert_eq!(detect_comment_style("config.yaml"), "#");
-        assert_eq!(detect_comment_style("Cargo.toml"), "#");
-    }
-
-    #[test]
-    fn test_detect_comment_style_swift() {
-        assert_eq!(detect_comment_style("App.swift"), "//");
-    }
+    use crate::models::Severity;
+    use std::fs;
 
     #[test]
     fn test_parse_file_without_annotations() {
         let content = "fn main() {\n    println!(\"Hello\");\n}";
         let lines = parse_file(content, "//");
         assert_eq!(lines.len(), 3);
-        assert!(lines[0].annotation.is_none());
+        assert!(lines[0].annotations.is_empty());
         assert_eq!(lines[0].content, "fn main() {");
     }
 
@@ -169,7 +441,7 @@ mod tests {
         let content = "// [ANNOTATION] This is a comment\nfn main() {\n    println!(\"Hello\");\n}";
         let lines = parse_file(content, "//");
         assert_eq!(lines.len(), 3);
-        assert_eq!(lines[0].annotation, Some("This is a comment".to_string()));
+        assert_eq!(lines[0].annotations, vec!["This is a comment"]);
         assert_eq!(lines[0].content, "fn main() {");
     }
 
@@ -194,7 +466,7 @@ mod tests {
         let lines = parse_file(&content, "//");
         
         // Save
-        save_file(temp_path, &lines, "//").unwrap();
+        save_file(temp_path, &lines, "//", TextFormat::default(), false).unwrap();
         
         // Read back
         let saved_content = fs::read_to_string(temp_path).unwrap();
@@ -202,7 +474,7 @@ mod tests {
         
         // Verify
         assert_eq!(lines.len(), lines2.len());
-        assert_eq!(lines[0].annotation, lines2[0].annotation);
+        assert_eq!(lines[0].annotations, lines2[0].annotations);
         assert_eq!(lines[0].content, lines2[0].content);
         
         // Cleanup
@@ -216,16 +488,16 @@ mod tests {
         
         // Line 0: Normal text
         assert_eq!(lines[0].content, "Normal text");
-        assert_eq!(lines[0].annotation, None);
+        assert!(lines[0].annotations.is_empty());
         
         // Line 1: ```
         assert_eq!(lines[1].content, "```");
-        assert_eq!(lines[1].annotation, None);
+        assert!(lines[1].annotations.is_empty());
         
         // Line 2: [ANNOTATION] ... 
         // Should be treated as content because it's in a code block
         assert_eq!(lines[2].content, "[ANNOTATION] This should be ignored");
-        assert_eq!(lines[2].annotation, None);
+        assert!(lines[2].annotations.is_empty());
         
         // Line 3: ```
         assert_eq!(lines[3].content, "```");
@@ -241,7 +513,7 @@ mod tests {
         
         // Lines 0-2: code block
         assert_eq!(lines[1].content, "[ANNOTATION] ignore me");
-        assert_eq!(lines[1].annotation, None);
+        assert!(lines[1].annotations.is_empty());
         
         // Line 3 (was 4 in raw): Target with annotation
         // "```" is line index 2.
@@ -254,6 +526,254 @@ mod tests {
         // 3: Target (with annotation)
         
         assert_eq!(lines[3].content, "Target");
-        assert_eq!(lines[3].annotation, Some("valid".to_string()));
+        assert_eq!(lines[3].annotations, vec!["valid"]);
+    }
+
+    #[test]
+    fn test_custom_marker_tag_round_trip() {
+        let content = "// [REVIEW] check this\nlet x = 5;\n// [ANNOTATION] other tag\nlet y = 6;\n";
+        let lines = parse_file_tagged(content, "//", "[REVIEW]");
+
+        assert_eq!(lines[0].annotations, vec!["check this"]);
+        // Markers with another tag are plain content
+        assert_eq!(lines[1].content, "// [ANNOTATION] other tag");
+        assert!(lines[1].annotations.is_empty());
+        assert_eq!(render_file_tagged(&lines, "//", "[REVIEW]", TextFormat::default()), content);
+    }
+
+    #[test]
+    fn test_multi_line_annotation_round_trip() {
+        let content = "fn a() {}\n// [ANNOTATION] first\n// [ANNOTATION]+\n// [ANNOTATION]+ second\nfn b() {}\n";
+        let lines = parse_file(content, "//");
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].content, "fn b() {}");
+        assert_eq!(lines[1].annotations, vec!["first\n\nsecond"]);
+        assert_eq!(render_file(&lines, "//", TextFormat::default()), content);
+
+        let html = "<!-- [ANNOTATION] one -->\n<!-- [ANNOTATION]+ -->\n<p></p>\n";
+        let html_lines = parse_file(html, "<!-- -->");
+        assert_eq!(html_lines[0].annotations, vec!["one\n"]);
+        assert_eq!(render_file(&html_lines, "<!-- -->", TextFormat::default()), html);
+    }
+
+    #[test]
+    fn test_stacked_markers_are_thread_entries() {
+        let content = "// [ANNOTATION] why?\n// [ANNOTATION] because\n// [ANNOTATION]+ of x\n// [ANNOTATION]\nfn a() {}\n";
+        let lines = parse_file(content, "//");
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].annotations, vec!["why?", "because\nof x", ""]);
+        assert_eq!(render_file(&lines, "//", TextFormat::default()), content);
+
+        // A continuation with nothing to continue starts the thread
+        let orphan = parse_file("# [ANNOTATION]+ lone\nx = 1\n", "#");
+        assert_eq!(orphan[0].annotations, vec!["lone"]);
+    }
+
+    #[test]
+    fn test_marker_attributes_round_trip() {
+        let content = "// [ANNOTATION sev=blocker by=\"Jane \\\"JD\\\" Doe\" at=2026-10-16 x-ticket=42] fix\n// [ANNOTATION]+ soon\n// [ANNOTATION] ok\nfn a() {}\n";
+        let lines = parse_file(content, "//");
+        assert_eq!(lines.len(), 1);
+        let thread = &lines[0].annotations;
+        assert_eq!(thread[0].text, "fix\nsoon");
+        assert_eq!(thread[0].severity(), Some(Severity::Blocker));
+        assert_eq!(thread[0].author(), Some("Jane \"JD\" Doe"));
+        assert_eq!(thread[0].date(), Some("2026-10-16"));
+        // Unknown keys are kept in order
+        assert_eq!(thread[0].get("x-ticket"), Some("42"));
+        assert_eq!(thread[1], "ok");
+        assert_eq!(render_file(&lines, "//", TextFormat::default()), content);
+    }
+
+    #[test]
+    fn test_range_survives_markers_added_around_it() {
+        let content = "// [ANNOTATION lines=3] extract\nfn a() {\n    x();\n}\nfn b() {}\n";
+        let mut lines = parse_file(content, "//");
+        let covered = |lines: &[Line]| {
+            let start = lines.iter().position(|line| line.annotations.iter().any(|a| a.span() == 3)).unwrap();
+            lines[start..start + 3].iter().map(|line| line.content.clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(covered(&lines), vec!["fn a() {", "    x();", "}"]);
+
+        // The span counts code lines, so markers above or inside the range
+        // don't move its end
+        lines.insert(0, Line { content: "use x;".to_string(), annotations: vec!["unused".into()] });
+        lines[2].annotations.push("inline".into());
+        let saved = render_file(&lines, "//", TextFormat::default());
+        assert_eq!(
+            saved,
+            "// [ANNOTATION] unused\nuse x;\n// [ANNOTATION lines=3] extract\nfn a() {\n    // [ANNOTATION] inline\n    x();\n}\nfn b() {}\n"
+        );
+        assert_eq!(covered(&parse_file(&saved, "//")), vec!["fn a() {", "    x();", "}"]);
+    }
+
+    #[test]
+    fn test_malformed_attributes_are_content() {
+        for marker in [
+            "// [ANNOTATIONS] a",
+            "// [ANNOTATION sev] a",
+            "// [ANNOTATION by=\"open] a",
+            "// [ANNOTATION sev=major a",
+        ] {
+            let lines = parse_file(&format!("{}\nx\n", marker), "//");
+            assert_eq!(lines[0].content, marker);
+            assert!(lines[0].annotations.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_attributes_need_a_bracketed_tag() {
+        let lines = parse_file_tagged("# TODO: sev=major fix\nx = 1\n", "#", "TODO:");
+        assert_eq!(lines[0].annotations, vec!["sev=major fix"]);
+        assert_eq!(tag_with_attributes("TODO:", &[("sev".to_string(), "major".to_string())]), "TODO:");
+    }
+
+    #[test]
+    fn test_tag_must_end_at_a_word_boundary() {
+        let content = "// TODOs later\n// TODO check\nfn a() {}\n";
+        let lines = parse_file_tagged(content, "//", "TODO");
+        assert_eq!(lines[0].content, "// TODOs later");
+        assert!(lines[0].annotations.is_empty());
+        assert_eq!(lines[1].annotations, vec!["check"]);
+        assert_eq!(render_file_tagged(&lines, "//", "TODO", TextFormat::default()), content);
+
+        let content = "// @reviewer: ok\n// @review\nfn b() {}\n";
+        let lines = parse_file_tagged(content, "//", "@review");
+        assert_eq!(lines[0].content, "// @reviewer: ok");
+        assert_eq!(lines[1].annotations, vec![""]);
+    }
+
+    #[test]
+    fn test_trailing_markers_are_content() {
+        let lines = parse_file("x\n// [ANNOTATION] a\n// [ANNOTATION] b\n", "//");
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().all(|line| line.annotations.is_empty()));
+    }
+
+    #[test]
+    fn test_saved_annotation_text_multi_line() {
+        assert_eq!(saved_annotation_text("  one \r\n two */\n", "/* */"), "one\ntwo * /");
+        assert_eq!(marker_line_count("one\n\ntwo"), 3);
+    }
+
+    fn round_trip(content: &str) -> String {
+        round_trip_with(content, "//")
+    }
+
+    fn round_trip_with(content: &str, comment: &str) -> String {
+        let lines = parse_file(content, comment);
+        render_file(&lines, comment, TextFormat::detect(content))
+    }
+
+    #[test]
+    fn test_round_trip_keeps_crlf() {
+        let content = "// [ANNOTATION] note\r\nfn a() {}\r\nfn b() {}\r\n";
+        let lines = parse_file(content, "//");
+        assert_eq!(lines[0].content, "fn a() {}");
+        assert_eq!(lines[0].annotations, vec!["note"]);
+        assert!(TextFormat::detect(content).crlf);
+        assert_eq!(round_trip(content), content);
+    }
+
+    #[test]
+    fn test_round_trip_keeps_missing_final_newline() {
+        assert_eq!(round_trip("fn a() {}\nfn b() {}"), "fn a() {}\nfn b() {}");
+        assert_eq!(round_trip("fn a() {}\r\nfn b() {}"), "fn a() {}\r\nfn b() {}");
+        assert_eq!(round_trip("fn a() {}\n\n"), "fn a() {}\n\n");
+    }
+
+    #[test]
+    fn test_round_trip_keeps_bom() {
+        let content = "\u{feff}// [ANNOTATION] note\nfn a() {}\n";
+        let lines = parse_file(content, "//");
+        assert_eq!(lines[0].content, "fn a() {}");
+        assert_eq!(lines[0].annotations, vec!["note"]);
+        assert_eq!(round_trip(content), content);
+
+        // The BOM stays first even when the first line gains a marker
+        let mut lines = parse_file("\u{feff}fn a() {}\n", "//");
+        lines[0].annotations = vec!["new".into()];
+        let format = TextFormat::detect("\u{feff}fn a() {}\n");
+        assert_eq!(render_file(&lines, "//", format), "\u{feff}// [ANNOTATION] new\nfn a() {}\n");
+    }
+
+    #[test]
+    fn test_round_trip_empty_file() {
+        assert_eq!(round_trip(""), "");
+        assert_eq!(round_trip("\n"), "\n");
+    }
+
+    #[test]
+    fn test_new_markers_use_file_line_endings() {
+        let content = "a\r\nb";
+        let mut lines = parse_file(content, "#");
+        lines[1].annotations = vec!["one\ntwo".into()];
+        assert_eq!(
+            render_file(&lines, "#", TextFormat::detect(content)),
+            "a\r\n# [ANNOTATION] one\r\n# [ANNOTATION]+ two\r\nb"
+        );
+    }
+
+    #[test]
+    fn test_markers_follow_line_indentation() {
+        let content = "fn a() {\n    if x {\n        // [ANNOTATION] one\n        // [ANNOTATION]+ two\n        y();\n\t// [ANNOTATION] tab\n\tz();\n    }\n}\n";
+        let lines = parse_file(content, "//");
+        assert_eq!(lines[2].annotations, vec!["one\ntwo"]);
+        assert_eq!(lines[3].annotations, vec!["tab"]);
+        assert_eq!(render_file(&lines, "//", TextFormat::default()), content);
+
+        let yaml = "root:\n  # [ANNOTATION] rename\n  key: 1\n";
+        assert_eq!(round_trip_with(yaml, "#"), yaml);
+    }
+
+    #[test]
+    fn test_unindented_markers_are_read_and_reindented() {
+        let lines = parse_file("fn a() {\n// [ANNOTATION] note\n    x();\n}\n", "//");
+        assert_eq!(lines[1].annotations, vec!["note"]);
+        assert_eq!(
+            render_file(&lines, "//", TextFormat::default()),
+            "fn a() {\n    // [ANNOTATION] note\n    x();\n}\n"
+        );
+
+        let flat = TextFormat { indent_markers: false, ..TextFormat::default() };
+        assert_eq!(render_file(&lines, "//", flat), "fn a() {\n// [ANNOTATION] note\n    x();\n}\n");
+    }
+
+    #[test]
+    fn test_comment_style_parse() {
+        assert_eq!(CommentStyle::parse("<!-- -->"), CommentStyle { open: "<!--", close: "-->" });
+        assert_eq!(CommentStyle::parse("//"), CommentStyle { open: "//", close: "" });
+        assert_eq!(CommentStyle::parse(""), CommentStyle { open: "", close: "" });
+    }
+
+    #[test]
+    fn test_block_comment_round_trip() {
+        let content = "<ul>\n  <!-- [ANNOTATION] use ol -->\n  <li>One</li>\n</ul>\n";
+        let lines = parse_file(content, "<!-- -->");
+        assert_eq!(lines[1].content, "  <li>One</li>");
+        assert_eq!(lines[1].annotations, vec!["use ol"]);
+
+        let css = "/* [ANNOTATION] too bright */\na { color: red; }\n";
+        let css_lines = parse_file(css, "/* */");
+        assert_eq!(css_lines[0].annotations, vec!["too bright"]);
+        assert_eq!(render_file(&css_lines, "/* */", TextFormat::default()), css);
+    }
+
+    #[test]
+    fn test_unclosed_block_marker_is_content() {
+        let lines = parse_file("<!-- [ANNOTATION] open\n<p></p>\n", "<!-- -->");
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].annotations.is_empty());
+    }
+
+    #[test]
+    fn test_block_comment_escapes_closing_token() {
+        let lines = vec![Line {
+            content: "a {}".to_string(),
+            annotations: vec!["end */ early".into()],
+        }];
+        assert_eq!(render_file(&lines, "/* */", TextFormat::default()), "/* [ANNOTATION] end * / early */\na {}\n");
+        assert_eq!(saved_annotation_text("a -- b --> c", "<!-- -->"), "a - - b - -> c");
+        assert_eq!(saved_annotation_text("a -- b", "//"), "a -- b");
     }
 }
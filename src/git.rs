@@ -1,7 +1,7 @@
 //! Git integration module for reading HEAD content and checking file status.
 
-use git2::Repository;
-use std::path::Path;
+use git2::{Delta, DiffOptions, Repository, Status, StatusOptions, Tree};
+use std::path::{Path, PathBuf};
 
 /// Error types for git operations
 #[derive(Debug)]
@@ -12,6 +12,8 @@ pub enum GitError {
     NotTracked,
     /// File does not exist in HEAD (new file that's staged but not committed)
     NotInHead,
+    /// Base revision given with `--base` could not be resolved
+    InvalidBase(String),
     /// Other git error
     Git(git2::Error),
 }
@@ -22,6 +24,7 @@ impl std::fmt::Display for GitError {
             GitError::NotARepo => write!(f, "Not a git repository"),
             GitError::NotTracked => write!(f, "File is not tracked"),
             GitError::NotInHead => write!(f, "File does not exist in HEAD"),
+            GitError::InvalidBase(base) => write!(f, "Unknown base revision '{}'", base),
             GitError::Git(e) => write!(f, "Git error: {}", e),
         }
     }
@@ -33,6 +36,33 @@ impl From<git2::Error> for GitError {
     }
 }
 
+/// How a file differs from HEAD in the index or working tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileChange {
+    Modified,
+    Added,
+    Deleted,
+}
+
+/// A file reported as changed by `git status`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedFile {
+    /// Root of the working tree the file belongs to
+    pub repo_root: PathBuf,
+    /// Path relative to the repository root
+    pub relative_path: String,
+    pub change: FileChange,
+    /// Binary files are listed but can't be annotated
+    pub binary: bool,
+}
+
+impl ChangedFile {
+    /// Absolute path of the file in the working tree
+    pub fn path(&self) -> PathBuf {
+        self.repo_root.join(&self.relative_path)
+    }
+}
+
 /// Check if a file is inside a git repository
 pub fn is_git_available(path: &str) -> bool {
     let path = Path::new(path);
@@ -140,6 +170,273 @@ pub fn get_head_content(path: &str) -> Result<String, GitError> {
     Ok(content.to_string())
 }
 
+/// List modified, added and deleted files in the repository containing `path`,
+/// sorted by path. Untracked files count as added; ignored files are skipped.
+pub fn changed_files(path: &str) -> Result<Vec<ChangedFile>, GitError> {
+    let path = Path::new(path);
+    let abs_path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map(|cwd| cwd.join(path))
+            .map_err(|_| GitError::NotARepo)?
+    };
+
+    let repo = Repository::discover(&abs_path).map_err(|_| GitError::NotARepo)?;
+    let repo_root = repo.workdir().ok_or(GitError::NotARepo)?.to_path_buf();
+
+    let mut options = StatusOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .include_ignored(false)
+        .exclude_submodules(true);
+    let statuses = repo.statuses(Some(&mut options))?;
+
+    let mut files = Vec::new();
+    for entry in statuses.iter() {
+        let Some(relative_path) = entry.path() else {
+            continue;
+        };
+        let status = entry.status();
+
+        // Staged as new and then deleted: nothing to show against HEAD
+        if status.contains(Status::INDEX_NEW | Status::WT_DELETED) {
+            continue;
+        }
+        let change = if status.intersects(Status::INDEX_DELETED | Status::WT_DELETED) {
+            FileChange::Deleted
+        } else if status.intersects(Status::INDEX_NEW | Status::WT_NEW) {
+            FileChange::Added
+        } else if status.intersects(
+            Status::INDEX_MODIFIED
+                | Status::WT_MODIFIED
+                | Status::INDEX_RENAMED
+                | Status::WT_RENAMED
+                | Status::INDEX_TYPECHANGE
+                | Status::WT_TYPECHANGE,
+        ) {
+            FileChange::Modified
+        } else {
+            continue;
+        };
+
+        let binary = match change {
+            FileChange::Deleted => head_blob_is_binary(&repo, Path::new(relative_path)),
+            _ => is_binary_file(&repo_root.join(relative_path)),
+        };
+
+        files.push(ChangedFile {
+            repo_root: repo_root.clone(),
+            relative_path: relative_path.to_string(),
+            change,
+            binary,
+        });
+    }
+
+    files.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    Ok(files)
+}
+
+/// List files touched between the merge base of `base` and HEAD, plus any
+/// uncommitted changes on top, sorted by path. This is what a reviewer of
+/// `base...HEAD` sees when the working copy is diffed against the merge base.
+pub fn changed_files_since(path: &str, base: &str) -> Result<Vec<ChangedFile>, GitError> {
+    let (repo, _) = open_repo_for(path)?;
+    let repo_root = repo.workdir().ok_or(GitError::NotARepo)?.to_path_buf();
+    let base_tree = merge_base_tree(&repo, base)?;
+
+    let mut options = DiffOptions::new();
+    options.include_untracked(true).recurse_untracked_dirs(true);
+    let diff = repo.diff_tree_to_workdir_with_index(Some(&base_tree), Some(&mut options))?;
+
+    let mut files = Vec::new();
+    for delta in diff.deltas() {
+        let change = match delta.status() {
+            Delta::Deleted => FileChange::Deleted,
+            Delta::Added | Delta::Untracked => FileChange::Added,
+            Delta::Modified | Delta::Renamed | Delta::Copied | Delta::Typechange => FileChange::Modified,
+            _ => continue,
+        };
+        let file = match change {
+            FileChange::Deleted => delta.old_file(),
+            _ => delta.new_file(),
+        };
+        let Some(relative_path) = file.path().and_then(|p| p.to_str()) else {
+            continue;
+        };
+
+        let binary = match change {
+            FileChange::Deleted => tree_blob_is_binary(&repo, &base_tree, Path::new(relative_path)),
+            _ => is_binary_file(&repo_root.join(relative_path)),
+        };
+
+        files.push(ChangedFile {
+            repo_root: repo_root.clone(),
+            relative_path: relative_path.to_string(),
+            change,
+            binary,
+        });
+    }
+
+    files.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    Ok(files)
+}
+
+/// Get the content of a file at the merge base of `base` and HEAD.
+/// Fails with `NotInHead` when the file doesn't exist there (added since).
+pub fn get_base_content(path: &str, base: &str) -> Result<String, GitError> {
+    let (repo, relative_path) = open_repo_for(path)?;
+    let tree = merge_base_tree(&repo, base)?;
+    read_tree_file(&repo, &tree, &relative_path)
+}
+
+/// Check that `base` resolves and shares history with HEAD.
+pub fn validate_base(path: &str, base: &str) -> Result<(), GitError> {
+    let (repo, _) = open_repo_for(path)?;
+    merge_base_tree(&repo, base).map(|_| ())
+}
+
+/// Get the committed content of a changed file, which works even when the
+/// file no longer exists in the working tree. Reads HEAD, or the merge base
+/// when reviewing against `base`.
+pub fn get_changed_file_base_content(file: &ChangedFile, base: Option<&str>) -> Result<String, GitError> {
+    let repo = Repository::open(&file.repo_root).map_err(|_| GitError::NotARepo)?;
+    let tree = match base {
+        Some(base) => merge_base_tree(&repo, base)?,
+        None => repo.head()?.peel_to_commit()?.tree()?,
+    };
+    read_tree_file(&repo, &tree, Path::new(&file.relative_path))
+}
+
+/// `user.name` from the git config that applies to `path` (the repository's
+/// config when inside one, otherwise the global config).
+pub fn user_name(path: &str) -> Option<String> {
+    let path = Path::new(path);
+    let dir = if path.is_dir() { path } else { path.parent().unwrap_or(Path::new(".")) };
+    let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+    let abs_dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let config = match Repository::discover(abs_dir).and_then(|repo| repo.config()) {
+        Ok(config) => config,
+        Err(_) => git2::Config::open_default().ok()?,
+    };
+    config
+        .get_string("user.name")
+        .ok()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// Root of the working tree containing the existing file at `path`, and
+/// the file's path relative to it.
+pub fn work_tree_path(path: &str) -> Option<(PathBuf, PathBuf)> {
+    let (repo, relative_path) = open_repo_for(path).ok()?;
+    let workdir = repo.workdir()?.to_path_buf();
+    (!relative_path.as_os_str().is_empty()).then_some((workdir, relative_path))
+}
+
+/// `.gitignore` rules of the repository containing a directory, if any.
+pub struct IgnoreRules {
+    repo: Option<Repository>,
+}
+
+impl IgnoreRules {
+    /// Finds the repository containing `path`. Outside a repository nothing is ignored.
+    pub fn discover(path: &Path) -> Self {
+        let abs_path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        IgnoreRules {
+            repo: Repository::discover(abs_path).ok(),
+        }
+    }
+
+    /// Check whether `path` (a file or directory) is excluded by `.gitignore`
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        let Some(repo) = &self.repo else {
+            return false;
+        };
+        let Some(workdir) = repo.workdir() else {
+            return false;
+        };
+        let abs_path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        match abs_path.strip_prefix(workdir) {
+            Ok(relative) if !relative.as_os_str().is_empty() => {
+                repo.is_path_ignored(relative).unwrap_or(false)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Resolve a path to its repository and its path relative to the working tree.
+fn open_repo_for(path: &str) -> Result<(Repository, PathBuf), GitError> {
+    let path = Path::new(path);
+    let abs_path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map(|cwd| cwd.join(path))
+            .map_err(|_| GitError::NotARepo)?
+    };
+    // Canonicalize to resolve symlinks (important on macOS where /var -> /private/var)
+    let abs_path = abs_path.canonicalize().unwrap_or(abs_path);
+
+    let repo = Repository::discover(&abs_path).map_err(|_| GitError::NotARepo)?;
+    let workdir = repo.workdir().ok_or(GitError::NotARepo)?;
+    let relative_path = abs_path
+        .strip_prefix(workdir)
+        .map(|p| p.to_path_buf())
+        .unwrap_or_default();
+
+    Ok((repo, relative_path))
+}
+
+/// Tree of the merge base between `base` and HEAD (the `base...HEAD` starting point).
+fn merge_base_tree<'r>(repo: &'r Repository, base: &str) -> Result<Tree<'r>, GitError> {
+    let base_commit = repo
+        .revparse_single(base)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|_| GitError::InvalidBase(base.to_string()))?;
+    let head_commit = repo.head()?.peel_to_commit()?;
+    let merge_base = repo.merge_base(base_commit.id(), head_commit.id())?;
+
+    Ok(repo.find_commit(merge_base)?.tree()?)
+}
+
+fn read_tree_file(repo: &Repository, tree: &Tree, relative_path: &Path) -> Result<String, GitError> {
+    let entry = tree.get_path(relative_path).map_err(|_| GitError::NotInHead)?;
+    let blob = repo.find_blob(entry.id())?;
+    let content = std::str::from_utf8(blob.content())
+        .map_err(|_| GitError::Git(git2::Error::from_str("Invalid UTF-8 content")))?;
+
+    Ok(content.to_string())
+}
+
+/// Same heuristic as git: a NUL byte in the first 8000 bytes, or content
+/// that isn't valid UTF-8 (the editor can't display it either way).
+fn is_binary_file(path: &Path) -> bool {
+    match std::fs::read(path) {
+        Ok(bytes) => bytes.iter().take(8000).any(|&b| b == 0) || std::str::from_utf8(&bytes).is_err(),
+        Err(_) => false,
+    }
+}
+
+fn head_blob_is_binary(repo: &Repository, relative_path: &Path) -> bool {
+    match repo.head().and_then(|h| h.peel_to_commit()).and_then(|c| c.tree()) {
+        Ok(tree) => tree_blob_is_binary(repo, &tree, relative_path),
+        Err(_) => false,
+    }
+}
+
+fn tree_blob_is_binary(repo: &Repository, tree: &Tree, relative_path: &Path) -> bool {
+    let blob = tree
+        .get_path(relative_path)
+        .and_then(|e| repo.find_blob(e.id()));
+    match blob {
+        Ok(blob) => blob.is_binary() || std::str::from_utf8(blob.content()).is_err(),
+        Err(_) => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -208,6 +505,31 @@ mod tests {
         assert!(is_git_available(file_path.to_str().unwrap()));
     }
 
+    #[test]
+    fn test_user_name_from_repo_config() {
+        let dir = create_git_repo();
+        let file_path = dir.path().join("new.txt");
+
+        assert_eq!(user_name(file_path.to_str().unwrap()).as_deref(), Some("Test User"));
+    }
+
+    #[test]
+    fn test_work_tree_path() {
+        let dir = create_git_repo();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        let file_path = dir.path().join("sub/data.json");
+        fs::write(&file_path, "{}").unwrap();
+
+        let (root, relative) = work_tree_path(file_path.to_str().unwrap()).unwrap();
+        assert_eq!(root, dir.path().canonicalize().unwrap());
+        assert_eq!(relative, Path::new("sub/data.json"));
+
+        let outside = TempDir::new().unwrap();
+        let file_path = outside.path().join("data.json");
+        fs::write(&file_path, "{}").unwrap();
+        assert_eq!(work_tree_path(file_path.to_str().unwrap()), None);
+    }
+
     #[test]
     fn test_is_file_tracked_untracked() {
         let dir = create_git_repo();
@@ -279,4 +601,192 @@ mod tests {
         let result = get_head_content(file_path.to_str().unwrap());
         assert!(matches!(result, Err(GitError::NotARepo)));
     }
+
+    fn git(dir: &TempDir, args: &[&str]) {
+        Command::new("git")
+            .args(args)
+            .current_dir(dir.path())
+            .output()
+            .expect("Failed to run git");
+    }
+
+    #[test]
+    fn test_changed_files_reports_status() {
+        let dir = create_git_repo();
+        add_and_commit_file(&dir, "modified.txt", "one");
+        add_and_commit_file(&dir, "deleted.txt", "gone");
+        add_and_commit_file(&dir, "clean.txt", "same");
+
+        fs::write(dir.path().join("modified.txt"), "two").unwrap();
+        fs::remove_file(dir.path().join("deleted.txt")).unwrap();
+        fs::write(dir.path().join("added.txt"), "new").unwrap();
+        fs::write(dir.path().join("staged.txt"), "staged").unwrap();
+        git(&dir, &["add", "staged.txt"]);
+
+        let files = changed_files(dir.path().to_str().unwrap()).unwrap();
+        let summary: Vec<(&str, FileChange)> = files
+            .iter()
+            .map(|f| (f.relative_path.as_str(), f.change))
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                ("added.txt", FileChange::Added),
+                ("deleted.txt", FileChange::Deleted),
+                ("modified.txt", FileChange::Modified),
+                ("staged.txt", FileChange::Added),
+            ]
+        );
+        assert!(files.iter().all(|f| !f.binary));
+    }
+
+    #[test]
+    fn test_changed_files_flags_binary() {
+        let dir = create_git_repo();
+        add_and_commit_file(&dir, "initial.txt", "initial");
+        fs::write(dir.path().join("image.bin"), [0u8, 159, 146, 150]).unwrap();
+
+        let files = changed_files(dir.path().to_str().unwrap()).unwrap();
+        assert_eq!(files.len(), 1);
+        assert!(files[0].binary);
+    }
+
+    #[test]
+    fn test_changed_files_skips_ignored() {
+        let dir = create_git_repo();
+        add_and_commit_file(&dir, ".gitignore", "target/\n");
+        fs::create_dir(dir.path().join("target")).unwrap();
+        fs::write(dir.path().join("target").join("out.txt"), "build").unwrap();
+
+        let files = changed_files(dir.path().to_str().unwrap()).unwrap();
+        assert!(files.is_empty());
+    }
+
+    #[test]
+    fn test_changed_files_no_repo() {
+        let dir = TempDir::new().unwrap();
+        let result = changed_files(dir.path().to_str().unwrap());
+        assert!(matches!(result, Err(GitError::NotARepo)));
+    }
+
+    #[test]
+    fn test_get_changed_file_head_content_deleted() {
+        let dir = create_git_repo();
+        add_and_commit_file(&dir, "deleted.txt", "old content");
+        fs::remove_file(dir.path().join("deleted.txt")).unwrap();
+
+        let files = changed_files(dir.path().to_str().unwrap()).unwrap();
+        assert_eq!(files[0].change, FileChange::Deleted);
+        assert_eq!(get_changed_file_base_content(&files[0], None).unwrap(), "old content");
+    }
+
+    #[test]
+    fn test_changed_files_skips_staged_then_deleted() {
+        let dir = create_git_repo();
+        add_and_commit_file(&dir, "kept.txt", "kept");
+        fs::write(dir.path().join("brief.txt"), "short-lived").unwrap();
+        git(&dir, &["add", "brief.txt"]);
+        fs::remove_file(dir.path().join("brief.txt")).unwrap();
+
+        assert!(changed_files(dir.path().to_str().unwrap()).unwrap().is_empty());
+        let since = changed_files_since(dir.path().to_str().unwrap(), "HEAD").unwrap();
+        assert!(since.iter().all(|f| get_changed_file_base_content(f, Some("HEAD")).is_ok()));
+    }
+
+    #[test]
+    fn test_ignore_rules() {
+        let dir = create_git_repo();
+        fs::write(dir.path().join(".gitignore"), "target/\n*.log\n").unwrap();
+        fs::create_dir(dir.path().join("target")).unwrap();
+        fs::write(dir.path().join("debug.log"), "").unwrap();
+        fs::write(dir.path().join("main.rs"), "").unwrap();
+
+        let rules = IgnoreRules::discover(dir.path());
+        assert!(rules.is_ignored(&dir.path().join("target")));
+        assert!(rules.is_ignored(&dir.path().join("debug.log")));
+        assert!(!rules.is_ignored(&dir.path().join("main.rs")));
+        assert!(!rules.is_ignored(dir.path()));
+    }
+
+    #[test]
+    fn test_ignore_rules_outside_repo() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("debug.log"), "").unwrap();
+        let rules = IgnoreRules::discover(dir.path());
+        assert!(!rules.is_ignored(&dir.path().join("debug.log")));
+    }
+
+    /// Repo with `main` at "base" and a `feature` branch with one more commit.
+    fn create_feature_branch_repo() -> TempDir {
+        let dir = create_git_repo();
+        git(&dir, &["checkout", "-b", "main"]);
+        add_and_commit_file(&dir, "shared.txt", "base");
+        add_and_commit_file(&dir, "removed.txt", "to remove");
+        git(&dir, &["checkout", "-b", "feature"]);
+        add_and_commit_file(&dir, "shared.txt", "feature");
+        add_and_commit_file(&dir, "added.txt", "new");
+        git(&dir, &["rm", "-q", "removed.txt"]);
+        git(&dir, &["commit", "-q", "-m", "Remove file"]);
+        dir
+    }
+
+    #[test]
+    fn test_get_base_content_uses_merge_base() {
+        let dir = create_feature_branch_repo();
+        // Advancing main after branching must not change the merge base
+        git(&dir, &["checkout", "-q", "main"]);
+        add_and_commit_file(&dir, "shared.txt", "main moved on");
+        git(&dir, &["checkout", "-q", "feature"]);
+
+        let path = dir.path().join("shared.txt");
+        assert_eq!(get_base_content(path.to_str().unwrap(), "main").unwrap(), "base");
+        // HEAD has the committed feature version
+        assert_eq!(get_head_content(path.to_str().unwrap()).unwrap(), "feature");
+    }
+
+    #[test]
+    fn test_get_base_content_added_on_branch() {
+        let dir = create_feature_branch_repo();
+        let path = dir.path().join("added.txt");
+        let result = get_base_content(path.to_str().unwrap(), "main");
+        assert!(matches!(result, Err(GitError::NotInHead)));
+    }
+
+    #[test]
+    fn test_get_base_content_invalid_base() {
+        let dir = create_feature_branch_repo();
+        let path = dir.path().join("shared.txt");
+        let result = get_base_content(path.to_str().unwrap(), "no-such-branch");
+        assert!(matches!(result, Err(GitError::InvalidBase(_))));
+        assert!(validate_base(path.to_str().unwrap(), "no-such-branch").is_err());
+        assert!(validate_base(path.to_str().unwrap(), "main").is_ok());
+    }
+
+    #[test]
+    fn test_changed_files_since_base() {
+        let dir = create_feature_branch_repo();
+        fs::write(dir.path().join("wip.txt"), "uncommitted").unwrap();
+
+        let files = changed_files_since(dir.path().to_str().unwrap(), "main").unwrap();
+        let summary: Vec<(&str, FileChange)> = files
+            .iter()
+            .map(|f| (f.relative_path.as_str(), f.change))
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                ("added.txt", FileChange::Added),
+                ("removed.txt", FileChange::Deleted),
+                ("shared.txt", FileChange::Modified),
+                ("wip.txt", FileChange::Added),
+            ]
+        );
+
+        // Deleted files are read from the merge base, not HEAD
+        let removed = &files[1];
+        assert!(get_changed_file_base_content(removed, None).is_err());
+        assert_eq!(get_changed_file_base_content(removed, Some("main")).unwrap(), "to remove");
+    }
 }
@@ -1,15 +1,23 @@
 use syntect::parsing::SyntaxSet;
 use syntect::highlighting::{Theme, ThemeSettings, ThemeItem, Color, Style, FontStyle, ScopeSelectors};
 use crossterm::style::{Color as CrosstermColor};
+use std::sync::OnceLock;
+
+/// Syntax definitions, loaded once and shared by highlighting and
+/// language detection.
+pub fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAX_SET: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAX_SET.get_or_init(SyntaxSet::load_defaults_newlines)
+}
 
 pub struct SyntaxHighlighter {
-    pub syntax_set: SyntaxSet,
+    pub syntax_set: &'static SyntaxSet,
     pub theme: Theme,
 }
 
 impl SyntaxHighlighter {
     pub fn new(dark_mode: bool) -> Self {
-        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let syntax_set = syntax_set();
         let theme = if dark_mode {
             create_zenbones_dark()
         } else {
@@ -22,12 +30,14 @@ impl SyntaxHighlighter {
         }
     }
 
-    pub fn highlight<'a>(&self, line: &'a str, extension: &str) -> Vec<(Style, &'a str)> {
-        let syntax = self.syntax_set.find_syntax_by_extension(extension)
+    /// Highlights one line with the named syntax (see `language::detect`).
+    pub fn highlight<'a>(&self, line: &'a str, syntax: Option<&str>) -> Vec<(Style, &'a str)> {
+        let syntax = syntax
+            .and_then(|name| self.syntax_set.find_syntax_by_name(name))
             .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text());
         
         let mut h = syntect::easy::HighlightLines::new(syntax, &self.theme);
-        h.highlight_line(line, &self.syntax_set).unwrap_or_else(|_| vec![(Style::default(), line)])
+        h.highlight_line(line, self.syntax_set).unwrap_or_else(|_| vec![(Style::default(), line)])
     }
 }
 
@@ -61,6 +71,7 @@ fn create_zenbones_light() -> Theme {
     build_alabaster_theme("Alabaster Light", bg, fg, comment, string, constant, definition, regex)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn build_alabaster_theme(
     name: &str, 
     bg: Color, 
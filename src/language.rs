@@ -0,0 +1,325 @@
+//! Working out a file's language: the comment style its annotation markers
+//! use and the syntax it is highlighted with.
+//!
+//! Detection tries, in order: the user's `[[languages]]` config mappings,
+//! well-known file names and extensions, a shebang or editor modeline in the
+//! content, then syntect's syntax definitions (matched by name, extension or
+//! first line), whose metadata carries each language's comment tokens.
+
+use crate::config::{self, LanguageRule};
+use crate::highlighting;
+use std::path::Path;
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+
+/// Comment style used when nothing else matches
+pub const FALLBACK_COMMENT: &str = "//";
+
+/// How many lines at the start and end of a file are searched for a modeline
+const MODELINE_LINES: usize = 5;
+
+/// A file's comment style and highlighting syntax.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Language {
+    /// Comment style in `lang_comment` form (see `file::CommentStyle`)
+    pub comment: String,
+    /// Name of the syntect syntax to highlight with (plain text if `None`)
+    pub syntax: Option<String>,
+}
+
+/// Detects the language of the file at `path` holding `content`. The
+/// content may be empty when only the path is known.
+pub fn detect(path: &str, content: &str) -> Language {
+    detect_with_rules(&config::get().languages, path, content)
+}
+
+fn detect_with_rules(rules: &[LanguageRule], path: &str, content: &str) -> Language {
+    let syntax_set = highlighting::syntax_set();
+    let file_name = Path::new(path).file_name().and_then(|n| n.to_str()).unwrap_or(path);
+    let extension = Path::new(file_name).extension().and_then(|e| e.to_str()).unwrap_or("");
+
+    let rule = rules.iter().find(|rule| rule.matches(path));
+    let by_rule = rule
+        .and_then(|rule| rule.syntax.as_deref())
+        .and_then(|name| syntax_set.find_syntax_by_name(name));
+
+    let by_path = by_rule
+        .or_else(|| syntax_set.find_syntax_by_extension(file_name))
+        .or_else(|| syntax_set.find_syntax_by_extension(extension));
+    let by_content = || {
+        content_token(content)
+            .and_then(|token| syntax_set.find_syntax_by_token(&token))
+            .or_else(|| content.lines().next().and_then(|line| syntax_set.find_syntax_by_first_line(line)))
+    };
+    let syntax = by_path.or_else(by_content);
+
+    let comment = rule
+        .and_then(|rule| rule.comment.clone())
+        .or_else(|| by_rule.and_then(|syntax| syntax_comment(syntax_set, syntax)))
+        .or_else(|| file_name_comment(file_name).or_else(|| extension_comment(extension)).map(str::to_string))
+        .or_else(|| by_content().and_then(|syntax| syntax_comment(syntax_set, syntax)))
+        .or_else(|| by_path.and_then(|syntax| syntax_comment(syntax_set, syntax)))
+        .unwrap_or_else(|| FALLBACK_COMMENT.to_string());
+
+    Language {
+        comment,
+        syntax: syntax.map(|syntax| syntax.name.clone()),
+    }
+}
+
+/// Comment styles for files known by their whole name.
+fn file_name_comment(file_name: &str) -> Option<&'static str> {
+    let comment = match file_name {
+        "Makefile" | "makefile" | "GNUmakefile" | "CMakeLists.txt" | "BUILD" | "BUILD.bazel"
+        | "WORKSPACE" | "WORKSPACE.bazel" | "Dockerfile" | "Containerfile" | "Gemfile" | "Rakefile"
+        | "Vagrantfile" | "Brewfile" | "Procfile" | "Pipfile" | ".bashrc" | ".bash_profile"
+        | ".bash_aliases" | ".zshrc" | ".zprofile" | ".profile" | ".gitignore" | ".gitattributes"
+        | ".dockerignore" | ".editorconfig" | ".env" => "#",
+        "Jenkinsfile" => "//",
+        ".vimrc" => "\"",
+        _ if file_name.starts_with("Dockerfile.") || file_name.starts_with(".env.") => "#",
+        _ => return None,
+    };
+    Some(comment)
+}
+
+/// Comment styles by extension. These take precedence over syntect, e.g.
+/// Markdown uses bare markers rather than HTML comments.
+fn extension_comment(extension: &str) -> Option<&'static str> {
+    let comment = match extension.to_ascii_lowercase().as_str() {
+        "rs" | "go" | "java" | "kt" | "js" | "ts" | "jsx" | "tsx" | "mjs" | "cjs" | "c" | "cpp" | "h"
+        | "cs" | "php" | "scala" | "dart" | "swift" | "scss" | "less" | "groovy" | "gradle" => "//",
+        "py" | "sh" | "rb" | "yaml" | "yml" | "toml" | "pl" | "r" | "dockerfile" | "patch" | "diff"
+        | "cmake" | "mk" | "mak" | "bzl" | "bazel" | "tf" | "conf" | "cfg" => "#",
+        "sql" | "lua" | "hs" | "ada" => "--",
+        "html" | "htm" | "xhtml" | "xml" | "svg" | "vue" | "svelte" => "<!-- -->",
+        "css" => "/* */",
+        "ini" => ";",
+        "vim" => "\"",
+        "md" => "",
+        _ => return None,
+    };
+    Some(comment)
+}
+
+/// Language named by a shebang (`#!/usr/bin/env python3`) or a modeline
+/// (`-*- mode: ruby -*-`, `vim: set ft=sh:`).
+fn content_token(content: &str) -> Option<String> {
+    let lines: Vec<&str> = content.lines().collect();
+    if let Some(interpreter) = lines.first().and_then(|line| shebang_interpreter(line)) {
+        return Some(interpreter);
+    }
+
+    let head = lines.iter().take(MODELINE_LINES);
+    let tail = lines.iter().rev().take(MODELINE_LINES);
+    head.chain(tail).find_map(|line| modeline_language(line))
+}
+
+fn shebang_interpreter(line: &str) -> Option<String> {
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        // Skip `env` flags such as `-S` and variable assignments
+        program = words.find(|word| !word.starts_with('-') && !word.contains('='))?;
+    }
+
+    // python3.11 -> python
+    let name = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    let token = match name {
+        "node" | "nodejs" | "deno" | "bun" => "js",
+        "Rscript" => "r",
+        "" => return None,
+        other => other,
+    };
+    Some(token.to_string())
+}
+
+fn modeline_language(line: &str) -> Option<String> {
+    // Emacs: -*- mode: python -*- or -*- python -*-
+    if let Some(start) = line.find("-*-") {
+        let rest = &line[start + 3..];
+        let inner = &rest[..rest.find("-*-")?];
+        let mode = inner
+            .split(';')
+            .find_map(|part| part.trim().strip_prefix("mode:").map(str::trim))
+            .or_else(|| Some(inner.trim()).filter(|inner| !inner.contains(':')))?;
+        return Some(mode.to_lowercase()).filter(|mode| !mode.is_empty());
+    }
+
+    // Vim: vim: set ft=python: or vi: filetype=sh
+    let (_, settings) = line.split_once("vim:").or_else(|| line.split_once("vi:"))?;
+    settings
+        .split(|c: char| c.is_whitespace() || c == ':')
+        .find_map(|setting| setting.strip_prefix("ft=").or_else(|| setting.strip_prefix("filetype=")))
+        .filter(|ft| !ft.is_empty())
+        .map(str::to_string)
+}
+
+/// Comment style for a syntax: from the extension table when its main
+/// extension is listed there, otherwise from syntect's comment metadata,
+/// preferring line comments.
+fn syntax_comment(syntax_set: &SyntaxSet, syntax: &SyntaxReference) -> Option<String> {
+    if let Some(comment) = syntax.file_extensions.first().and_then(|ext| extension_comment(ext)) {
+        return Some(comment.to_string());
+    }
+
+    let metadata = syntax_set.metadata().metadata_for_scope(&[syntax.scope]);
+    if let Some(line) = metadata.line_comment().map(str::trim).filter(|line| !line.is_empty()) {
+        return Some(line.to_string());
+    }
+    // Only single-line open/close pairs without spaces fit `lang_comment`
+    let (open, close) = metadata.block_comment()?;
+    let (open, close) = (open.trim(), close.trim());
+    let fits = |token: &str| !token.is_empty() && !token.contains(char::is_whitespace);
+    (fits(open) && fits(close)).then(|| format!("{} {}", open, close))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comment(path: &str, content: &str) -> String {
+        detect(path, content).comment
+    }
+
+    #[test]
+    fn test_detect_by_file_name() {
+        assert_eq!(comment("Makefile", ""), "#");
+        assert_eq!(comment("build/CMakeLists.txt", ""), "#");
+        assert_eq!(comment("BUILD", ""), "#");
+        assert_eq!(comment("/home/me/.bashrc", ""), "#");
+        assert_eq!(comment("Jenkinsfile", ""), "//");
+        assert_eq!(comment("Dockerfile.dev", ""), "#");
+    }
+
+    #[test]
+    fn test_detect_by_shebang() {
+        assert_eq!(comment("bin/tool", "#!/usr/bin/env python3\nprint(1)\n"), "#");
+        assert_eq!(comment("run", "#!/bin/bash\necho hi\n"), "#");
+        assert_eq!(comment("serve", "#!/usr/bin/env -S node --no-warnings\n"), "//");
+        assert_eq!(detect("bin/tool", "#!/usr/bin/env python3\n").syntax.as_deref(), Some("Python"));
+    }
+
+    #[test]
+    fn test_detect_by_modeline() {
+        assert_eq!(comment("script", "# -*- mode: ruby -*-\nputs 1\n"), "#");
+        assert_eq!(comment("notes", "x\ny\n-- vim: set ft=lua:\n"), "--");
+    }
+
+    #[test]
+    fn test_detect_from_syntect_metadata() {
+        // Not in the tables; syntect knows the language and its comments
+        assert_eq!(comment("core.clj", ""), ";");
+        assert_eq!(comment("paper.tex", ""), "%");
+        assert_eq!(comment("lib.ml", ""), "(* *)");
+    }
+
+    #[test]
+    fn test_extension_table_wins_over_content() {
+        assert_eq!(comment("README.md", "#!/bin/sh\n"), "");
+        assert_eq!(detect("README.md", "").syntax.as_deref(), Some("Markdown"));
+    }
+
+    #[test]
+    fn test_unknown_falls_back() {
+        let language = detect("notes.xyz", "hello\n");
+        assert_eq!(language.comment, FALLBACK_COMMENT);
+        assert_eq!(language.syntax, None);
+    }
+
+    #[test]
+    fn test_config_rules_override_tables() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join(config::PROJECT_CONFIG_FILE);
+        std::fs::write(
+            &path,
+            r##"
+[[languages]]
+extensions = ["flow"]
+comment = "#"
+
+[[languages]]
+extensions = ["bzl", "rs"]
+syntax = "Python"
+
+[[languages]]
+patterns = ["*.gql"]
+block_comment = ["/*", "*/"]
+"##,
+        )
+        .unwrap();
+        let mut config = config::Config::default();
+        config::apply_file(&mut config, &path).unwrap();
+        let detect = |path: &str| detect_with_rules(&config.languages, path, "");
+
+        assert_eq!(detect("app.flow"), Language { comment: "#".to_string(), syntax: None });
+        let rust_as_python = detect("src/main.rs");
+        assert_eq!(rust_as_python.comment, "#");
+        assert_eq!(rust_as_python.syntax.as_deref(), Some("Python"));
+        assert_eq!(detect("schema.gql").comment, "/* */");
+        assert_eq!(detect("lib.go").comment, "//");
+    }
+
+    #[test]
+    fn test_shebang_interpreter() {
+        assert_eq!(shebang_interpreter("#!/usr/bin/python3.11").as_deref(), Some("python"));
+        assert_eq!(shebang_interpreter("#!/usr/bin/env FOO=1 ruby").as_deref(), Some("ruby"));
+        assert_eq!(shebang_interpreter("# not a shebang"), None);
+    }
+
+    #[test]
+    fn test_detect_comment_style_rust() {
+        assert_eq!(comment("main.rs", ""), "//");
+    }
+
+    #[test]
+    fn test_detect_comment_style_python() {
+        assert_eq!(comment("script.py", ""), "#");
+    }
+
+    #[test]
+    fn test_detect_comment_style_markdown() {
+        assert_eq!(comment("README.md", ""), "");
+    }
+
+    #[test]
+    fn test_detect_comment_style_unknown() {
+        assert_eq!(comment("file.xyz", ""), "//");
+    }
+
+    #[test]
+    fn test_detect_comment_style_sql() {
+        assert_eq!(comment("query.sql", ""), "--");
+    }
+
+    #[test]
+    fn test_detect_comment_style_patch() {
+        assert_eq!(comment("fix.patch", ""), "#");
+        assert_eq!(comment("changes.diff", ""), "#");
+    }
+
+    #[test]
+    fn test_detect_comment_style_docker() {
+        assert_eq!(comment("Dockerfile", ""), "#");
+        assert_eq!(comment("dev.dockerfile", ""), "#");
+    }
+
+    #[test]
+    fn test_detect_comment_style_config() {
+        assert_eq!(comment("config.yaml", ""), "#");
+        assert_eq!(comment("Cargo.toml", ""), "#");
+    }
+
+    #[test]
+    fn test_detect_comment_style_swift() {
+        assert_eq!(comment("App.swift", ""), "//");
+    }
+
+    #[test]
+    fn test_detect_comment_style_block_languages() {
+        assert_eq!(comment("index.html", ""), "<!-- -->");
+        assert_eq!(comment("icon.svg", ""), "<!-- -->");
+        assert_eq!(comment("App.vue", ""), "<!-- -->");
+        assert_eq!(comment("site.css", ""), "/* */");
+        assert_eq!(comment("App.tsx", ""), "//");
+    }
+}
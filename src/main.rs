@@ -1,56 +1,289 @@
+mod annotate;
+mod atomic;
+mod buffer;
+mod clean;
+mod cli;
+mod config;
 mod diff;
 mod editor;
 mod event_handler;
+mod export;
 mod file;
 mod git;
 mod highlighting;
+mod language;
+mod migrate;
 mod models;
 mod navigation;
+mod patch;
+mod reanchor;
+mod report;
+mod scan;
+mod sidecar;
 mod text;
 mod theme;
 mod ui;
 mod ui_diff;
 
+use buffer::Buffer;
+use cli::{AnnotateOptions, CleanOptions, Command, EditOptions, ExportOptions, ListOptions, MigrateOptions};
 use editor::Editor;
-use std::io;
+use std::io::{self, Read};
 
 fn main() -> io::Result<()> {
-    let args: Vec<String> = std::env::args().collect();
-
-    if args.len() > 1 && (args[1] == "-h" || args[1] == "--help") {
-        println!("nanot - nano for annotations and code review");
-        println!("\nUsage: nanot [file]");
-        println!("\nKeyboard shortcuts:");
-        println!("  ^X        Exit");
-        println!("  ^O        Save file");
-        println!("  ^W        Search");
-        println!("  ^T        Toggle theme");
-        println!("  ^G        Toggle Help Overlay");
-        println!("  ^K        Toggle diff view");
-        println!("  ^D        Delete annotation");
-        println!("  ^N        Next annotation");
-        println!("  ^P        Prev annotation");
-        println!("  Enter     Add/edit annotation");
-        println!("  ↑↓        Navigate lines");
-        println!("  PgUp/PgDn (Alt+↑/↓) Page navigation");
-        return Ok(());
-    }
-
-    let file_path = if args.len() > 1 {
-        args[1].clone()
-    } else {
-        println!("Error: No file specified.");
-        println!("Usage: nanot <file>");
-        std::process::exit(1);
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+
+    match cli::take_marker_option(&mut args).and_then(config::load) {
+        Ok(config) => config::init(config),
+        Err(message) => {
+            println!("Error: {}", message);
+            std::process::exit(1);
+        }
+    }
+
+    let options = match cli::parse_args(&args) {
+        Ok(Command::Help) => {
+            print_help();
+            return Ok(());
+        }
+        Ok(Command::Edit(options)) => options,
+        Ok(Command::List(options)) => return run_list(options),
+        Ok(Command::Export(options)) => return run_export(options),
+        Ok(Command::Annotate(options)) => return run_annotate(options),
+        Ok(Command::Clean(options)) => return run_clean(options),
+        Ok(Command::Migrate(options)) => return run_migrate(options),
+        Err(message) => {
+            println!("Error: {}", message);
+            println!("Usage: nanot <file>...");
+            std::process::exit(1);
+        }
     };
 
-    if !std::path::Path::new(&file_path).exists() {
-        println!("Error: File '{}' does not exist.", file_path);
+    let mut editor = open_editor(options)?;
+    let report = editor.run()?;
+    if report.has_changes() {
+        println!("{}", report.summary());
+    }
+    std::process::exit(report.outcome.exit_code());
+}
+
+fn print_help() {
+    println!("nanot - nano for annotations and code review");
+    println!("\nUsage: nanot [+LINE[,COL]] <file>[:LINE]...");
+    println!("       nanot --changed     Review every file changed in the git repository");
+    println!("       nanot --base <rev>  Diff against the merge base of <rev> and HEAD");
+    println!("                           (with --changed: review files touched in <rev>...HEAD)");
+    println!("       nanot [-o file] -   Review a unified diff from stdin (saved to review.patch,");
+    println!("                           or as JSON when the -o file ends in .json)");
+    println!("       nanot --view <file>...  Read-only: annotation edits and saves are blocked");
+    println!("       nanot --report <file>  Write a JSON report of annotation changes on exit");
+    println!("       nanot --sidecar <file>...  Keep annotations in .nanotation/ instead of the file");
+    println!("       nanot -B <file>...  Back up each file to <file>~ before saving over it");
+    println!("       nanot list [path]...  Print all annotations as path:line: text");
+    println!("       nanot export [--format json|markdown|sarif] [-o file] [path]...");
+    println!("       nanot annotate [--dry-run] [--reply] [--severity <level>] [--by <name>] [--no-stamp] [--resolve] <file>:<line>[-<end>] <text>");
+    println!("       nanot annotate [--dry-run] --resolve|--reopen <file>:<line>");
+    println!("       nanot annotate [--dry-run] --remove <file>:<line>");
+    println!("       nanot clean [--dry-run | --check] [path]...");
+    println!("                           Remove all annotations (--check: fail if any remain)");
+    println!("       nanot migrate --from <tag> [--to <tag>] [--dry-run] [path]...");
+    println!("                           Rewrite markers to another tag (default: the configured one)");
+    println!("\nOptions for every command:");
+    println!("  --marker <tag>  Marker tag to read and write (default [ANNOTATION], or `marker`");
+    println!("                  in .nanot.toml / ~/.config/nanot/config.toml)");
+    println!("\nExit codes (editor): 0 saved, no annotation changes or none left;");
+    println!("                     3 saved annotation changes, some left;");
+    println!("                     4 quit without saving; 1 error");
+    println!("\nKeyboard shortcuts:");
+    println!("  ^X        Exit");
+    println!("  ^O        Save file");
+    println!("  ^W        Search");
+    println!("  ^T        Toggle theme");
+    println!("  ^G        Toggle Help Overlay");
+    println!("  ^D        Toggle diff view");
+    println!("  Del       Delete annotation");
+    println!("  ^K        Resolve / reopen annotation");
+    println!("  ^↓ / ^↑   Extend / shrink the annotated range (while editing)");
+    println!("  M-K       Remove resolved annotations");
+    println!("  ^N        Next annotation");
+    println!("  ^P        Prev annotation");
+    println!("  M-N / M-P Next / Prev open annotation");
+    println!("  ^B        Open files (buffer picker)");
+    println!("  M-. / M-, Next / Prev file");
+    println!("  ^E        Export annotations");
+    println!("  Enter     Add/edit annotation");
+    println!("  ^R        Reply (add an entry to the line's thread)");
+    println!("  Tab       Select next entry in the thread (Shift+Tab: previous)");
+    println!("  M-Enter   New line in annotation");
+    println!("  ↑↓        Navigate lines");
+    println!("  PgUp/PgDn (Alt+↑/↓) Page navigation");
+}
+
+fn run_list(options: ListOptions) -> io::Result<()> {
+    match scan::scan(&options.paths) {
+        Ok(files) => {
+            print!("{}", scan::format_list(&files));
+            Ok(())
+        }
+        Err(e) => {
+            println!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run_export(options: ExportOptions) -> io::Result<()> {
+    let files = match scan::scan(&options.paths) {
+        Ok(files) => files,
+        Err(e) => {
+            println!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let annotations: Vec<_> = files
+        .iter()
+        .flat_map(|file| {
+            if file.sidecar {
+                export::collect_sidecar_annotations(&file.path, &file.lines)
+            } else {
+                export::collect_annotations(&file.path, &file.lines)
+            }
+        })
+        .collect();
+    let output = export::render(&annotations, options.format);
+
+    match options.output {
+        Some(path) => std::fs::write(path, output),
+        None => {
+            print!("{}", output);
+            Ok(())
+        }
+    }
+}
+
+fn run_annotate(options: AnnotateOptions) -> io::Result<()> {
+    match annotate::run(&options) {
+        Ok(output) => {
+            print!("{}", output);
+            Ok(())
+        }
+        Err(message) => {
+            println!("Error: {}", message);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run_clean(options: CleanOptions) -> io::Result<()> {
+    match clean::run(&options) {
+        Ok(outcome) => {
+            print!("{}", outcome.output);
+            if options.check && outcome.found_annotations {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Err(e) => {
+            println!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run_migrate(options: MigrateOptions) -> io::Result<()> {
+    match migrate::run(&options) {
+        Ok(output) => {
+            print!("{}", output);
+            Ok(())
+        }
+        Err(e) => {
+            println!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn open_editor(options: EditOptions) -> io::Result<Editor> {
+    let mut buffers = Vec::new();
+    let base = options.base.as_deref();
+
+    if let Some(base) = base
+        && let Err(e) = git::validate_base(".", base)
+    {
+        println!("Error: {}", e);
         std::process::exit(1);
     }
 
-    let mut editor = Editor::new(file_path)?;
-    editor.run()?;
+    let (width, height) = crossterm::terminal::size().unwrap_or((80, 24));
+    let visible_height = height.saturating_sub(5) as usize;
+
+    let mut read_stdin = false;
+    for file in options.files {
+        // `-` reads a unified diff from stdin, e.g. `git diff | nanot -`
+        if file.path == "-" {
+            if read_stdin {
+                println!("Error: '-' can only be given once.");
+                std::process::exit(1);
+            }
+            read_stdin = true;
+            let mut content = String::new();
+            io::stdin().read_to_string(&mut content)?;
+            buffers.push(Buffer::open_patch(&content, patch::STDIN_PATCH_OUTPUT.to_string()));
+            continue;
+        }
+        if !std::path::Path::new(&file.path).exists() {
+            println!("Error: File '{}' does not exist.", file.path);
+            std::process::exit(1);
+        }
+        let mut buffer = Buffer::open_with_sidecar(file.path, options.sidecar)?;
+        if let Some(line) = file.line {
+            buffer.go_to_line(line, visible_height, width as usize);
+        }
+        buffers.push(buffer);
+    }
+
+    if options.changed {
+        let changed = match base {
+            Some(base) => git::changed_files_since(".", base),
+            None => git::changed_files("."),
+        };
+        let changed = match changed {
+            Ok(changed) => changed,
+            Err(e) => {
+                println!("Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+        for file in &changed {
+            buffers.push(Buffer::open_changed(file, base, options.sidecar)?);
+        }
+    }
+
+    if buffers.is_empty() {
+        println!("No changed files to review.");
+        std::process::exit(0);
+    }
+
+    if let Some(output) = options.output {
+        match buffers.as_mut_slice() {
+            [buffer] if buffer.patch.is_some() => buffer.set_save_path(output),
+            _ => {
+                println!("Error: --output needs exactly one patch to review.");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if options.view {
+        for buffer in &mut buffers {
+            buffer.read_only.get_or_insert_with(|| buffer::VIEW_READ_ONLY.to_string());
+        }
+    }
 
-    Ok(())
-}
\ No newline at end of file
+    let mut editor = Editor::with_buffers(buffers);
+    editor.diff_base = options.base;
+    editor.report_path = options.report;
+    editor.backup = options.backup;
+    editor.author = git::user_name(editor.file_path.as_deref().unwrap_or("."));
+    Ok(editor)
+}
@@ -0,0 +1,163 @@
+//! Rewriting annotation markers from one tag to another (`nanot migrate`).
+
+use crate::atomic;
+use crate::cli::MigrateOptions;
+use crate::config;
+use crate::file;
+use crate::language;
+use crate::scan;
+use similar::TextDiff;
+use std::fs;
+use std::io;
+
+/// Rewrites every marker using `options.from` under `options.paths` to use
+/// `options.to` (or the configured tag). Returns what to print: a diff per
+/// file for a dry run, otherwise one summary line per rewritten file.
+///
+/// Markers are found with the same parser the editor uses, so text that only
+/// looks like a marker (e.g. inside a Markdown code fence) is left alone.
+/// Only the tag changes; the rest of each file is kept byte for byte. Every
+/// file is checked before any is written, so an error leaves the tree as
+/// it was.
+pub fn run(options: &MigrateOptions) -> Result<String, String> {
+    let to = options.to.as_deref().unwrap_or(config::marker_tag());
+    config::validate_marker(&options.from)?;
+    config::validate_marker(to)?;
+    if options.from == to {
+        return Err(format!("Markers already use '{}'; nothing to migrate", to));
+    }
+
+    let mut migrations = Vec::new();
+    for path in scan::collect_files(&options.paths).map_err(|e| e.to_string())? {
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => continue,
+            Err(e) => return Err(format!("Cannot read '{}': {}", path, e)),
+        };
+        let lang_comment = language::detect(&path, &content).comment;
+        let lines = file::parse_file_tagged(&content, &lang_comment, &options.from);
+        let count: usize = lines.iter().map(|line| line.annotations.len()).sum();
+        if count == 0 {
+            continue;
+        }
+        let has_attributes = lines
+            .iter()
+            .flat_map(|line| &line.annotations)
+            .any(|annotation| !annotation.attributes.is_empty());
+        if has_attributes && !config::holds_attributes(to) {
+            return Err(format!(
+                "'{}' has marker attributes, which the tag '{}' can't hold (it must end in ']')",
+                path, to
+            ));
+        }
+
+        let migrated = file::retag_markers(&content, &lang_comment, &options.from, to);
+        migrations.push((path, content, migrated, count));
+    }
+
+    let mut output = String::new();
+    for (path, content, migrated, count) in migrations {
+        if options.dry_run {
+            output.push_str(
+                &TextDiff::from_lines(&content, &migrated)
+                    .unified_diff()
+                    .header(&path, &path)
+                    .to_string(),
+            );
+        } else {
+            atomic::write(&path, migrated).map_err(|e| format!("Cannot write '{}': {}", path, e))?;
+            output.push_str(&format!("{}: migrated {} marker(s)\n", path, count));
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn options(dir: &TempDir, from: &str, to: &str, dry_run: bool) -> MigrateOptions {
+        MigrateOptions {
+            paths: vec![dir.path().to_string_lossy().to_string()],
+            from: from.to_string(),
+            to: Some(to.to_string()),
+            dry_run,
+        }
+    }
+
+    #[test]
+    fn test_migrate_rewrites_markers() {
+        let dir = TempDir::new().unwrap();
+        let rs = dir.path().join("a.rs");
+        let md = dir.path().join("notes.md");
+        fs::write(&rs, "// [ANNOTATION] fix\nfn a() {}\n").unwrap();
+        fs::write(&md, "[ANNOTATION] reword\nIntro\n```\n[ANNOTATION] example\n```\n").unwrap();
+
+        let output = run(&options(&dir, "[ANNOTATION]", "[REVIEW]", false)).unwrap();
+        assert!(output.contains("a.rs: migrated 1 marker(s)\n"));
+        assert_eq!(fs::read_to_string(&rs).unwrap(), "// [REVIEW] fix\nfn a() {}\n");
+        assert_eq!(
+            fs::read_to_string(&md).unwrap(),
+            "[REVIEW] reword\nIntro\n```\n[ANNOTATION] example\n```\n"
+        );
+
+        let lines = file::parse_file_tagged(&fs::read_to_string(&rs).unwrap(), "//", "[REVIEW]");
+        assert_eq!(lines[0].annotations, vec!["fix"]);
+    }
+
+    #[test]
+    fn test_migrate_dry_run_leaves_files() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("a.py");
+        fs::write(&path, "# [ANNOTATION] check\nx = 1\n").unwrap();
+
+        let output = run(&options(&dir, "[ANNOTATION]", "[NANOT]", true)).unwrap();
+        assert!(output.contains("-# [ANNOTATION] check\n"));
+        assert!(output.contains("+# [NANOT] check\n"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "# [ANNOTATION] check\nx = 1\n");
+    }
+
+    #[test]
+    fn test_migrate_keeps_attributes() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("a.rs");
+        fs::write(&path, "// [ANNOTATION sev=nit by=\"Jane Doe\"] fix\nfn a() {}\n").unwrap();
+
+        assert!(run(&options(&dir, "[ANNOTATION]", "TODO:", false)).is_err());
+        run(&options(&dir, "[ANNOTATION]", "[REVIEW]", false)).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "// [REVIEW sev=nit by=\"Jane Doe\"] fix\nfn a() {}\n");
+    }
+
+    #[test]
+    fn test_migrate_only_changes_the_tag() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("a.rs");
+        // An unindented marker above indented code, markers indented more
+        // than their code, CRLF endings and no final newline
+        let content = "fn a() {\r\n// [ANNOTATION] fix\r\n    x();\r\n        // [ANNOTATION sev=nit]   spacing\r\n  // [ANNOTATION]+ more\r\n    y();   \r\n}";
+        fs::write(&path, content).unwrap();
+
+        run(&options(&dir, "[ANNOTATION]", "[REVIEW]", false)).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), content.replace("[ANNOTATION", "[REVIEW"));
+    }
+
+    #[test]
+    fn test_migrate_checks_every_file_before_writing() {
+        let dir = TempDir::new().unwrap();
+        let plain = dir.path().join("a.rs");
+        let with_attributes = dir.path().join("b.rs");
+        fs::write(&plain, "// [ANNOTATION] fix\nfn a() {}\n").unwrap();
+        fs::write(&with_attributes, "// [ANNOTATION sev=nit] fix\nfn b() {}\n").unwrap();
+
+        assert!(run(&options(&dir, "[ANNOTATION]", "TODO:", false)).is_err());
+        assert_eq!(fs::read_to_string(&plain).unwrap(), "// [ANNOTATION] fix\nfn a() {}\n");
+    }
+
+    #[test]
+    fn test_migrate_rejects_bad_tags() {
+        let dir = TempDir::new().unwrap();
+        assert!(run(&options(&dir, "[SAME]", "[SAME]", false)).is_err());
+        assert!(run(&options(&dir, "[ANNOTATION]", "TWO WORDS", false)).is_err());
+    }
+}
@@ -1,9 +1,231 @@
 use crate::diff::DiffResult;
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Line {
     pub content: String,
-    pub annotation: Option<String>,
+    /// Annotation thread on this line, oldest entry first
+    pub annotations: Vec<Annotation>,
+}
+
+/// Marker attribute naming who wrote an annotation
+pub const AUTHOR_KEY: &str = "by";
+/// Marker attribute holding the date an annotation was written (YYYY-MM-DD)
+pub const DATE_KEY: &str = "at";
+/// Marker attribute holding an annotation's severity
+pub const SEVERITY_KEY: &str = "sev";
+/// Marker attribute holding an annotation's status; absent means open
+pub const STATUS_KEY: &str = "status";
+/// `status` value of an annotation that has been addressed
+pub const RESOLVED: &str = "resolved";
+/// Marker attribute giving how many code lines an annotation covers,
+/// starting with the annotated line. Marker lines are not counted, so the
+/// range stays put when annotations are added or removed around it.
+pub const LINES_KEY: &str = "lines";
+
+/// One entry of an annotation thread: its text plus the optional
+/// attributes written inside the marker tag, e.g.
+/// `[ANNOTATION sev=blocker by=alice at=2026-10-16]`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Annotation {
+    /// Annotation text; lines are separated by `\n`
+    pub text: String,
+    /// `key=value` attributes in the order they appear in the marker.
+    /// Unknown keys are kept so they survive a save.
+    pub attributes: Vec<(String, String)>,
+}
+
+impl Annotation {
+    /// An annotation without attributes, as written by a bare marker.
+    pub fn new(text: impl Into<String>) -> Self {
+        Annotation {
+            text: text.into(),
+            attributes: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.attributes.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    /// Sets an attribute, or removes it when `value` is `None`. A new
+    /// attribute goes after the existing ones.
+    pub fn set(&mut self, key: &str, value: Option<&str>) {
+        let index = self.attributes.iter().position(|(k, _)| k == key);
+        match (index, value) {
+            (Some(i), Some(value)) => self.attributes[i].1 = value.to_string(),
+            (Some(i), None) => {
+                self.attributes.remove(i);
+            }
+            (None, Some(value)) => self.attributes.push((key.to_string(), value.to_string())),
+            (None, None) => {}
+        }
+    }
+
+    /// Records `author` and today's date on a newly written entry, as both
+    /// the editor and `nanot annotate` do.
+    pub fn stamp(&mut self, author: Option<&str>) {
+        self.set(AUTHOR_KEY, author);
+        self.set(DATE_KEY, Some(&today()));
+    }
+
+    pub fn author(&self) -> Option<&str> {
+        self.get(AUTHOR_KEY)
+    }
+
+    pub fn date(&self) -> Option<&str> {
+        self.get(DATE_KEY)
+    }
+
+    /// Severity from the `sev` attribute; unknown values count as none.
+    pub fn severity(&self) -> Option<Severity> {
+        self.get(SEVERITY_KEY).and_then(Severity::parse)
+    }
+
+    pub fn set_severity(&mut self, severity: Option<Severity>) {
+        self.set(SEVERITY_KEY, severity.map(Severity::as_str));
+    }
+
+    pub fn is_resolved(&self) -> bool {
+        self.get(STATUS_KEY) == Some(RESOLVED)
+    }
+
+    /// Marks the annotation resolved, or open again by dropping the status.
+    pub fn set_resolved(&mut self, resolved: bool) {
+        self.set(STATUS_KEY, resolved.then_some(RESOLVED));
+    }
+
+    /// Number of code lines the annotation covers (at least 1).
+    pub fn span(&self) -> usize {
+        self.get(LINES_KEY)
+            .and_then(|value| value.parse().ok())
+            .filter(|&span| span >= 1)
+            .unwrap_or(1)
+    }
+
+    /// Sets the covered range; a single line needs no attribute.
+    pub fn set_span(&mut self, span: usize) {
+        self.set(LINES_KEY, (span > 1).then(|| span.to_string()).as_deref());
+    }
+}
+
+impl From<&str> for Annotation {
+    fn from(text: &str) -> Self {
+        Annotation::new(text)
+    }
+}
+
+/// An annotation equals a string if it has that text and no attributes.
+impl PartialEq<&str> for Annotation {
+    fn eq(&self, other: &&str) -> bool {
+        self.attributes.is_empty() && self.text == *other
+    }
+}
+
+/// How much an annotation matters. Ordered from least to most severe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Nit,
+    Minor,
+    Major,
+    Blocker,
+}
+
+impl Severity {
+    /// Every severity, most severe first (the order the picker cycles in)
+    pub const ALL: [Severity; 4] = [Severity::Blocker, Severity::Major, Severity::Minor, Severity::Nit];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Nit => "nit",
+            Severity::Minor => "minor",
+            Severity::Major => "major",
+            Severity::Blocker => "blocker",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Severity::ALL.into_iter().find(|s| s.as_str().eq_ignore_ascii_case(value))
+    }
+
+    /// The next choice in the severity picker, where `None` (no severity)
+    /// sits between the least and the most severe. Steps backwards when
+    /// `forward` is false.
+    pub fn cycle(current: Option<Severity>, forward: bool) -> Option<Severity> {
+        let choices = [None, Some(Severity::Blocker), Some(Severity::Major), Some(Severity::Minor), Some(Severity::Nit)];
+        let index = choices.iter().position(|c| *c == current).unwrap_or(0);
+        let step = if forward { 1 } else { choices.len() - 1 };
+        choices[(index + step) % choices.len()]
+    }
+}
+
+impl Line {
+    /// Most severe open entry of the line's thread, if any has a severity.
+    pub fn severity(&self) -> Option<Severity> {
+        self.annotations
+            .iter()
+            .filter(|a| !a.is_resolved())
+            .filter_map(Annotation::severity)
+            .max()
+    }
+
+    /// True if the line has a thread and every entry of it is resolved.
+    pub fn is_resolved(&self) -> bool {
+        !self.annotations.is_empty() && self.annotations.iter().all(Annotation::is_resolved)
+    }
+
+    /// True if at least one entry of the line's thread is still open.
+    pub fn has_open_annotations(&self) -> bool {
+        self.annotations.iter().any(|a| !a.is_resolved())
+    }
+}
+
+/// Marks the lines covered by a range annotation that starts on an earlier
+/// line. Only open entries draw a band. `span_of` gives the span to use for
+/// a line, so callers can substitute one being edited.
+pub fn range_bands(lines: &[Line], span_of: impl Fn(usize, &Line) -> usize) -> Vec<bool> {
+    let mut bands = vec![false; lines.len()];
+    let mut covered_until = 0;
+    for (i, line) in lines.iter().enumerate() {
+        if i < covered_until {
+            bands[i] = true;
+        }
+        covered_until = covered_until.max(i + span_of(i, line));
+    }
+    bands
+}
+
+/// Span of the widest open entry on a line, 1 if there is none.
+pub fn open_span(line: &Line) -> usize {
+    line.annotations
+        .iter()
+        .filter(|a| !a.is_resolved())
+        .map(Annotation::span)
+        .max()
+        .unwrap_or(1)
+}
+
+/// Today's date in UTC as YYYY-MM-DD, for stamping new annotations.
+pub fn today() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Converts days since 1970-01-01 to a (year, month, day) date in the
+/// proleptic Gregorian calendar (Howard Hinnant's `civil_from_days`).
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
 }
 
 // ============================================================================
@@ -12,48 +234,122 @@ pub struct Line {
 
 /// How the main content area is rendered.
 /// This affects ONLY the visual presentation, not input handling.
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub enum ViewMode {
     /// Standard single-pane view
+    #[default]
     Normal,
     /// Split-pane diff view comparing working copy to HEAD
     Diff { diff_result: DiffResult },
 }
 
-impl Default for ViewMode {
-    fn default() -> Self {
-        ViewMode::Normal
-    }
-}
-
 /// What input mode the user is in.
 /// This affects ONLY input handling, independent of view mode.
+#[derive(Default)]
 pub enum EditorState {
     /// Normal navigation, all shortcuts active
+    #[default]
     Idle,
-    /// Editing an annotation for the current line
-    Annotating { buffer: String, cursor_pos: usize },
+    /// Editing entry `entry` of the current line's annotation thread. An
+    /// entry one past the end of the thread is a new reply. `severity` is
+    /// the picker's current choice and `span` the number of code lines the
+    /// entry covers.
+    Annotating {
+        buffer: String,
+        cursor_pos: usize,
+        entry: usize,
+        severity: Option<Severity>,
+        span: usize,
+    },
     /// Searching for text in the file
     Searching { query: String, cursor_pos: usize },
     /// Showing help overlay
     ShowingHelp,
     /// Asking about unsaved changes before quit
     QuitPrompt,
+    /// Choosing which open buffer to switch to
+    BufferPicker { selected: usize },
+    /// Choosing a format to export all annotations in
+    ExportPrompt,
 }
 
-impl Default for EditorState {
-    fn default() -> Self {
-        EditorState::Idle
-    }
+/// Summary of an open buffer for the status bar and buffer picker.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BufferInfo {
+    pub name: String,
+    pub modified: bool,
 }
 
 #[derive(Clone, Debug)]
 pub enum Action {
+    /// Changes one entry of a line's annotation thread. `None` for the old
+    /// entry means it was inserted at `entry`; `None` for the new entry
+    /// means it was removed.
     EditAnnotation {
         line_index: usize,
-        old_text: Option<String>,
-        new_text: Option<String>,
+        entry: usize,
+        old: Option<Annotation>,
+        new: Option<Annotation>,
     },
+    /// Several actions undone and redone as one step. They are applied in
+    /// order and undone in reverse.
+    Batch(Vec<Action>),
+}
+
+impl Action {
+    /// Applies the action to `lines`.
+    pub fn apply(&self, lines: &mut [Line]) {
+        match self {
+            Action::EditAnnotation { line_index, entry, old, new } => {
+                replace_entry(&mut lines[*line_index].annotations, *entry, old, new);
+            }
+            Action::Batch(actions) => actions.iter().for_each(|action| action.apply(lines)),
+        }
+    }
+
+    /// Reverts the action on `lines`.
+    pub fn undo(&self, lines: &mut [Line]) {
+        match self {
+            Action::EditAnnotation { line_index, entry, old, new } => {
+                replace_entry(&mut lines[*line_index].annotations, *entry, new, old);
+            }
+            Action::Batch(actions) => actions.iter().rev().for_each(|action| action.undo(lines)),
+        }
+    }
+
+    /// Removes every resolved entry from `lines`, or `None` if there are none.
+    pub fn purge_resolved(lines: &[Line]) -> Option<Action> {
+        // Later entries go first so the indices of earlier ones stay valid
+        let actions: Vec<Action> = lines
+            .iter()
+            .enumerate()
+            .flat_map(|(line_index, line)| {
+                line.annotations
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .filter(|(_, a)| a.is_resolved())
+                    .map(move |(entry, a)| Action::EditAnnotation {
+                        line_index,
+                        entry,
+                        old: Some(a.clone()),
+                        new: None,
+                    })
+            })
+            .collect();
+        (!actions.is_empty()).then_some(Action::Batch(actions))
+    }
+}
+
+fn replace_entry(thread: &mut Vec<Annotation>, entry: usize, from: &Option<Annotation>, to: &Option<Annotation>) {
+    match (from, to) {
+        (None, Some(annotation)) => thread.insert(entry.min(thread.len()), annotation.clone()),
+        (Some(_), None) if entry < thread.len() => {
+            thread.remove(entry);
+        }
+        (Some(_), Some(annotation)) if entry < thread.len() => thread[entry] = annotation.clone(),
+        _ => {}
+    }
 }
 
 #[cfg(test)]
@@ -121,16 +417,202 @@ mod tests {
         let state = EditorState::Annotating {
             buffer: "test annotation".to_string(),
             cursor_pos: 5,
+            entry: 1,
+            severity: Some(Severity::Major),
+            span: 1,
         };
 
-        if let EditorState::Annotating { buffer, cursor_pos } = state {
+        if let EditorState::Annotating {
+            buffer,
+            cursor_pos,
+            entry,
+            severity,
+            ..
+        } = state
+        {
             assert_eq!(buffer, "test annotation");
             assert_eq!(cursor_pos, 5);
+            assert_eq!(entry, 1);
+            assert_eq!(severity, Some(Severity::Major));
         } else {
             panic!("Expected EditorState::Annotating");
         }
     }
 
+    #[test]
+    fn test_edit_annotation_apply_and_undo_per_entry() {
+        let mut lines = vec![Line {
+            content: "x".to_string(),
+            annotations: vec!["first".into()],
+        }];
+        let edit = |entry, old: Option<&str>, new: Option<&str>| Action::EditAnnotation {
+            line_index: 0,
+            entry,
+            old: old.map(Annotation::from),
+            new: new.map(Annotation::from),
+        };
+
+        let reply = edit(1, None, Some("reply"));
+        reply.apply(&mut lines);
+        assert_eq!(lines[0].annotations, vec!["first", "reply"]);
+
+        let reword = edit(0, Some("first"), Some("reworded"));
+        reword.apply(&mut lines);
+        assert_eq!(lines[0].annotations, vec!["reworded", "reply"]);
+
+        let delete = edit(0, Some("reworded"), None);
+        delete.apply(&mut lines);
+        assert_eq!(lines[0].annotations, vec!["reply"]);
+
+        delete.undo(&mut lines);
+        reword.undo(&mut lines);
+        assert_eq!(lines[0].annotations, vec!["first", "reply"]);
+        reply.undo(&mut lines);
+        assert_eq!(lines[0].annotations, vec!["first"]);
+    }
+
+    // =========================================================================
+    // Annotation metadata Tests
+    // =========================================================================
+
+    #[test]
+    fn test_annotation_attributes() {
+        let mut annotation = Annotation::new("check");
+        assert_eq!(annotation, "check");
+
+        annotation.set(AUTHOR_KEY, Some("Jane"));
+        annotation.set_severity(Some(Severity::Major));
+        annotation.set(AUTHOR_KEY, Some("Joe"));
+        assert_eq!(annotation.author(), Some("Joe"));
+        assert_eq!(annotation.severity(), Some(Severity::Major));
+        assert_eq!(annotation.attributes.len(), 2);
+        assert_ne!(annotation, "check");
+
+        annotation.set_severity(None);
+        annotation.set(AUTHOR_KEY, None);
+        assert_eq!(annotation, "check");
+    }
+
+    #[test]
+    fn test_severity_parse_and_cycle() {
+        assert_eq!(Severity::parse("Blocker"), Some(Severity::Blocker));
+        assert_eq!(Severity::parse("nit"), Some(Severity::Nit));
+        assert_eq!(Severity::parse("urgent"), None);
+
+        let mut severity = None;
+        let mut seen = Vec::new();
+        for _ in 0..5 {
+            severity = Severity::cycle(severity, true);
+            seen.push(severity);
+        }
+        assert_eq!(seen, vec![Some(Severity::Blocker), Some(Severity::Major), Some(Severity::Minor), Some(Severity::Nit), None]);
+        assert_eq!(Severity::cycle(None, false), Some(Severity::Nit));
+    }
+
+    #[test]
+    fn test_line_severity_is_most_severe_entry() {
+        let mut major = Annotation::new("a");
+        major.set_severity(Some(Severity::Major));
+        let mut nit = Annotation::new("b");
+        nit.set_severity(Some(Severity::Nit));
+        let line = Line {
+            content: "x".to_string(),
+            annotations: vec![nit, "c".into(), major],
+        };
+        assert_eq!(line.severity(), Some(Severity::Major));
+    }
+
+    #[test]
+    fn test_resolved_status() {
+        let mut annotation = Annotation::new("done");
+        annotation.set_severity(Some(Severity::Blocker));
+        annotation.set_resolved(true);
+        assert!(annotation.is_resolved());
+        assert_eq!(annotation.get(STATUS_KEY), Some("resolved"));
+
+        let mut line = Line {
+            content: "x".to_string(),
+            annotations: vec![annotation.clone(), "open".into()],
+        };
+        // Resolved entries don't count towards the line's severity
+        assert_eq!(line.severity(), None);
+        assert!(line.has_open_annotations() && !line.is_resolved());
+        line.annotations[1].set_resolved(true);
+        assert!(!line.has_open_annotations() && line.is_resolved());
+
+        annotation.set_resolved(false);
+        assert!(!annotation.is_resolved());
+        assert_eq!(annotation.get(STATUS_KEY), None);
+    }
+
+    #[test]
+    fn test_purge_resolved_is_one_undo_step() {
+        let resolved = |text: &str| {
+            let mut a = Annotation::new(text);
+            a.set_resolved(true);
+            a
+        };
+        let mut lines = vec![
+            Line { content: "a".to_string(), annotations: vec![resolved("r1"), "open".into(), resolved("r2")] },
+            Line { content: "b".to_string(), annotations: vec!["keep".into()] },
+            Line { content: "c".to_string(), annotations: vec![resolved("r3")] },
+        ];
+        let before = lines.clone();
+
+        let purge = Action::purge_resolved(&lines).unwrap();
+        purge.apply(&mut lines);
+        assert_eq!(lines[0].annotations, vec!["open"]);
+        assert_eq!(lines[1].annotations, vec!["keep"]);
+        assert!(lines[2].annotations.is_empty());
+        assert!(Action::purge_resolved(&lines).is_none());
+
+        purge.undo(&mut lines);
+        assert_eq!(lines, before);
+    }
+
+    #[test]
+    fn test_annotation_span() {
+        let mut annotation = Annotation::new("split this");
+        assert_eq!(annotation.span(), 1);
+        annotation.set_span(12);
+        assert_eq!((annotation.span(), annotation.get(LINES_KEY)), (12, Some("12")));
+        annotation.set_span(1);
+        assert_eq!(annotation, "split this");
+
+        annotation.set(LINES_KEY, Some("many"));
+        assert_eq!(annotation.span(), 1);
+    }
+
+    #[test]
+    fn test_range_bands() {
+        let line = |text: &str, span: usize| {
+            let mut annotation = Annotation::new(text);
+            annotation.set_span(span);
+            Line { content: text.to_string(), annotations: vec![annotation] }
+        };
+        let plain = || Line { content: "x".to_string(), annotations: Vec::new() };
+        let mut done = line("done", 3);
+        done.annotations[0].set_resolved(true);
+        let lines = vec![line("a", 3), plain(), line("b", 2), plain(), plain(), done, plain()];
+
+        // Overlapping ranges merge; resolved ones draw nothing
+        assert_eq!(
+            range_bands(&lines, |_, line| open_span(line)),
+            vec![false, true, true, true, false, false, false]
+        );
+        // A span past the end of the file stops at the last line
+        assert_eq!(range_bands(&lines[3..], |i, _| if i == 1 { 10 } else { 1 }), vec![false, false, true, true]);
+    }
+
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(20_742), (2026, 10, 16));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(today().len(), 10);
+    }
+
     #[test]
     fn test_editor_state_searching_holds_query() {
         let state = EditorState::Searching {
@@ -157,4 +639,21 @@ mod tests {
         let state = EditorState::QuitPrompt;
         assert!(matches!(state, EditorState::QuitPrompt));
     }
+
+    #[test]
+    fn test_editor_state_buffer_picker_holds_selection() {
+        let state = EditorState::BufferPicker { selected: 2 };
+
+        if let EditorState::BufferPicker { selected } = state {
+            assert_eq!(selected, 2);
+        } else {
+            panic!("Expected EditorState::BufferPicker");
+        }
+    }
+
+    #[test]
+    fn test_editor_state_export_prompt() {
+        let state = EditorState::ExportPrompt;
+        assert!(matches!(state, EditorState::ExportPrompt));
+    }
 }
@@ -13,23 +13,23 @@ use crate::text::wrap_text;
 /// Find the next line with an annotation after the current line.
 /// Returns None if no annotation exists after current_line.
 pub fn find_next_annotation(lines: &[Line], current_line: usize) -> Option<usize> {
-    for i in (current_line + 1)..lines.len() {
-        if lines[i].annotation.is_some() {
-            return Some(i);
-        }
-    }
-    None
+    ((current_line + 1)..lines.len()).find(|&i| !lines[i].annotations.is_empty())
 }
 
 /// Find the previous line with an annotation before the current line.
 /// Returns None if no annotation exists before current_line.
 pub fn find_prev_annotation(lines: &[Line], current_line: usize) -> Option<usize> {
-    for i in (0..current_line).rev() {
-        if lines[i].annotation.is_some() {
-            return Some(i);
-        }
-    }
-    None
+    (0..current_line).rev().find(|&i| !lines[i].annotations.is_empty())
+}
+
+/// Like `find_next_annotation`, but skips lines whose thread is fully resolved.
+pub fn find_next_open_annotation(lines: &[Line], current_line: usize) -> Option<usize> {
+    ((current_line + 1)..lines.len()).find(|&i| lines[i].has_open_annotations())
+}
+
+/// Like `find_prev_annotation`, but skips lines whose thread is fully resolved.
+pub fn find_prev_open_annotation(lines: &[Line], current_line: usize) -> Option<usize> {
+    (0..current_line).rev().find(|&i| lines[i].has_open_annotations())
 }
 
 // ============================================================================
@@ -140,6 +140,19 @@ pub fn adjust_normal_scroll(
     scroll
 }
 
+/// Scroll offset that puts the cursor line in the middle of the visible area,
+/// counting wrapped lines above it. Used when opening a file at a given line.
+pub fn center_scroll(cursor_line: usize, visible_height: usize, lines: &[Line], width: usize) -> usize {
+    let mut scroll = cursor_line;
+    let half = visible_height / 2;
+
+    while scroll > 0 && calculate_visual_lines(lines, scroll - 1, cursor_line, width) <= half + 1 {
+        scroll -= 1;
+    }
+
+    scroll
+}
+
 // ============================================================================
 // Wrapped Text Cursor Navigation
 // ============================================================================
@@ -336,9 +349,7 @@ pub fn find_prev_word_boundary(buffer: &str, cursor_pos: usize) -> usize {
     let mut pos = cursor_pos.min(chars.len());
 
     // Move back one position to analyze what we're dealing with
-    if pos > 0 {
-        pos -= 1;
-    }
+    pos = pos.saturating_sub(1);
 
     // If we're at a boundary, skip back over boundaries to find a word character
     if is_word_boundary(chars[pos]) {
@@ -401,13 +412,14 @@ pub fn find_next_word_boundary(buffer: &str, cursor_pos: usize) -> usize {
 #[cfg(test)]
 mod annotation_jump_tests {
     use super::*;
+    use crate::models::Annotation;
 
     fn make_lines(annotations: &[Option<&str>]) -> Vec<Line> {
         annotations
             .iter()
             .map(|a| Line {
                 content: "code".to_string(),
-                annotation: a.map(|s| s.to_string()),
+                annotations: a.map(Annotation::from).into_iter().collect(),
             })
             .collect()
     }
@@ -461,6 +473,24 @@ mod annotation_jump_tests {
         assert_eq!(find_prev_annotation(&lines, 2), Some(1));
         assert_eq!(find_prev_annotation(&lines, 1), Some(0));
     }
+
+    #[test]
+    fn test_find_open_annotation_skips_resolved() {
+        let mut lines = make_lines(&[Some("open"), Some("done"), None, Some("done"), Some("open")]);
+        lines[1].annotations[0].set_resolved(true);
+        lines[3].annotations[0].set_resolved(true);
+        // A thread with one open entry left still counts
+        lines[3].annotations.push("follow-up".into());
+
+        assert_eq!(find_next_open_annotation(&lines, 0), Some(3));
+        lines[3].annotations[1].set_resolved(true);
+        assert_eq!(find_next_open_annotation(&lines, 0), Some(4));
+        assert_eq!(find_next_open_annotation(&lines, 4), None);
+        assert_eq!(find_prev_open_annotation(&lines, 4), Some(0));
+        assert_eq!(find_prev_open_annotation(&lines, 0), None);
+        // The plain variants still stop everywhere
+        assert_eq!(find_next_annotation(&lines, 0), Some(1));
+    }
 }
 
 #[cfg(test)]
@@ -472,7 +502,7 @@ mod search_tests {
             .iter()
             .map(|c| Line {
                 content: c.to_string(),
-                annotation: None,
+                annotations: Vec::new(),
             })
             .collect()
     }
@@ -569,7 +599,7 @@ mod scroll_tests {
             .iter()
             .map(|c| Line {
                 content: c.to_string(),
-                annotation: None,
+                annotations: Vec::new(),
             })
             .collect()
     }
@@ -636,6 +666,33 @@ mod scroll_tests {
         // Should scroll to make line 3 visible
         assert!(new_scroll > 0);
     }
+
+    #[test]
+    fn test_center_scroll_middle_of_file() {
+        let contents: Vec<String> = (0..100).map(|i| i.to_string()).collect();
+        let lines = make_lines(&contents.iter().map(String::as_str).collect::<Vec<_>>());
+
+        // Height 10: five lines above the cursor
+        assert_eq!(center_scroll(50, 10, &lines, 80), 45);
+    }
+
+    #[test]
+    fn test_center_scroll_near_top() {
+        let lines = make_lines(&["a", "b", "c", "d"]);
+        assert_eq!(center_scroll(2, 10, &lines, 80), 0);
+    }
+
+    #[test]
+    fn test_center_scroll_counts_wrapped_lines() {
+        let lines = make_lines(&[
+            "a",
+            "b",
+            "this is a very long line that definitely needs to wrap at width 20",
+            "target",
+        ]);
+        // The wrapped line fills the space above the cursor on its own
+        assert_eq!(center_scroll(3, 6, &lines, 20), 3);
+    }
 }
 
 #[cfg(test)]
@@ -706,6 +763,15 @@ mod wrapped_cursor_tests {
         assert!(new_pos < 10);
     }
 
+    #[test]
+    fn test_cursor_moves_across_newlines() {
+        let buffer = "first\n\nthird line";
+        assert_eq!(cursor_to_wrapped_position(buffer, 7, 80), WrappedPosition { line: 2, col: 0 });
+        assert_eq!(cursor_to_wrapped_position(buffer, 6, 80), WrappedPosition { line: 1, col: 0 });
+        assert_eq!(move_cursor_up_in_wrapped(buffer, 9, 80), 6);
+        assert_eq!(move_cursor_down_in_wrapped(buffer, 3, 80), 6);
+    }
+
     #[test]
     fn test_move_cursor_up_at_top() {
         let buffer = "hello world";
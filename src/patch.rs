@@ -0,0 +1,388 @@
+//! Reviewing unified diffs: parsing patches into per-file hunks and writing
+//! annotations back as an annotated patch or JSON.
+
+use crate::diff::LineChange;
+use crate::file::TextFormat;
+use crate::models::{Line, Severity};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Comment prefix for annotation markers in patches. `#` lines are what
+/// `git add -p` treats as comments, and no hunk line starts with one.
+pub const PATCH_COMMENT: &str = "#";
+
+/// Syntect syntax patches are highlighted with, whatever their save path
+pub const PATCH_SYNTAX: &str = "Diff";
+
+/// Where annotations of a patch read from stdin are saved by default
+pub const STDIN_PATCH_OUTPUT: &str = "review.patch";
+
+/// How a patch is written back. Markers stay at column 0, since an
+/// indented `#` line would read as a context line.
+pub fn text_format(content: &str) -> TextFormat {
+    TextFormat {
+        indent_markers: false,
+        ..TextFormat::detect(content)
+    }
+}
+
+/// Role of one line of a patch.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchLineKind {
+    /// `diff --git`, `---`/`+++`, `index` and any other text between hunks
+    Meta,
+    /// `@@ -a,b +c,d @@`
+    HunkHeader,
+    /// A context, added or removed line inside a hunk
+    Change(LineChange),
+}
+
+/// What a single buffer line of a patch refers to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchLineInfo {
+    pub kind: PatchLineKind,
+    /// Index into `Patch::files`
+    pub file: Option<usize>,
+    pub old_line: Option<usize>,
+    /// New-side line number. Removed lines get the new-side line they were
+    /// removed in front of, so every hunk line has a position in the new file.
+    pub new_line: Option<usize>,
+}
+
+/// A parsed unified diff, line-aligned with the buffer showing it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Patch {
+    /// Paths of the files in the patch (new side, or old side for deletions)
+    pub files: Vec<String>,
+    pub lines: Vec<PatchLineInfo>,
+    /// Save annotations as JSON instead of an annotated patch
+    pub json_output: bool,
+}
+
+impl Patch {
+    /// Parses the content of buffer lines as a unified diff.
+    pub fn parse(lines: &[Line]) -> Self {
+        let mut files: Vec<String> = Vec::new();
+        let mut infos = Vec::with_capacity(lines.len());
+        let mut current_file: Option<usize> = None;
+        // Set by `diff --git` until its `+++` line names the file
+        let mut header_pending = false;
+        let mut old_path = String::new();
+        let (mut old_line, mut new_line) = (0usize, 0usize);
+        let (mut old_remaining, mut new_remaining) = (0usize, 0usize);
+
+        for line in lines {
+            let content = line.content.as_str();
+            let in_hunk = old_remaining > 0 || new_remaining > 0;
+
+            let info = if in_hunk && !content.starts_with('\\') {
+                let (kind, old, new) = match content.chars().next() {
+                    Some('+') => {
+                        new_remaining = new_remaining.saturating_sub(1);
+                        new_line += 1;
+                        (LineChange::Added, None, Some(new_line))
+                    }
+                    Some('-') => {
+                        old_remaining = old_remaining.saturating_sub(1);
+                        old_line += 1;
+                        (LineChange::Removed, Some(old_line), Some(new_line + 1))
+                    }
+                    // Context line; some tools strip the space from empty ones
+                    _ => {
+                        old_remaining = old_remaining.saturating_sub(1);
+                        new_remaining = new_remaining.saturating_sub(1);
+                        old_line += 1;
+                        new_line += 1;
+                        (LineChange::Unchanged, Some(old_line), Some(new_line))
+                    }
+                };
+                PatchLineInfo {
+                    kind: PatchLineKind::Change(kind),
+                    file: current_file,
+                    old_line: old,
+                    new_line: new,
+                }
+            } else if let Some((old_start, old_count, new_start, new_count)) = parse_hunk_header(content) {
+                if current_file.is_none() {
+                    files.push(String::new());
+                    current_file = Some(files.len() - 1);
+                }
+                old_line = old_start.saturating_sub(1);
+                new_line = new_start.saturating_sub(1);
+                old_remaining = old_count;
+                new_remaining = new_count;
+                PatchLineInfo {
+                    kind: PatchLineKind::HunkHeader,
+                    file: current_file,
+                    old_line: None,
+                    new_line: None,
+                }
+            } else {
+                if let Some(rest) = content.strip_prefix("diff --git ") {
+                    let path = rest.rsplit_once(" b/").map(|(_, b)| b).unwrap_or(rest);
+                    files.push(path.to_string());
+                    current_file = Some(files.len() - 1);
+                    header_pending = true;
+                } else if let Some(rest) = content.strip_prefix("--- ") {
+                    old_path = strip_path(rest);
+                } else if let Some(rest) = content.strip_prefix("+++ ") {
+                    let new_path = strip_path(rest);
+                    let path = if new_path == "/dev/null" { old_path.clone() } else { new_path };
+                    if header_pending {
+                        if let Some(index) = current_file {
+                            files[index] = path;
+                        }
+                    } else {
+                        files.push(path);
+                        current_file = Some(files.len() - 1);
+                    }
+                    header_pending = false;
+                }
+                PatchLineInfo {
+                    kind: PatchLineKind::Meta,
+                    file: current_file,
+                    old_line: None,
+                    new_line: None,
+                }
+            };
+            infos.push(info);
+        }
+
+        Patch {
+            files,
+            lines: infos,
+            json_output: false,
+        }
+    }
+
+    /// Only lines inside hunks can carry annotations.
+    pub fn can_annotate(&self, line_index: usize) -> bool {
+        matches!(
+            self.lines.get(line_index).map(|info| &info.kind),
+            Some(PatchLineKind::Change(_))
+        )
+    }
+
+    /// Change shown for a buffer line, if it is a hunk line.
+    pub fn line_change(&self, line_index: usize) -> Option<&LineChange> {
+        match &self.lines.get(line_index)?.kind {
+            PatchLineKind::Change(change) => Some(change),
+            _ => None,
+        }
+    }
+}
+
+/// Check if a path is a patch file that should open in patch review mode.
+pub fn is_patch_path(path: &str) -> bool {
+    path.ends_with(".patch") || path.ends_with(".diff")
+}
+
+/// Parses `@@ -a,b +c,d @@` into (a, b, c, d). Counts default to 1.
+fn parse_hunk_header(content: &str) -> Option<(usize, usize, usize, usize)> {
+    let rest = content.strip_prefix("@@ -")?;
+    let (ranges, _) = rest.split_once(" @@")?;
+    let (old, new) = ranges.split_once(" +")?;
+    let (old_start, old_count) = parse_range(old)?;
+    let (new_start, new_count) = parse_range(new)?;
+    Some((old_start, old_count, new_start, new_count))
+}
+
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    match range.split_once(',') {
+        Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+/// Drops `a/`/`b/` prefixes and trailing timestamps from `---`/`+++` paths.
+fn strip_path(path: &str) -> String {
+    let path = path.split('\t').next().unwrap_or(path).trim_end();
+    path.strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path)
+        .to_string()
+}
+
+/// One annotated hunk line in the JSON output.
+#[derive(Debug, Serialize)]
+struct JsonAnnotation<'a> {
+    change: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old_line: Option<usize>,
+    /// Line without its `+`/`-`/space prefix
+    content: &'a str,
+    annotation: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    severity: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    author: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date: Option<&'a str>,
+}
+
+/// Renders annotations as `{ file: { new_line: [entries] } }`.
+pub fn render_json(patch: &Patch, lines: &[Line]) -> String {
+    let mut files: BTreeMap<&str, BTreeMap<usize, Vec<JsonAnnotation>>> = BTreeMap::new();
+
+    for (line, info) in lines.iter().zip(&patch.lines) {
+        let PatchLineKind::Change(change) = &info.kind else {
+            continue;
+        };
+        if line.annotations.is_empty() {
+            continue;
+        }
+        let file = info
+            .file
+            .and_then(|index| patch.files.get(index))
+            .map(String::as_str)
+            .unwrap_or("");
+        let change_name = match change {
+            LineChange::Added => "added",
+            LineChange::Removed => "removed",
+            _ => "context",
+        };
+        let content = line.content.get(1..).unwrap_or("");
+
+        let entries = files.entry(file).or_default().entry(info.new_line.unwrap_or(0)).or_default();
+        for annotation in &line.annotations {
+            entries.push(JsonAnnotation {
+                change: change_name,
+                old_line: if *change == LineChange::Removed { info.old_line } else { None },
+                content,
+                annotation: &annotation.text,
+                severity: annotation.severity().map(Severity::as_str),
+                author: annotation.author(),
+                date: annotation.date(),
+            });
+        }
+    }
+
+    let mut output = serde_json::to_string_pretty(&files).unwrap_or_default();
+    output.push('\n');
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file;
+
+    const GIT_PATCH: &str = "\
+diff --git a/src/a.rs b/src/a.rs
+index 111..222 100644
+--- a/src/a.rs
++++ b/src/a.rs
+@@ -10,3 +10,3 @@ fn main() {
+     let x = 1;
+-    let y = 2;
++    let y = 3;
+     x + y
+diff --git a/old.txt b/old.txt
+deleted file mode 100644
+--- a/old.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-gone
+";
+
+    fn parse(content: &str) -> (Vec<Line>, Patch) {
+        let lines = file::parse_file(content, PATCH_COMMENT);
+        let patch = Patch::parse(&lines);
+        (lines, patch)
+    }
+
+    #[test]
+    fn test_parse_git_patch_files_and_kinds() {
+        let (lines, patch) = parse(GIT_PATCH);
+        assert_eq!(patch.files, vec!["src/a.rs", "old.txt"]);
+        assert_eq!(patch.lines.len(), lines.len());
+
+        assert_eq!(patch.lines[0].kind, PatchLineKind::Meta);
+        assert_eq!(patch.lines[4].kind, PatchLineKind::HunkHeader);
+        assert_eq!(patch.line_change(5), Some(&LineChange::Unchanged));
+        assert_eq!(patch.line_change(6), Some(&LineChange::Removed));
+        assert_eq!(patch.line_change(7), Some(&LineChange::Added));
+        assert_eq!(patch.lines[14].kind, PatchLineKind::Change(LineChange::Removed));
+        assert_eq!(patch.lines[14].file, Some(1));
+    }
+
+    #[test]
+    fn test_parse_line_numbers() {
+        let (_, patch) = parse(GIT_PATCH);
+        // Context line
+        assert_eq!((patch.lines[5].old_line, patch.lines[5].new_line), (Some(10), Some(10)));
+        // Removed line sits in front of new line 11
+        assert_eq!((patch.lines[6].old_line, patch.lines[6].new_line), (Some(11), Some(11)));
+        // Added line
+        assert_eq!((patch.lines[7].old_line, patch.lines[7].new_line), (None, Some(11)));
+        assert_eq!((patch.lines[8].old_line, patch.lines[8].new_line), (Some(12), Some(12)));
+    }
+
+    #[test]
+    fn test_parse_plain_unified_diff() {
+        let content = "--- a.c\t2024-01-01\n+++ a.c\t2024-01-02\n@@ -1 +1,2 @@\n int x;\n+int y;\n";
+        let (_, patch) = parse(content);
+        assert_eq!(patch.files, vec!["a.c"]);
+        assert_eq!(patch.line_change(4), Some(&LineChange::Added));
+        assert_eq!(patch.lines[4].new_line, Some(2));
+    }
+
+    #[test]
+    fn test_hunk_lines_that_look_like_headers() {
+        // "--- " inside a hunk is a removed line, not a file header
+        let content = "--- a/x.md\n+++ b/x.md\n@@ -1,2 +1 @@\n--- rule\n-text\n";
+        let (_, patch) = parse(content);
+        assert_eq!(patch.files, vec!["x.md"]);
+        assert_eq!(patch.line_change(3), Some(&LineChange::Removed));
+    }
+
+    #[test]
+    fn test_can_annotate_only_hunk_lines() {
+        let (_, patch) = parse(GIT_PATCH);
+        assert!(!patch.can_annotate(0));
+        assert!(!patch.can_annotate(4));
+        assert!(patch.can_annotate(5));
+        assert!(patch.can_annotate(6));
+    }
+
+    #[test]
+    fn test_annotated_patch_round_trip() {
+        let (mut lines, _) = parse(GIT_PATCH);
+        lines[7].annotations = vec!["why 3?".into()];
+
+        lines[5].annotations = vec!["context".into()];
+
+        let rendered = file::render_file(&lines, PATCH_COMMENT, text_format(GIT_PATCH));
+        assert!(rendered.contains("\n# [ANNOTATION] context\n     let x = 1;\n"));
+        assert!(rendered.contains("# [ANNOTATION] why 3?\n+    let y = 3;\n"));
+
+        let (reparsed, patch) = parse(&rendered);
+        assert_eq!(reparsed, lines);
+        assert_eq!(patch.files, vec!["src/a.rs", "old.txt"]);
+    }
+
+    #[test]
+    fn test_render_json_keyed_by_file_and_new_line() {
+        let (mut lines, patch) = parse(GIT_PATCH);
+        lines[7].annotations = vec!["why 3?".into()];
+        lines[6].annotations = vec!["keep 2".into()];
+        lines[14].annotations = vec!["still used".into()];
+
+        let value: serde_json::Value = serde_json::from_str(&render_json(&patch, &lines)).unwrap();
+        let entries = &value["src/a.rs"]["11"];
+        assert_eq!(entries[0]["change"], "removed");
+        assert_eq!(entries[0]["old_line"], 11);
+        assert_eq!(entries[0]["content"], "    let y = 2;");
+        assert_eq!(entries[1]["change"], "added");
+        assert_eq!(entries[1]["annotation"], "why 3?");
+        assert!(entries[1].get("old_line").is_none());
+        assert_eq!(value["old.txt"]["1"][0]["annotation"], "still used");
+    }
+
+    #[test]
+    fn test_is_patch_path() {
+        assert!(is_patch_path("fix.patch"));
+        assert!(is_patch_path("changes.diff"));
+        assert!(!is_patch_path("main.rs"));
+    }
+}
@@ -0,0 +1,204 @@
+//! Finding an annotated line again after the file has changed underneath
+//! its annotations.
+//!
+//! An anchor records the line's position, content (or just its hash) and a
+//! few lines of context on each side. A line whose content is unchanged is
+//! found by that content, using the context to choose between repeats; an
+//! edited line is found where the context still matches and the content is
+//! similar enough (by `similar`'s character diff). Anything less certain is
+//! left unplaced, for the caller to report as orphaned.
+
+use similar::TextDiff;
+
+/// Share of the stored context lines that must still surround a line for
+/// the context to count as a match
+const MIN_CONTEXT_SCORE: f32 = 0.5;
+
+/// How similar an edited line must be to its old content to take over its
+/// annotations
+const MIN_LINE_RATIO: f32 = 0.6;
+
+/// Where an annotated line was and what surrounded it.
+#[derive(Debug, Clone, Copy)]
+pub struct Anchor<'a> {
+    /// 0-based index of the line when the anchor was taken
+    pub index: usize,
+    /// Content of the line, when known
+    pub content: Option<&'a str>,
+    /// `content_hash` of the line
+    pub hash: &'a str,
+    /// Lines just above it, nearest last
+    pub context_before: &'a [String],
+    /// Lines just below it, nearest first
+    pub context_after: &'a [String],
+}
+
+/// Stable 64-bit FNV-1a hash of a line, as 16 hex digits. Unlike
+/// `DefaultHasher` it doesn't change between Rust releases.
+pub fn content_hash(content: &str) -> String {
+    let hash = content.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{:016x}", hash)
+}
+
+/// Index of the line in `lines` that `anchor` now refers to, or `None` if
+/// it can't be told with confidence.
+///
+/// Lines with the anchored content win: the one whose context matches best
+/// (nearest the old position on a tie), the only one if there is just one,
+/// or the one still at the old position. Failing that, an edited line is
+/// accepted where the context matches and the content is similar enough.
+pub fn locate(lines: &[&str], anchor: &Anchor) -> Option<usize> {
+    let same_content = |line: &str| match anchor.content {
+        Some(content) => line.trim() == content.trim(),
+        None => content_hash(line) == anchor.hash,
+    };
+    let by_score_then_distance =
+        |&(a, score_a): &(usize, f32), &(b, score_b): &(usize, f32)| {
+            score_a
+                .total_cmp(&score_b)
+                .then(b.abs_diff(anchor.index).cmp(&a.abs_diff(anchor.index)))
+        };
+
+    let exact: Vec<(usize, f32)> = (0..lines.len())
+        .filter(|&i| same_content(lines[i]))
+        .map(|i| (i, context_score(lines, i, anchor)))
+        .collect();
+    if let Some(&(index, _)) = exact
+        .iter()
+        .filter(|(_, score)| *score >= MIN_CONTEXT_SCORE)
+        .max_by(|a, b| by_score_then_distance(a, b))
+    {
+        return Some(index);
+    }
+    match exact.as_slice() {
+        [(index, _)] => return Some(*index),
+        candidates if candidates.iter().any(|&(i, _)| i == anchor.index) => return Some(anchor.index),
+        [] => {}
+        _ => return None,
+    }
+
+    // The line itself was edited: look for it where the context still is
+    let content = anchor.content?;
+    (0..lines.len())
+        .map(|i| (i, context_score(lines, i, anchor)))
+        .filter(|&(_, score)| score >= MIN_CONTEXT_SCORE)
+        .filter_map(|(i, score)| {
+            let ratio = TextDiff::from_chars(content.trim(), lines[i].trim()).ratio();
+            (ratio >= MIN_LINE_RATIO).then_some((i, score + ratio))
+        })
+        .max_by(by_score_then_distance)
+        .map(|(index, _)| index)
+}
+
+/// Share of the anchor's context lines found at the same distance around
+/// `index`, ignoring indentation. An anchor without context scores 0.
+fn context_score(lines: &[&str], index: usize, anchor: &Anchor) -> f32 {
+    let total = anchor.context_before.len() + anchor.context_after.len();
+    if total == 0 {
+        return 0.0;
+    }
+    let same = |line: Option<&&str>, expected: &String| line.is_some_and(|line| line.trim() == expected.trim());
+    let before = anchor
+        .context_before
+        .iter()
+        .rev()
+        .enumerate()
+        .filter(|&(k, expected)| same(index.checked_sub(k + 1).and_then(|i| lines.get(i)), expected))
+        .count();
+    let after = anchor
+        .context_after
+        .iter()
+        .enumerate()
+        .filter(|&(k, expected)| same(lines.get(index + k + 1), expected))
+        .count();
+    (before + after) as f32 / total as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    /// Anchor for `lines[index]` with two lines of context on each side.
+    fn locate_in(old: &[&str], index: usize, new: &[&str]) -> Option<usize> {
+        let before = strings(&old[index.saturating_sub(2)..index]);
+        let after = strings(&old[index + 1..(index + 3).min(old.len())]);
+        let hash = content_hash(old[index]);
+        let anchor = Anchor {
+            index,
+            content: Some(old[index]),
+            hash: &hash,
+            context_before: &before,
+            context_after: &after,
+        };
+        locate(new, &anchor)
+    }
+
+    const OLD: &[&str] = &["{", "  \"name\": \"app\",", "  \"version\": \"1.0.0\",", "  \"private\": true", "}"];
+
+    #[test]
+    fn test_unchanged_line_stays() {
+        assert_eq!(locate_in(OLD, 2, OLD), Some(2));
+    }
+
+    #[test]
+    fn test_follows_inserted_and_removed_lines() {
+        let inserted = ["{", "  \"$schema\": \"x\",", "  \"name\": \"app\",", "  \"version\": \"1.0.0\",", "  \"private\": true", "}"];
+        assert_eq!(locate_in(OLD, 2, &inserted), Some(3));
+
+        let removed = ["{", "  \"version\": \"1.0.0\",", "  \"private\": true", "}"];
+        assert_eq!(locate_in(OLD, 2, &removed), Some(1));
+    }
+
+    #[test]
+    fn test_context_picks_between_repeated_lines() {
+        let old = ["fn a() {", "    x();", "}", "fn b() {", "    y();", "}"];
+        let new = ["fn c() {}", "fn a() {", "    x();", "}", "fn b() {", "    y();", "}"];
+        assert_eq!(locate_in(&old, 5, &new), Some(6));
+        assert_eq!(locate_in(&old, 2, &new), Some(3));
+    }
+
+    #[test]
+    fn test_follows_edited_line() {
+        let edited = ["{", "  \"name\": \"app\",", "  \"version\": \"1.0.1\",", "  \"private\": true", "}"];
+        assert_eq!(locate_in(OLD, 2, &edited), Some(2));
+
+        // Indentation changes don't matter
+        let reindented = ["{", "    \"name\": \"app\",", "    \"version\": \"1.0.0\",", "    \"private\": true", "}"];
+        assert_eq!(locate_in(OLD, 2, &reindented), Some(2));
+    }
+
+    #[test]
+    fn test_unconfident_matches_are_orphaned() {
+        // Rewritten line in a rewritten neighbourhood
+        let rewritten = ["[", "  1,", "  2", "]"];
+        assert_eq!(locate_in(OLD, 2, &rewritten), None);
+
+        // Line rewritten beyond recognition, even though its context remains
+        let replaced = ["{", "  \"name\": \"app\",", "  \"license\": \"MIT\",", "  \"private\": true", "}"];
+        assert_eq!(locate_in(OLD, 2, &replaced), None);
+
+        // Repeated line with no context left to choose between copies
+        let old = ["a", "b", "x", "c", "d"];
+        assert_eq!(locate_in(&old, 2, &["x", "p", "q", "x"]), None);
+    }
+
+    #[test]
+    fn test_hash_only_anchor() {
+        let hash = content_hash(OLD[2]);
+        let anchor = Anchor { index: 0, content: None, hash: &hash, context_before: &[], context_after: &[] };
+        let moved = ["{", "  \"name\": \"app\",", "  \"private\": true", "  \"version\": \"1.0.0\",", "}"];
+        assert_eq!(locate(&moved, &anchor), Some(3));
+    }
+
+    #[test]
+    fn test_content_hash_is_stable() {
+        assert_eq!(content_hash(""), "cbf29ce484222325");
+        assert_eq!(content_hash("a"), "af63dc4c8601ec8c");
+    }
+}
@@ -0,0 +1,261 @@
+//! How an editing session ended: the process exit code and the annotations
+//! added, changed and removed, for callers that launch nanot as `$EDITOR`.
+
+use crate::atomic;
+use crate::models::Annotation;
+use serde::Serialize;
+use similar::{Algorithm, DiffOp};
+use std::io;
+
+/// How the editor was left. The discriminants are the documented exit codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionOutcome {
+    /// Everything saved, and either no annotation was changed or none are
+    /// left in any open file
+    Clean = 0,
+    /// The session saved annotation changes and at least one annotation is
+    /// left in an open file
+    Annotated = 3,
+    /// Quit with unsaved changes, which were discarded
+    Discarded = 4,
+}
+
+impl SessionOutcome {
+    pub fn exit_code(self) -> i32 {
+        self as i32
+    }
+}
+
+/// One annotation that differs from when the file was opened.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AnnotationChange {
+    pub file: String,
+    /// 1-based line number as shown in the editor
+    pub line: usize,
+    /// Annotation when the file was opened (absent for added ones)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_annotation: Option<String>,
+    /// Annotation at exit (absent for removed ones)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub annotation: Option<String>,
+    /// Severity of the annotation at exit, or when opened for removed ones
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub severity: Option<&'static str>,
+}
+
+/// Summary of a session, written by `--report` and printed on exit.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SessionReport {
+    pub outcome: SessionOutcome,
+    pub exit_code: i32,
+    pub added: Vec<AnnotationChange>,
+    pub changed: Vec<AnnotationChange>,
+    pub removed: Vec<AnnotationChange>,
+}
+
+impl SessionReport {
+    pub fn new(outcome: SessionOutcome) -> Self {
+        SessionReport {
+            outcome,
+            exit_code: outcome.exit_code(),
+            added: Vec::new(),
+            changed: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
+
+    pub fn set_outcome(&mut self, outcome: SessionOutcome) {
+        self.outcome = outcome;
+        self.exit_code = outcome.exit_code();
+    }
+
+    /// Records how one file's annotations differ from when it was opened.
+    /// Annotations are compared line by line; nanot never adds or removes
+    /// lines, so a line index identifies the same line throughout. Within a
+    /// thread, entries are matched with an order-preserving diff, so
+    /// removing one entry doesn't make the ones after it look changed. An
+    /// entry replaced in place, including one whose attributes changed but
+    /// whose text didn't, counts as changed.
+    pub fn add_file(&mut self, file: &str, before: &[Vec<Annotation>], after: &[Vec<Annotation>]) {
+        for (i, (old_thread, new_thread)) in before.iter().zip(after).enumerate() {
+            let (old_ids, new_ids) = entry_ids(old_thread, new_thread);
+            for op in similar::capture_diff_slices(Algorithm::Myers, &old_ids, &new_ids) {
+                let (old_range, new_range) = match op {
+                    DiffOp::Equal { .. } => continue,
+                    DiffOp::Delete { old_index, old_len, .. } => (old_index..old_index + old_len, 0..0),
+                    DiffOp::Insert { new_index, new_len, .. } => (0..0, new_index..new_index + new_len),
+                    DiffOp::Replace {
+                        old_index,
+                        old_len,
+                        new_index,
+                        new_len,
+                    } => (old_index..old_index + old_len, new_index..new_index + new_len),
+                };
+                // Replaced entries pair up as changes; any left over were
+                // added or removed
+                let (old_entries, new_entries) = (&old_thread[old_range], &new_thread[new_range]);
+                for entry in 0..old_entries.len().max(new_entries.len()) {
+                    self.record(file, i + 1, old_entries.get(entry), new_entries.get(entry));
+                }
+            }
+        }
+    }
+
+    fn record(&mut self, file: &str, line: usize, old: Option<&Annotation>, new: Option<&Annotation>) {
+        let change = AnnotationChange {
+            file: file.to_string(),
+            line,
+            old_annotation: old.map(|a| a.text.clone()),
+            annotation: new.map(|a| a.text.clone()),
+            severity: new.or(old).and_then(Annotation::severity).map(|s| s.as_str()),
+        };
+        match (old, new) {
+            (None, _) => self.added.push(change),
+            (_, None) => self.removed.push(change),
+            _ => self.changed.push(change),
+        }
+    }
+
+    /// True if any annotation was added, changed or removed.
+    pub fn has_changes(&self) -> bool {
+        !(self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty())
+    }
+
+    /// One line for stdout, e.g. `Saved: 2 annotation(s) added, 1 changed, 0 removed`.
+    pub fn summary(&self) -> String {
+        let state = match self.outcome {
+            SessionOutcome::Discarded => "Discarded",
+            _ => "Saved",
+        };
+        format!(
+            "{}: {} annotation(s) added, {} changed, {} removed",
+            state,
+            self.added.len(),
+            self.changed.len(),
+            self.removed.len()
+        )
+    }
+
+    /// Writes the report as JSON, replacing `path` atomically.
+    pub fn write(&self, path: &str) -> io::Result<()> {
+        let mut output = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        output.push('\n');
+        atomic::write(path, output)
+    }
+}
+
+/// Entries of two versions of a thread as ids, equal entries sharing one,
+/// so the threads can be diffed as slices.
+fn entry_ids(old: &[Annotation], new: &[Annotation]) -> (Vec<usize>, Vec<usize>) {
+    let mut seen: Vec<&Annotation> = Vec::new();
+    let mut id = |annotation| match seen.iter().position(|&s| s == annotation) {
+        Some(id) => id,
+        None => {
+            seen.push(annotation);
+            seen.len() - 1
+        }
+    };
+    let old_ids = old.iter().map(&mut id).collect();
+    let new_ids = new.iter().map(&mut id).collect();
+    (old_ids, new_ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Severity;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn note(text: &str) -> Vec<Annotation> {
+        vec![text.into()]
+    }
+
+    #[test]
+    fn test_add_file_sorts_changes() {
+        let mut report = SessionReport::new(SessionOutcome::Annotated);
+        report.add_file(
+            "a.rs",
+            &[vec![], note("old"), note("gone"), note("same")],
+            &[note("new"), note("reworded"), vec![], note("same")],
+        );
+
+        assert_eq!(report.added.len(), 1);
+        assert_eq!(report.added[0].line, 1);
+        assert_eq!(report.changed[0].old_annotation.as_deref(), Some("old"));
+        assert_eq!(report.changed[0].annotation.as_deref(), Some("reworded"));
+        assert_eq!(report.removed[0].line, 3);
+        assert!(report.has_changes());
+        assert_eq!(report.summary(), "Saved: 1 annotation(s) added, 1 changed, 1 removed");
+    }
+
+    #[test]
+    fn test_add_file_compares_thread_entries() {
+        let mut report = SessionReport::new(SessionOutcome::Annotated);
+        let before = note("question");
+        let after = vec!["question".into(), "answer".into()];
+        report.add_file("a.rs", &[before], &[after]);
+
+        assert_eq!(report.added.len(), 1);
+        assert_eq!(report.added[0].annotation.as_deref(), Some("answer"));
+        assert!(report.changed.is_empty() && report.removed.is_empty());
+    }
+
+    #[test]
+    fn test_add_file_removing_first_entry_is_one_removal() {
+        let mut report = SessionReport::new(SessionOutcome::Annotated);
+        let before = vec!["a".into(), "b".into()];
+        report.add_file("a.rs", &[before], &[note("b")]);
+
+        assert_eq!(report.removed.len(), 1);
+        assert_eq!(report.removed[0].old_annotation.as_deref(), Some("a"));
+        assert!(report.added.is_empty() && report.changed.is_empty());
+
+        // A reworded entry between unchanged ones is still one change
+        let mut report = SessionReport::new(SessionOutcome::Annotated);
+        let before = vec!["a".into(), "b".into(), "c".into()];
+        let after = vec!["a".into(), "b2".into(), "c".into()];
+        report.add_file("a.rs", &[before], &[after]);
+
+        assert_eq!(report.changed.len(), 1);
+        assert_eq!(report.changed[0].old_annotation.as_deref(), Some("b"));
+        assert_eq!(report.changed[0].annotation.as_deref(), Some("b2"));
+        assert!(report.added.is_empty() && report.removed.is_empty());
+    }
+
+    #[test]
+    fn test_add_file_reports_severity_changes() {
+        let mut report = SessionReport::new(SessionOutcome::Annotated);
+        let mut raised = Annotation::new("check");
+        raised.set_severity(Some(Severity::Blocker));
+        report.add_file("a.rs", &[note("check")], &[vec![raised]]);
+
+        assert_eq!(report.changed.len(), 1);
+        assert_eq!(report.changed[0].severity, Some("blocker"));
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(SessionOutcome::Clean.exit_code(), 0);
+        assert_eq!(SessionOutcome::Annotated.exit_code(), 3);
+        assert_eq!(SessionOutcome::Discarded.exit_code(), 4);
+    }
+
+    #[test]
+    fn test_write_json() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("report.json");
+        let mut report = SessionReport::new(SessionOutcome::Discarded);
+        report.add_file("a.rs", &[vec![]], &[note("fix")]);
+        report.write(&path.to_string_lossy()).unwrap();
+
+        let value: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(value["outcome"], "discarded");
+        assert_eq!(value["exit_code"], 4);
+        assert_eq!(value["added"][0]["file"], "a.rs");
+        assert_eq!(value["added"][0]["annotation"], "fix");
+        assert!(value["added"][0].get("old_annotation").is_none());
+        assert_eq!(value["removed"].as_array().unwrap().len(), 0);
+    }
+}
@@ -0,0 +1,265 @@
+//! Headless scanning of files and directories for annotations.
+
+use crate::file;
+use crate::git::IgnoreRules;
+use crate::language;
+use crate::models::{Annotation, Line, RESOLVED};
+use crate::sidecar;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// A parsed file that contains at least one annotation.
+pub struct ScannedFile {
+    /// Path as reached from the scanned argument (e.g. `src/main.rs`)
+    pub path: String,
+    pub lines: Vec<Line>,
+    /// Comment style the file's markers use
+    pub lang_comment: String,
+    /// Layout to write the file back with
+    pub format: file::TextFormat,
+    /// The annotations come from a sidecar file rather than markers
+    pub sidecar: bool,
+    /// Sidecar threads whose line could not be found in the file
+    pub orphans: Vec<sidecar::AnchoredThread>,
+}
+
+impl ScannedFile {
+    /// Annotations as (1-based editor line number, annotation) pairs, one
+    /// per entry of each thread
+    pub fn annotations(&self) -> impl Iterator<Item = (usize, &Annotation)> {
+        self.lines
+            .iter()
+            .enumerate()
+            .flat_map(|(i, line)| line.annotations.iter().map(move |annotation| (i + 1, annotation)))
+    }
+}
+
+/// Expands files and directories into the list of files they contain.
+///
+/// Directories are walked recursively in sorted order, skipping `.git`,
+/// sidecar directories and anything excluded by `.gitignore`. Files named
+/// explicitly are always kept.
+pub fn collect_files(paths: &[String]) -> io::Result<Vec<String>> {
+    let mut files = Vec::new();
+    for path in paths {
+        let metadata = fs::metadata(path).map_err(|e| {
+            io::Error::new(e.kind(), format!("'{}' does not exist", path))
+        })?;
+        if metadata.is_dir() {
+            let rules = IgnoreRules::discover(Path::new(path));
+            walk_dir(Path::new(path), &rules, &mut files)?;
+        } else {
+            files.push(path.clone());
+        }
+    }
+    Ok(files)
+}
+
+fn walk_dir(dir: &Path, rules: &IgnoreRules, files: &mut Vec<String>) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        // Symlinks are skipped so directory cycles can't trap the walk
+        let file_type = entry.file_type()?;
+        let path = entry.path();
+        if entry.file_name() == ".git" || entry.file_name() == sidecar::SIDECAR_DIR || rules.is_ignored(&path) {
+            continue;
+        }
+        if file_type.is_dir() {
+            walk_dir(&path, rules, files)?;
+        } else if file_type.is_file() {
+            files.push(display_path(&path));
+        }
+    }
+    Ok(())
+}
+
+/// Drops the leading `./` a walk of the current directory produces.
+fn display_path(path: &Path) -> String {
+    let path = path.strip_prefix(".").unwrap_or(path);
+    path.to_string_lossy().to_string()
+}
+
+/// Parses every file under `paths` and keeps those with annotations, read
+/// from the sidecar file for files that keep them there (including those
+/// only holding orphaned threads). Files that aren't valid UTF-8 (binaries)
+/// are skipped.
+pub fn scan(paths: &[String]) -> io::Result<Vec<ScannedFile>> {
+    let mut scanned = Vec::new();
+    for path in collect_files(paths)? {
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => continue,
+            Err(e) => return Err(e),
+        };
+        let lang_comment = language::detect(&path, &content).comment;
+        let inline = file::parse_file(&content, &lang_comment);
+        let sidecar = sidecar::storage_reason(&path, &content, &inline, false).is_some();
+        let (lines, orphans) = if sidecar { sidecar::load(&path, &content)? } else { (inline, Vec::new()) };
+        if !orphans.is_empty() || lines.iter().any(|line| !line.annotations.is_empty()) {
+            let format = file::TextFormat::detect(&content);
+            scanned.push(ScannedFile { path, lines, lang_comment, format, sidecar, orphans });
+        }
+    }
+    Ok(scanned)
+}
+
+/// Formats annotations as `path:line: text`, one per line, with the
+/// severity, a resolved status and the lines a range covers in brackets
+/// before the text when set (`[major] [resolved] [lines 4-9] text`).
+/// Further lines of a multi-line annotation follow, indented. Orphaned
+/// sidecar threads are listed last with the line they used to be on.
+pub fn format_list(files: &[ScannedFile]) -> String {
+    let mut output = String::new();
+    for file in files {
+        for (line_number, annotation) in file.annotations() {
+            let mut text_lines = annotation.text.split('\n');
+            let first = text_lines.next().unwrap_or_default();
+            let last_line = (line_number + annotation.span() - 1).min(file.lines.len());
+            let range = (last_line > line_number).then(|| format!("lines {}-{}", line_number, last_line));
+            let tags: String = [
+                annotation.severity().map(|s| s.as_str().to_string()),
+                annotation.is_resolved().then(|| RESOLVED.to_string()),
+                range,
+            ]
+            .into_iter()
+            .flatten()
+            .map(|tag| format!("[{}] ", tag))
+            .collect();
+            output.push_str(&format!("{}:{}: {}{}\n", file.path, line_number, tags, first));
+            for line in text_lines {
+                output.push_str(format!("    {}", line).trim_end());
+                output.push('\n');
+            }
+        }
+    }
+
+    let orphans: Vec<_> = files
+        .iter()
+        .flat_map(|file| file.orphans.iter().map(move |orphan| (&file.path, orphan)))
+        .collect();
+    if !orphans.is_empty() {
+        output.push_str("\nOrphaned annotations (their line was not found):\n");
+    }
+    for (path, orphan) in orphans {
+        for entry in &orphan.thread {
+            let first = entry.text.split('\n').next().unwrap_or_default();
+            output.push_str(&format!("{} (was line {}): {}\n", path, orphan.line, first));
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    fn path_str(dir: &TempDir, name: &str) -> String {
+        dir.path().join(name).to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_scan_directory_lists_annotations() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/a.rs"), "fn a() {}\n// [ANNOTATION lines=2] rename\nfn b() {}\n}\n").unwrap();
+        fs::write(dir.path().join("b.py"), "# [ANNOTATION] check\n# [ANNOTATION]+\n# [ANNOTATION]+ twice\n# [ANNOTATION sev=nit by=bob status=resolved] done\nx = 1\n").unwrap();
+        fs::write(dir.path().join("c.txt"), "nothing here\n").unwrap();
+
+        let files = scan(&[dir.path().to_string_lossy().to_string()]).unwrap();
+        let root = dir.path().to_string_lossy().to_string();
+        assert_eq!(
+            format_list(&files),
+            format!("{root}/b.py:1: check\n\n    twice\n{root}/b.py:1: [nit] [resolved] done\n{root}/src/a.rs:2: [lines 2-3] rename\n")
+        );
+    }
+
+    #[test]
+    fn test_scan_respects_markdown_code_fences() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("notes.md"),
+            "[ANNOTATION] real\nIntro\n```\n[ANNOTATION] example\ncode\n```\n",
+        )
+        .unwrap();
+
+        let files = scan(&[path_str(&dir, "notes.md")]).unwrap();
+        let annotations: Vec<_> = files[0].annotations().map(|(line, a)| (line, a.text.as_str())).collect();
+        assert_eq!(annotations, vec![(1, "real")]);
+    }
+
+    #[test]
+    fn test_scan_skips_gitignored_and_binary_files() {
+        let dir = TempDir::new().unwrap();
+        Command::new("git")
+            .args(["init", "-q"])
+            .current_dir(dir.path())
+            .output()
+            .expect("Failed to init git repo");
+        fs::write(dir.path().join(".gitignore"), "target/\n").unwrap();
+        fs::create_dir(dir.path().join("target")).unwrap();
+        fs::write(dir.path().join("target/gen.rs"), "// [ANNOTATION] generated\nfn g() {}\n").unwrap();
+        fs::write(dir.path().join("blob.bin"), [0xffu8, 0xfe, 0x00]).unwrap();
+        fs::write(dir.path().join("lib.rs"), "// [ANNOTATION] keep\nfn l() {}\n").unwrap();
+
+        let files = scan(&[dir.path().to_string_lossy().to_string()]).unwrap();
+        let paths: Vec<_> = files.iter().map(|f| f.path.clone()).collect();
+        assert_eq!(paths, vec![path_str(&dir, "lib.rs")]);
+    }
+
+    #[test]
+    fn test_scan_reads_sidecar_annotations() {
+        let dir = TempDir::new().unwrap();
+        let path = path_str(&dir, "data.json");
+        fs::write(&path, "{\n  \"a\": 1\n}\n").unwrap();
+        let mut lines = file::parse_plain("{\n  \"a\": 1\n}\n");
+        lines[1].annotations.push("rename".into());
+        sidecar::save(&path, &lines, &[]).unwrap();
+
+        let files = scan(&[dir.path().to_string_lossy().to_string()]).unwrap();
+        assert_eq!(files.len(), 1);
+        assert!(files[0].sidecar);
+        assert_eq!(format_list(&files), format!("{}:2: rename\n", path));
+    }
+
+    #[test]
+    fn test_scan_lists_orphaned_sidecar_annotations() {
+        let dir = TempDir::new().unwrap();
+        let path = path_str(&dir, "data.json");
+        let mut lines = file::parse_plain("{\n  \"a\": 1\n}\n");
+        lines[1].annotations.push("rename\nto alpha".into());
+        sidecar::save(&path, &lines, &[]).unwrap();
+        fs::write(&path, "[\n  true,\n  false\n]\n").unwrap();
+
+        let files = scan(std::slice::from_ref(&path)).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].annotations().count(), 0);
+        assert_eq!(
+            format_list(&files),
+            format!("\nOrphaned annotations (their line was not found):\n{} (was line 2): rename\n", path)
+        );
+    }
+
+    #[test]
+    fn test_collect_files_keeps_explicit_ignored_file() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("a.log"), "").unwrap();
+        let files = collect_files(&[path_str(&dir, "a.log")]).unwrap();
+        assert_eq!(files, vec![path_str(&dir, "a.log")]);
+    }
+
+    #[test]
+    fn test_collect_files_missing_path_is_error() {
+        assert!(collect_files(&["/nonexistent/nanot/path".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_display_path_strips_current_dir() {
+        assert_eq!(display_path(Path::new("./src/a.rs")), "src/a.rs");
+        assert_eq!(display_path(Path::new("src/a.rs")), "src/a.rs");
+    }
+}
@@ -0,0 +1,464 @@
+//! Keeping annotations in a sidecar file for files that can't hold inline
+//! markers: JSON, lockfiles, CSV, minified assets and generated code.
+//!
+//! A file's annotations are stored in `.nanotation/<path>.json` at the root
+//! of its git working tree, or next to the file outside one. Each annotated
+//! line is saved with its line number, its content and hash and the lines
+//! around it, so the annotations can find their line again after the file
+//! has changed (see `reanchor`). Threads whose line can't be found are kept
+//! in the sidecar file as orphans. The annotated file itself is never
+//! written.
+
+use crate::atomic;
+use crate::file;
+use crate::git;
+use crate::models::{Annotation, Line};
+use crate::reanchor::{self, Anchor};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Directory holding sidecar files
+pub const SIDECAR_DIR: &str = ".nanotation";
+
+/// Version written to new sidecar files
+const FORMAT_VERSION: u32 = 1;
+
+/// Lines of context saved before and after each annotated line
+const CONTEXT_LINES: usize = 2;
+
+/// How many lines at the start of a file are searched for a "generated"
+/// header
+const HEADER_LINES: usize = 5;
+
+/// Scripts and stylesheets with a line this long are treated as minified
+const MINIFIED_LINE_LENGTH: usize = 1000;
+
+/// Lockfiles that don't use the `.lock` extension
+const LOCKFILES: &[&str] = &["package-lock.json", "npm-shrinkwrap.json", "pnpm-lock.yaml", "go.sum"];
+
+/// Contents of one sidecar file.
+#[derive(Debug, Serialize, Deserialize)]
+struct SidecarFile {
+    version: u32,
+    /// The annotated file, relative to the directory holding `.nanotation`
+    file: String,
+    annotations: Vec<AnchoredThread>,
+    /// Threads whose line could not be found when the file was last loaded
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    orphaned: Vec<AnchoredThread>,
+}
+
+/// How a buffer keeps its annotations in a sidecar file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Sidecar {
+    /// Why the file uses a sidecar file (see `storage_reason`)
+    pub reason: String,
+    /// Threads whose line could not be found again. They are written back
+    /// untouched so they are never lost, and placed once their line returns.
+    pub orphans: Vec<AnchoredThread>,
+}
+
+impl Sidecar {
+    /// Status bar text for the buffer, counting orphaned threads.
+    pub fn status(&self) -> String {
+        match self.orphans.len() {
+            0 => self.reason.clone(),
+            count => format!("{} ({} orphaned, see nanot list)", self.reason, count),
+        }
+    }
+}
+
+/// The annotation thread of one line and what it takes to find that line
+/// again.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchoredThread {
+    /// 1-based line number when the thread was saved
+    pub line: usize,
+    /// The line's content
+    #[serde(default)]
+    pub content: Option<String>,
+    /// Hash of the line's content (see `reanchor::content_hash`)
+    pub hash: String,
+    pub context_before: Vec<String>,
+    pub context_after: Vec<String>,
+    pub thread: Vec<StoredAnnotation>,
+}
+
+/// One thread entry as written in a sidecar file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredAnnotation {
+    pub text: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attributes: BTreeMap<String, String>,
+}
+
+impl From<&Annotation> for StoredAnnotation {
+    fn from(annotation: &Annotation) -> Self {
+        StoredAnnotation {
+            text: annotation.text.clone(),
+            attributes: annotation.attributes.iter().cloned().collect(),
+        }
+    }
+}
+
+impl From<StoredAnnotation> for Annotation {
+    fn from(stored: StoredAnnotation) -> Self {
+        Annotation {
+            text: stored.text,
+            attributes: stored.attributes.into_iter().collect(),
+        }
+    }
+}
+
+/// Why inline markers would damage the file at `path`, if they would.
+pub fn inline_unsafe(path: &str, content: &str) -> Option<&'static str> {
+    let file_name = Path::new(path).file_name().and_then(|n| n.to_str()).unwrap_or(path);
+    let extension = Path::new(file_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase();
+
+    if extension == "lock" || LOCKFILES.contains(&file_name) {
+        return Some("Lockfiles are rewritten by their tools");
+    }
+    match extension.as_str() {
+        "json" | "jsonl" | "ndjson" | "geojson" | "ipynb" => return Some("JSON can't hold comments"),
+        "csv" | "tsv" => return Some("CSV files can't hold comments"),
+        _ => {}
+    }
+    let minified = file_name.contains(".min.")
+        || (matches!(extension.as_str(), "js" | "mjs" | "cjs" | "css")
+            && content.lines().any(|line| line.len() > MINIFIED_LINE_LENGTH));
+    if minified {
+        return Some("Minified files can't take marker lines");
+    }
+    let generated = content
+        .lines()
+        .take(HEADER_LINES)
+        .any(|line| line.contains("@generated") || line.contains("DO NOT EDIT"));
+    generated.then_some("Generated files lose markers when regenerated")
+}
+
+/// Why the file at `path` keeps its annotations in a sidecar file, or
+/// `None` if they are written inline. A file uses a sidecar when it already
+/// has one, when `explicit` asks for it, or when inline markers would be
+/// unsafe and `inline` (the file parsed for markers) holds none yet.
+pub fn storage_reason(path: &str, content: &str, inline: &[Line], explicit: bool) -> Option<String> {
+    let sidecar_path = path_for(path);
+    let location = display_path(&sidecar_path);
+    if explicit || sidecar_path.is_file() {
+        return Some(format!("Annotations are kept in {}", location));
+    }
+    let reason = inline_unsafe(path, content)?;
+    if inline.iter().any(|line| !line.annotations.is_empty()) {
+        return None;
+    }
+    Some(format!("{}: annotations are kept in {}", reason, location))
+}
+
+/// Where the sidecar file of `path` lives, and the name it records for the
+/// annotated file.
+fn location(path: &str) -> (PathBuf, String) {
+    let (root, relative) = git::work_tree_path(path).unwrap_or_else(|| {
+        let path = Path::new(path);
+        let parent = path.parent().unwrap_or(Path::new(""));
+        (parent.to_path_buf(), PathBuf::from(path.file_name().unwrap_or(path.as_os_str())))
+    });
+    let name = relative.to_string_lossy().replace('\\', "/");
+    (root.join(SIDECAR_DIR).join(format!("{}.json", name)), name)
+}
+
+/// Path of the sidecar file holding the annotations of `path`.
+pub fn path_for(path: &str) -> PathBuf {
+    location(path).0
+}
+
+/// `path` relative to the current directory when it lies below it.
+pub fn display_path(path: &Path) -> String {
+    let cwd = std::env::current_dir().ok().and_then(|cwd| cwd.canonicalize().ok());
+    cwd.and_then(|cwd| path.strip_prefix(cwd).ok())
+        .unwrap_or(path)
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Anchors for every annotated line.
+pub fn anchors(lines: &[Line]) -> Vec<AnchoredThread> {
+    let contents = |range: &[Line]| range.iter().map(|line| line.content.clone()).collect();
+    lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !line.annotations.is_empty())
+        .map(|(i, line)| AnchoredThread {
+            line: i + 1,
+            content: Some(line.content.clone()),
+            hash: reanchor::content_hash(&line.content),
+            context_before: contents(&lines[i.saturating_sub(CONTEXT_LINES)..i]),
+            context_after: contents(&lines[i + 1..(i + 1 + CONTEXT_LINES).min(lines.len())]),
+            thread: line.annotations.iter().map(StoredAnnotation::from).collect(),
+        })
+        .collect()
+}
+
+/// Puts stored threads back onto the lines they now belong to (see
+/// `reanchor::locate`) and returns those that can't be placed.
+pub fn place(lines: &mut [Line], anchors: Vec<AnchoredThread>) -> Vec<AnchoredThread> {
+    let contents: Vec<&str> = lines.iter().map(|line| line.content.as_str()).collect();
+    let placements: Vec<Option<usize>> = anchors
+        .iter()
+        .map(|thread| {
+            reanchor::locate(
+                &contents,
+                &Anchor {
+                    index: thread.line.saturating_sub(1),
+                    content: thread.content.as_deref(),
+                    hash: &thread.hash,
+                    context_before: &thread.context_before,
+                    context_after: &thread.context_after,
+                },
+            )
+        })
+        .collect();
+
+    let mut orphans = Vec::new();
+    for (thread, placement) in anchors.into_iter().zip(placements) {
+        match placement {
+            Some(index) => lines[index].annotations.extend(thread.thread.into_iter().map(Annotation::from)),
+            None => orphans.push(thread),
+        }
+    }
+    orphans
+}
+
+/// Sidecar file content for `lines` and the `orphans` still unplaced, or
+/// `None` when there are no annotations to keep.
+pub fn render(path: &str, lines: &[Line], orphans: &[AnchoredThread]) -> Option<String> {
+    let annotations = anchors(lines);
+    if annotations.is_empty() && orphans.is_empty() {
+        return None;
+    }
+    let sidecar = SidecarFile {
+        version: FORMAT_VERSION,
+        file: location(path).1,
+        annotations,
+        orphaned: orphans.to_vec(),
+    };
+    let mut output = serde_json::to_string_pretty(&sidecar).unwrap_or_default();
+    output.push('\n');
+    Some(output)
+}
+
+/// Reads the file at `path` holding `content` into lines, with the
+/// annotations from its sidecar file (if there is one yet) moved to where
+/// their lines are now. Also returns the threads that found no line,
+/// including earlier orphans that still don't.
+pub fn load(path: &str, content: &str) -> io::Result<(Vec<Line>, Vec<AnchoredThread>)> {
+    let mut lines = file::parse_plain(content);
+    let sidecar_path = path_for(path);
+    let json = match fs::read_to_string(&sidecar_path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((lines, Vec::new())),
+        Err(e) => return Err(e),
+    };
+    let invalid = |message: String| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid sidecar '{}': {}", display_path(&sidecar_path), message),
+        )
+    };
+    let sidecar: SidecarFile = serde_json::from_str(&json).map_err(|e| invalid(e.to_string()))?;
+    if sidecar.version > FORMAT_VERSION {
+        return Err(invalid(format!("version {} needs a newer nanot", sidecar.version)));
+    }
+    let mut anchors = sidecar.annotations;
+    anchors.extend(sidecar.orphaned);
+    let orphans = place(&mut lines, anchors);
+    Ok((lines, orphans))
+}
+
+/// Writes the annotations in `lines` and the unplaced `orphans` to the
+/// sidecar file of `path`, or deletes the sidecar file once there are none.
+pub fn save(path: &str, lines: &[Line], orphans: &[AnchoredThread]) -> io::Result<()> {
+    let sidecar_path = path_for(path);
+    match render(path, lines, orphans) {
+        Some(json) => {
+            if let Some(dir) = sidecar_path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            atomic::write(&sidecar_path, json)
+        }
+        None => match fs::remove_file(&sidecar_path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Severity;
+    use tempfile::TempDir;
+
+    fn write_temp(dir: &TempDir, name: &str, content: &str) -> String {
+        let path = dir.path().join(name);
+        fs::write(&path, content).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_inline_unsafe() {
+        assert!(inline_unsafe("config/data.json", "{}").is_some());
+        assert!(inline_unsafe("Cargo.lock", "").is_some());
+        assert!(inline_unsafe("web/package-lock.json", "").is_some());
+        assert!(inline_unsafe("go.sum", "").is_some());
+        assert!(inline_unsafe("table.CSV", "a,b\n").is_some());
+        assert!(inline_unsafe("app.min.js", "").is_some());
+        assert!(inline_unsafe("bundle.js", &"x".repeat(MINIFIED_LINE_LENGTH + 1)).is_some());
+        assert!(inline_unsafe("api.pb.go", "// Code generated by protoc-gen-go. DO NOT EDIT.\npackage api\n").is_some());
+
+        assert_eq!(inline_unsafe("src/main.rs", "fn main() {}\n"), None);
+        assert_eq!(inline_unsafe("notes.md", &"word ".repeat(MINIFIED_LINE_LENGTH)), None);
+    }
+
+    #[test]
+    fn test_storage_reason() {
+        let dir = TempDir::new().unwrap();
+        let json = write_temp(&dir, "data.json", "{}\n");
+        let reason = storage_reason(&json, "{}\n", &file::parse_plain("{}\n"), false).unwrap();
+        assert!(reason.starts_with("JSON can't hold comments: annotations are kept in "));
+        assert!(reason.ends_with(".nanotation/data.json.json"));
+
+        // Markers already in the file stay where they are
+        let marked = "// [ANNOTATION] old\n{}\n";
+        assert_eq!(storage_reason(&json, marked, &file::parse_file(marked, "//"), false), None);
+
+        let rs = write_temp(&dir, "a.rs", "fn a() {}\n");
+        let lines = file::parse_plain("fn a() {}\n");
+        assert_eq!(storage_reason(&rs, "fn a() {}\n", &lines, false), None);
+        assert!(storage_reason(&rs, "fn a() {}\n", &lines, true).is_some());
+
+        // Once written, a sidecar file is used without being asked for
+        let mut annotated = lines.clone();
+        annotated[0].annotations.push("check".into());
+        save(&rs, &annotated, &[]).unwrap();
+        assert!(storage_reason(&rs, "fn a() {}\n", &lines, false).is_some());
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let content = "{\n  \"a\": 1,\n  \"b\": 2\n}\n";
+        let path = write_temp(&dir, "data.json", content);
+
+        let (mut lines, orphans) = load(&path, content).unwrap();
+        assert!(orphans.is_empty());
+        assert!(lines.iter().all(|line| line.annotations.is_empty()));
+        let mut annotation = Annotation::new("rename\nto alpha");
+        annotation.set_severity(Some(Severity::Major));
+        lines[1].annotations = vec![annotation, "agreed".into()];
+        save(&path, &lines, &[]).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), content);
+        let sidecar_path = dir.path().join(SIDECAR_DIR).join("data.json.json");
+        let value: serde_json::Value = serde_json::from_str(&fs::read_to_string(&sidecar_path).unwrap()).unwrap();
+        assert_eq!(value["file"], "data.json");
+        assert_eq!(value["annotations"][0]["line"], 2);
+        assert_eq!(value["annotations"][0]["content"], "  \"a\": 1,");
+        assert_eq!(value["annotations"][0]["hash"], reanchor::content_hash("  \"a\": 1,"));
+        assert_eq!(value["annotations"][0]["context_before"], serde_json::json!(["{"]));
+        assert_eq!(value["annotations"][0]["thread"][0]["attributes"]["sev"], "major");
+
+        assert_eq!(load(&path, content).unwrap(), (lines, Vec::new()));
+    }
+
+    #[test]
+    fn test_load_follows_moved_line() {
+        let dir = TempDir::new().unwrap();
+        let path = write_temp(&dir, "data.csv", "id,name\n1,a\n2,b\n");
+        let (mut lines, _) = load(&path, "id,name\n1,a\n2,b\n").unwrap();
+        lines[2].annotations.push("duplicate?".into());
+        save(&path, &lines, &[]).unwrap();
+
+        let (moved, orphans) = load(&path, "id,name\n0,z\n1,a\n2,b\n").unwrap();
+        assert_eq!(moved[3].annotations, vec!["duplicate?"]);
+        assert!(orphans.is_empty());
+
+        let (edited, _) = load(&path, "id,name\n1,a\n2,bb\n").unwrap();
+        assert_eq!(edited[2].annotations, vec!["duplicate?"]);
+    }
+
+    #[test]
+    fn test_orphans_are_kept_until_their_line_returns() {
+        let dir = TempDir::new().unwrap();
+        let content = "id,name\n1,a\n2,b\n";
+        let path = write_temp(&dir, "data.csv", content);
+        let (mut lines, _) = load(&path, content).unwrap();
+        lines[2].annotations.push("duplicate?".into());
+        lines[0].annotations.push("header".into());
+        save(&path, &lines, &[]).unwrap();
+
+        let rewritten = "id,name\n7,x\n8,y\n";
+        let (lines, orphans) = load(&path, rewritten).unwrap();
+        assert_eq!(lines[0].annotations, vec!["header"]);
+        assert!(lines[1..].iter().all(|line| line.annotations.is_empty()));
+        assert_eq!(orphans.len(), 1);
+        assert_eq!(orphans[0].line, 3);
+        assert_eq!(orphans[0].thread[0].text, "duplicate?");
+
+        // Saving keeps the orphan, and it finds its line once that is back
+        save(&path, &lines, &orphans).unwrap();
+        let (lines, orphans) = load(&path, rewritten).unwrap();
+        assert_eq!(orphans.len(), 1);
+        save(&path, &lines, &orphans).unwrap();
+        let (restored, orphans) = load(&path, content).unwrap();
+        assert_eq!(restored[2].annotations, vec!["duplicate?"]);
+        assert!(orphans.is_empty());
+
+        // Orphans alone keep the sidecar file
+        save(&path, &file::parse_plain(rewritten), &load(&path, rewritten).unwrap().1).unwrap();
+        assert!(path_for(&path).is_file());
+    }
+
+    #[test]
+    fn test_sidecar_status_counts_orphans() {
+        let mut sidecar = Sidecar { reason: "JSON can't hold comments".to_string(), orphans: Vec::new() };
+        assert_eq!(sidecar.status(), "JSON can't hold comments");
+        sidecar.orphans.push(AnchoredThread {
+            line: 1,
+            content: None,
+            hash: reanchor::content_hash(""),
+            context_before: Vec::new(),
+            context_after: Vec::new(),
+            thread: Vec::new(),
+        });
+        assert_eq!(sidecar.status(), "JSON can't hold comments (1 orphaned, see nanot list)");
+    }
+
+    #[test]
+    fn test_save_without_annotations_removes_sidecar() {
+        let dir = TempDir::new().unwrap();
+        let path = write_temp(&dir, "Cargo.lock", "version = 3\n");
+        let (mut lines, _) = load(&path, "version = 3\n").unwrap();
+        lines[0].annotations.push("bump".into());
+        save(&path, &lines, &[]).unwrap();
+        assert!(path_for(&path).is_file());
+
+        lines[0].annotations.clear();
+        save(&path, &lines, &[]).unwrap();
+        assert!(!path_for(&path).exists());
+    }
+
+    #[test]
+    fn test_load_rejects_bad_sidecar() {
+        let dir = TempDir::new().unwrap();
+        let path = write_temp(&dir, "data.json", "{}\n");
+        fs::create_dir(dir.path().join(SIDECAR_DIR)).unwrap();
+        fs::write(path_for(&path), "not json").unwrap();
+        assert_eq!(load(&path, "{}\n").unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
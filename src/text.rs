@@ -32,7 +32,12 @@ pub fn calculate_padding(current_text: &str, target_width: usize) -> usize {
 }
 
 /// Wraps text to fit within a specified width, preserving leading and trailing whitespace.
+/// Each newline starts a new wrapped line.
 pub fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    if text.contains('\n') {
+        return text.split('\n').flat_map(|line| wrap_text(line, width)).collect();
+    }
+
     // Preserve leading whitespace
     let leading_whitespace: String = text.chars()
         .take_while(|c| c.is_whitespace())
@@ -91,6 +96,144 @@ pub fn wrap_text(text: &str, width: usize) -> Vec<String> {
     wrapped
 }
 
+/// Wraps styled text segments to fit within a specified width.
+/// Returns a list of lines, where each line is a list of (style, text) tuples.
+/// Wraps styled text segments to fit within a specified width.
+/// Returns a list of lines, where each line is a list of (style, text) tuples.
+pub fn wrap_styled_text<T: Clone + Copy + PartialEq>(segments: &[(T, &str)], width: usize) -> Vec<Vec<(T, String)>> {
+    // Collect all text to calculate leading whitespace
+    let full_text: String = segments.iter().map(|(_, s)| *s).collect();
+    
+    let leading_whitespace: String = full_text.chars()
+        .take_while(|c| c.is_whitespace())
+        .collect();
+    let leading_width = leading_whitespace.width();
+    
+    // Quick exit for empty case
+    if full_text.trim().is_empty() {
+         if !full_text.is_empty()
+             && let Some((style, _)) = segments.first()
+         {
+             return vec![vec![(*style, full_text)]];
+         }
+         return vec![vec![]];
+    }
+    
+    if leading_width >= width {
+        let line = segments.iter().map(|(style, text)| (*style, text.to_string())).collect();
+        return vec![line];
+    }
+
+    let _available_width = width.saturating_sub(leading_width);
+    let mut wrapped_lines = Vec::new();
+    let mut current_line: Vec<(T, String)> = Vec::new();
+    let mut current_width = 0;
+    
+    struct Token<T> {
+        style: T,
+        text: String,
+        is_whitespace: bool,
+        width: usize,
+    }
+    
+    let mut tokens: Vec<Token<T>> = Vec::new();
+    
+    for (style, text) in segments {
+        let char_indices: Vec<(usize, char)> = text.char_indices().collect();
+        let mut start = 0;
+        
+        while start < char_indices.len() {
+            let start_char = char_indices[start].1;
+            let mut end = start + 1;
+            let is_ws = start_char.is_whitespace();
+            
+            while end < char_indices.len() {
+                if char_indices[end].1.is_whitespace() != is_ws {
+                    break;
+                }
+                end += 1;
+            }
+            
+            let slice_end = if end < char_indices.len() {
+                char_indices[end].0
+            } else {
+                text.len()
+            };
+            
+            let slice_start = char_indices[start].0;
+            let token_text = &text[slice_start..slice_end];
+            
+            tokens.push(Token {
+                style: *style,
+                text: token_text.to_string(),
+                is_whitespace: is_ws,
+                width: token_text.width(),
+            });
+            
+            start = end;
+        }
+    }
+
+    let indent_style = segments.first().map(|(s, _)| *s).expect("Checked empty");
+    
+    for token in tokens {
+        if token.is_whitespace {
+             if current_width + token.width > width {
+                 wrapped_lines.push(current_line);
+                 current_line = Vec::new();
+                 if !leading_whitespace.is_empty() {
+                      current_line.push((indent_style, leading_whitespace.clone()));
+                 }
+                 current_width = leading_width;
+                 continue; 
+             }
+             
+             current_line.push((token.style, token.text));
+             current_width += token.width;
+        } else {
+            if current_width + token.width > width {
+                 wrapped_lines.push(current_line);
+                 current_line = Vec::new();
+                 if !leading_whitespace.is_empty() {
+                      current_line.push((indent_style, leading_whitespace.clone()));
+                 }
+                 current_width = leading_width;
+            }
+            
+            current_line.push((token.style, token.text));
+            current_width += token.width;
+        }
+    }
+    
+    if !current_line.is_empty() {
+        wrapped_lines.push(current_line);
+    }
+    
+    // Merge adjacent segments with same style
+    let mut merged_wrapped_lines = Vec::new();
+    for line in wrapped_lines {
+        let mut merged_line: Vec<(T, String)> = Vec::new();
+        if let Some((first_style, first_text)) = line.first() {
+            let mut current_style = *first_style;
+            let mut current_text = first_text.clone();
+            
+            for (style, text) in line.iter().skip(1) {
+                if *style == current_style {
+                    current_text.push_str(text);
+                } else {
+                    merged_line.push((current_style, current_text));
+                    current_style = *style;
+                    current_text = text.clone();
+                }
+            }
+            merged_line.push((current_style, current_text));
+        }
+        merged_wrapped_lines.push(merged_line);
+    }
+    
+    merged_wrapped_lines
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -236,6 +379,12 @@ mod tests {
         assert!(wrapped[0].ends_with(' '), "Trailing space should be preserved");
     }
     
+    #[test]
+    fn test_wrap_text_breaks_at_newlines() {
+        assert_eq!(wrap_text("one two\n\nthree", 4), vec!["one", "two", "", "three"]);
+        assert_eq!(wrap_text("end\n", 20), vec!["end", ""]);
+    }
+
     #[test]
     fn test_wrap_styled_text() {
         let style1 = 1;
@@ -265,142 +414,3 @@ mod tests {
         assert_eq!(wrapped[0][0].1, "Hello ");
     }
 }
-
-/// Wraps styled text segments to fit within a specified width.
-/// Returns a list of lines, where each line is a list of (style, text) tuples.
-/// Wraps styled text segments to fit within a specified width.
-/// Returns a list of lines, where each line is a list of (style, text) tuples.
-pub fn wrap_styled_text<T: Clone + Copy + PartialEq>(segments: &[(T, &str)], width: usize) -> Vec<Vec<(T, String)>> {
-    // Collect all text to calculate leading whitespace
-    let full_text: String = segments.iter().map(|(_, s)| *s).collect();
-    
-    let leading_whitespace: String = full_text.chars()
-        .take_while(|c| c.is_whitespace())
-        .collect();
-    let leading_width = leading_whitespace.width();
-    
-    // Quick exit for empty case
-    if full_text.trim().is_empty() {
-         if !full_text.is_empty() {
-             if let Some((style, _)) = segments.first() {
-                return vec![vec![(*style, full_text)]];
-             }
-         }
-         return vec![vec![]];
-    }
-    
-    if leading_width >= width {
-        let line = segments.iter().map(|(style, text)| (*style, text.to_string())).collect();
-        return vec![line];
-    }
-
-    let _available_width = width.saturating_sub(leading_width);
-    let mut wrapped_lines = Vec::new();
-    let mut current_line: Vec<(T, String)> = Vec::new();
-    let mut current_width = 0;
-    
-    struct Token<T> {
-        style: T,
-        text: String,
-        is_whitespace: bool,
-        width: usize,
-    }
-    
-    let mut tokens: Vec<Token<T>> = Vec::new();
-    
-    for (style, text) in segments {
-        let char_indices: Vec<(usize, char)> = text.char_indices().collect();
-        let mut start = 0;
-        
-        while start < char_indices.len() {
-            let start_char = char_indices[start].1;
-            let mut end = start + 1;
-            let is_ws = start_char.is_whitespace();
-            
-            while end < char_indices.len() {
-                if char_indices[end].1.is_whitespace() != is_ws {
-                    break;
-                }
-                end += 1;
-            }
-            
-            let slice_end = if end < char_indices.len() {
-                char_indices[end].0
-            } else {
-                text.len()
-            };
-            
-            let slice_start = char_indices[start].0;
-            let token_text = &text[slice_start..slice_end];
-            
-            tokens.push(Token {
-                style: *style,
-                text: token_text.to_string(),
-                is_whitespace: is_ws,
-                width: token_text.width(),
-            });
-            
-            start = end;
-        }
-    }
-
-    let indent_style = segments.first().map(|(s, _)| *s).expect("Checked empty");
-    
-    for token in tokens {
-        if token.is_whitespace {
-             if current_width + token.width > width {
-                 wrapped_lines.push(current_line);
-                 current_line = Vec::new();
-                 if !leading_whitespace.is_empty() {
-                      current_line.push((indent_style, leading_whitespace.clone()));
-                 }
-                 current_width = leading_width;
-                 continue; 
-             }
-             
-             current_line.push((token.style, token.text));
-             current_width += token.width;
-        } else {
-            if current_width + token.width > width {
-                 wrapped_lines.push(current_line);
-                 current_line = Vec::new();
-                 if !leading_whitespace.is_empty() {
-                      current_line.push((indent_style, leading_whitespace.clone()));
-                 }
-                 current_width = leading_width;
-            }
-            
-            current_line.push((token.style, token.text));
-            current_width += token.width;
-        }
-    }
-    
-    if !current_line.is_empty() {
-        wrapped_lines.push(current_line);
-    }
-    
-    // Merge adjacent segments with same style
-    let mut merged_wrapped_lines = Vec::new();
-    for line in wrapped_lines {
-        let mut merged_line: Vec<(T, String)> = Vec::new();
-        if let Some((first_style, first_text)) = line.first() {
-            let mut current_style = *first_style;
-            let mut current_text = first_text.clone();
-            
-            for (style, text) in line.iter().skip(1) {
-                if *style == current_style {
-                    current_text.push_str(text);
-                } else {
-                    merged_line.push((current_style, current_text));
-                    current_style = *style;
-                    current_text = text.clone();
-                }
-            }
-            merged_line.push((current_style, current_text));
-        }
-        merged_wrapped_lines.push(merged_line);
-    }
-    
-    merged_wrapped_lines
-}
-
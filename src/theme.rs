@@ -1,3 +1,4 @@
+use crate::models::Severity;
 use crossterm::style::Color;
 
 #[derive(Clone, Copy, PartialEq)]
@@ -13,6 +14,13 @@ pub struct ColorScheme {
     pub selected_bg: Color,
     pub annotated_bg: Color,
     pub annotated_selected_bg: Color,
+    // Lines whose whole thread is resolved, and resolved entries' text
+    pub resolved_bg: Color,
+    pub resolved_selected_bg: Color,
+    pub resolved_fg: Color,
+    // Lines covered by a range annotation below its first line
+    pub range_bg: Color,
+    pub range_selected_bg: Color,
     pub annotation_window_bg: Color,
     pub annotation_window_fg: Color,
     pub status_bg: Color,
@@ -29,6 +37,22 @@ pub struct ColorScheme {
     // Diff indicator in status bar
     pub diff_indicator_bg: Color,
     pub diff_indicator_fg: Color,
+    // Line numbers of annotated lines, by the thread's highest severity
+    pub severity_blocker_fg: Color,
+    pub severity_major_fg: Color,
+    pub severity_minor_fg: Color,
+    pub severity_nit_fg: Color,
+}
+
+impl ColorScheme {
+    pub fn severity_fg(&self, severity: Severity) -> Color {
+        match severity {
+            Severity::Blocker => self.severity_blocker_fg,
+            Severity::Major => self.severity_major_fg,
+            Severity::Minor => self.severity_minor_fg,
+            Severity::Nit => self.severity_nit_fg,
+        }
+    }
 }
 
 impl Theme {
@@ -44,6 +68,15 @@ impl Theme {
                     g: 90,
                     b: 120,
                 },
+                resolved_bg: Color::Rgb { r: 30, g: 36, b: 42 },
+                resolved_selected_bg: Color::Rgb { r: 50, g: 58, b: 66 },
+                resolved_fg: Color::Rgb {
+                    r: 150,
+                    g: 150,
+                    b: 120,
+                },
+                range_bg: Color::Rgb { r: 24, g: 36, b: 50 },
+                range_selected_bg: Color::Rgb { r: 44, g: 60, b: 78 },
                 annotation_window_bg: Color::Rgb {
                     r: 50,
                     g: 70,
@@ -97,6 +130,22 @@ impl Theme {
                     b: 50,
                 },
                 diff_indicator_fg: Color::Black,
+                severity_blocker_fg: Color::Rgb { r: 255, g: 90, b: 90 },
+                severity_major_fg: Color::Rgb {
+                    r: 255,
+                    g: 170,
+                    b: 60,
+                },
+                severity_minor_fg: Color::Rgb {
+                    r: 220,
+                    g: 210,
+                    b: 90,
+                },
+                severity_nit_fg: Color::Rgb {
+                    r: 110,
+                    g: 170,
+                    b: 230,
+                },
             },
             Theme::Light => ColorScheme {
                 bg: Color::White,
@@ -116,6 +165,31 @@ impl Theme {
                     g: 200,
                     b: 230,
                 },
+                resolved_bg: Color::Rgb {
+                    r: 235,
+                    g: 240,
+                    b: 245,
+                },
+                resolved_selected_bg: Color::Rgb {
+                    r: 210,
+                    g: 215,
+                    b: 220,
+                },
+                resolved_fg: Color::Rgb {
+                    r: 110,
+                    g: 110,
+                    b: 140,
+                },
+                range_bg: Color::Rgb {
+                    r: 225,
+                    g: 235,
+                    b: 248,
+                },
+                range_selected_bg: Color::Rgb {
+                    r: 195,
+                    g: 212,
+                    b: 235,
+                },
                 annotation_window_bg: Color::Rgb {
                     r: 180,
                     g: 210,
@@ -177,6 +251,22 @@ impl Theme {
                     b: 60,
                 },
                 diff_indicator_fg: Color::Black,
+                severity_blocker_fg: Color::Rgb { r: 200, g: 0, b: 0 },
+                severity_major_fg: Color::Rgb {
+                    r: 200,
+                    g: 100,
+                    b: 0,
+                },
+                severity_minor_fg: Color::Rgb {
+                    r: 140,
+                    g: 120,
+                    b: 0,
+                },
+                severity_nit_fg: Color::Rgb {
+                    r: 40,
+                    g: 90,
+                    b: 180,
+                },
             },
         }
     }
@@ -249,4 +339,37 @@ mod tests {
         assert_ne!(colors.diff_added_selected_bg, colors.selected_bg);
         assert_ne!(colors.diff_removed_selected_bg, colors.selected_bg);
     }
+
+    #[test]
+    fn test_severity_colors_distinct() {
+        for theme in [Theme::Dark, Theme::Light] {
+            let colors = theme.colors();
+            let fgs: Vec<Color> = Severity::ALL.iter().map(|&s| colors.severity_fg(s)).collect();
+            for (i, fg) in fgs.iter().enumerate() {
+                assert_ne!(*fg, colors.line_number_fg);
+                assert!(!fgs[i + 1..].contains(fg));
+            }
+        }
+    }
+
+    #[test]
+    fn test_resolved_colors_are_dimmer_than_annotated() {
+        for theme in [Theme::Dark, Theme::Light] {
+            let colors = theme.colors();
+            assert_ne!(colors.resolved_bg, colors.annotated_bg);
+            assert_ne!(colors.resolved_bg, colors.bg);
+            assert_ne!(colors.resolved_selected_bg, colors.annotated_selected_bg);
+            assert_ne!(colors.resolved_fg, colors.annotation_window_fg);
+        }
+    }
+
+    #[test]
+    fn test_range_band_colors_distinct() {
+        for theme in [Theme::Dark, Theme::Light] {
+            let colors = theme.colors();
+            for band in [colors.range_bg, colors.range_selected_bg] {
+                assert!(![colors.bg, colors.selected_bg, colors.annotated_bg, colors.annotated_selected_bg].contains(&band));
+            }
+        }
+    }
 }
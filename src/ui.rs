@@ -1,5 +1,7 @@
+use crate::diff::LineChange;
 use crate::highlighting::{to_crossterm_color, SyntaxHighlighter};
-use crate::models::{EditorState, Line, ViewMode};
+use crate::models::{self, Annotation, BufferInfo, EditorState, Line, ViewMode};
+use crate::patch::Patch;
 use crate::text::{wrap_styled_text, wrap_text};
 use crate::theme::{ColorScheme, Theme};
 use crate::ui_diff::render_diff_mode;
@@ -29,10 +31,17 @@ pub fn render(
     search_matches: &[usize],
     current_match: Option<usize>,
     annotation_scroll: usize,
+    selected_entry: usize,
     highlighter: &SyntaxHighlighter,
     status_message: Option<&str>,
     lang_comment: &str,
     diff_available: bool,
+    buffers: &[BufferInfo],
+    active_buffer: usize,
+    diff_base: Option<&str>,
+    read_only: bool,
+    patch: Option<&Patch>,
+    syntax: Option<&str>,
 ) -> io::Result<()> {
     // Check if we're in diff view mode
     if let ViewMode::Diff { diff_result } = view_mode {
@@ -46,10 +55,16 @@ pub fn render(
             modified,
             theme,
             annotation_scroll,
+            selected_entry,
             highlighter,
             status_message,
             lang_comment,
             diff_available,
+            buffers,
+            active_buffer,
+            diff_base,
+            read_only,
+            syntax,
         );
     }
     let (width, height) = terminal::size()?;
@@ -62,6 +77,7 @@ pub fn render(
 
     let gutter_width = lines.len().to_string().len() + 2; // + padding
     let content_width = (width as usize).saturating_sub(gutter_width);
+    let bands = range_bands(lines, cursor_line, editor_state);
 
     let mut screen_line = 0;
     let mut line_idx = scroll_offset;
@@ -70,27 +86,29 @@ pub fn render(
     while screen_line < content_height && line_idx < lines.len() {
         let line = &lines[line_idx];
         let is_selected = line_idx == cursor_line;
-        let has_annotation = line.annotation.is_some();
-
-        let bg_color = if is_selected {
-            if has_annotation {
-                colors.annotated_selected_bg
-            } else {
-                colors.selected_bg
-            }
-        } else if has_annotation {
-            colors.annotated_bg
-        } else {
-            colors.bg
+        let has_annotation = !line.annotations.is_empty();
+        // Patch hunks keep the diff view's added/removed colours
+        let change = patch.and_then(|p| p.line_change(line_idx));
+
+        let bg_color = match (is_selected, has_annotation, change) {
+            (true, true, _) if line.is_resolved() => colors.resolved_selected_bg,
+            (false, true, _) if line.is_resolved() => colors.resolved_bg,
+            (true, true, _) => colors.annotated_selected_bg,
+            (false, true, _) => colors.annotated_bg,
+            (true, false, _) if bands[line_idx] => colors.range_selected_bg,
+            (false, false, _) if bands[line_idx] => colors.range_bg,
+            (true, false, Some(LineChange::Added)) => colors.diff_added_selected_bg,
+            (false, false, Some(LineChange::Added)) => colors.diff_added_bg,
+            (true, false, Some(LineChange::Removed)) => colors.diff_removed_selected_bg,
+            (false, false, Some(LineChange::Removed)) => colors.diff_removed_bg,
+            (true, false, _) => colors.selected_bg,
+            (false, false, _) => colors.bg,
         };
 
-        // Determine extension
-        let extension = file_path.as_deref()
-            .map(|p| Path::new(p).extension().and_then(|e| e.to_str()).unwrap_or("txt"))
-            .unwrap_or("txt");
+        let line_number_fg = line.severity().map_or(colors.line_number_fg, |s| colors.severity_fg(s));
 
         // Highlight
-        let styled_spans = highlighter.highlight(&line.content, extension);
+        let styled_spans = highlighter.highlight(&line.content, syntax);
         
         // Wrap styled
         let wrapped_styled = wrap_styled_text(&styled_spans, content_width);
@@ -129,7 +147,7 @@ pub fn render(
                 stdout,
                 MoveTo(0, screen_line as u16),
                 SetBackgroundColor(colors.bg),
-                SetForegroundColor(colors.line_number_fg),
+                SetForegroundColor(line_number_fg),
                 Print(line_num_str),
                 SetBackgroundColor(bg_color),
             )?;
@@ -198,6 +216,7 @@ pub fn render(
         cursor_line,
         editor_state,
         annotation_scroll,
+        selected_entry,
         &colors,
         width,
         annotation_start,
@@ -219,6 +238,10 @@ pub fn render(
         height,
         status_message,
         diff_available,
+        buffers,
+        active_buffer,
+        diff_base,
+        read_only,
     )?;
 
     // Show help overlay if in ShowingHelp state
@@ -226,8 +249,12 @@ pub fn render(
         render_help_overlay(&mut stdout, &colors, width, height)?;
     }
 
+    if let EditorState::BufferPicker { selected } = editor_state {
+        render_buffer_picker(&mut stdout, &colors, width, height, buffers, *selected, active_buffer)?;
+    }
+
     // Position and show cursor if in annotation edit state
-    if let EditorState::Annotating { buffer, cursor_pos } = editor_state {
+    if let EditorState::Annotating { buffer, cursor_pos, .. } = editor_state {
         position_cursor(
             &mut stdout,
             buffer,
@@ -244,56 +271,38 @@ pub fn render(
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 fn render_annotation_area(
     stdout: &mut impl Write,
     lines: &[Line],
     cursor_line: usize,
     editor_state: &EditorState,
     annotation_scroll: usize,
+    selected_entry: usize,
     colors: &ColorScheme,
     width: u16,
     annotation_start: u16,
 ) -> io::Result<()> {
+    let max_annotation_width = width as usize - 4;
+    let thread = lines.get(cursor_line).map_or(&[][..], |line| &line.annotations[..]);
+    let (wrapped_annotation, first_row, label) =
+        annotation_window(thread, editor_state, selected_entry, annotation_scroll, max_annotation_width);
+
     // Top border of annotation area
     queue!(
         stdout,
         MoveTo(0, annotation_start),
         SetBackgroundColor(colors.annotation_window_bg),
         SetForegroundColor(colors.annotation_window_fg),
-        Print(format!("╔{}╗", "═".repeat(width as usize - 2))),
+        Print(annotation_top_border(width, &label)),
         ResetColor
     )?;
 
-    // Get annotation content based on editor state
-    let annotation_text = match editor_state {
-        EditorState::Annotating { buffer, .. } => {
-            if buffer.is_empty() {
-                "[Type annotation here...]".to_string()
-            } else {
-                buffer.clone()
-            }
-        }
-        _ => {
-            if let Some(ref annotation) = lines[cursor_line].annotation {
-                annotation.clone()
-            } else {
-                "[No annotation - Press Enter to add]".to_string()
-            }
-        }
-    };
-
-    // Wrap annotation text
-    let max_annotation_width = width as usize - 4;
-    let wrapped_annotation = wrap_text(&annotation_text, max_annotation_width);
-
     // Display 2 lines of wrapped annotation with scroll support
     for i in 0..2 {
-        let line_idx = annotation_scroll + i;
-        let display_line = if line_idx < wrapped_annotation.len() {
-            wrapped_annotation[line_idx].clone()
-        } else {
-            String::new()
-        };
+        let line_idx = first_row + i;
+        let (display_line, resolved) = wrapped_annotation.get(line_idx).cloned().unwrap_or_default();
+        let text_fg = if resolved { colors.resolved_fg } else { colors.annotation_window_fg };
 
         let y_pos = annotation_start + 1 + i as u16;
         
@@ -305,7 +314,11 @@ fn render_annotation_area(
             MoveTo(0, y_pos),
             SetBackgroundColor(colors.annotation_window_bg),
             SetForegroundColor(colors.annotation_window_fg),
-            Print(format!("║ {}{}║", display_line, " ".repeat(padding))),
+            Print("║ "),
+            SetForegroundColor(text_fg),
+            Print(format!("{}{}", display_line, " ".repeat(padding))),
+            SetForegroundColor(colors.annotation_window_fg),
+            Print("║"),
             ResetColor
         )?;
     }
@@ -339,6 +352,10 @@ fn render_status_bar(
     height: u16,
     status_message: Option<&str>,
     diff_available: bool,
+    buffers: &[BufferInfo],
+    active_buffer: usize,
+    diff_base: Option<&str>,
+    read_only: bool,
 ) -> io::Result<()> {
     queue!(stdout, MoveTo(0, height - 1))?;
 
@@ -362,17 +379,22 @@ fn render_status_bar(
                 .as_deref()
                 .map(|p| Path::new(p).file_name().and_then(|n| n.to_str()).unwrap_or(p))
                 .unwrap_or("[No Name]");
-            let modified_flag = if modified { " [Modified]" } else { "" };
+            let modified_flag = file_flags(modified, read_only);
             let view_indicator = if matches!(view_mode, ViewMode::Diff { .. }) {
-                "DIFF | "
+                format!("{} | ", diff_label(diff_base))
             } else {
-                ""
+                String::new()
             };
 
             // Build the left part: filename and line info
             let left_part = format!(
-                " {}{}{} | Line {}/{}",
-                view_indicator, filename, modified_flag, cursor_line + 1, total_lines
+                " {}{}{}{} | Line {}/{}",
+                view_indicator,
+                buffer_position(buffers, active_buffer),
+                filename,
+                modified_flag,
+                cursor_line + 1,
+                total_lines
             );
 
             // Render left part with normal status colors
@@ -384,21 +406,24 @@ fn render_status_bar(
             )?;
 
             // If diff is available, show the orange indicator with a space before it
+            let diff_indicator = match diff_base {
+                Some(base) => format!(" ^D Diff vs {} ", base),
+                None => " ^D Diff ".to_string(),
+            };
             if diff_available {
-                let diff_indicator = " ^D Diff ";
                 queue!(
                     stdout,
                     SetBackgroundColor(colors.status_bg),
                     Print(" "),
                     SetBackgroundColor(colors.diff_indicator_bg),
                     SetForegroundColor(colors.diff_indicator_fg),
-                    Print(diff_indicator),
+                    Print(&diff_indicator),
                 )?;
             }
 
             // Continue with the rest of the shortcuts
-            let shortcuts = " ^G Help  ^X Exit  ^O Save  ^W Search  ^T Theme  Del/Bksp Del  ^N/^P Jump";
-            let current_len = left_part.len() + if diff_available { 10 } else { 0 }; // " " + " ^D Diff "
+            let shortcuts = " ^G Help  ^X Exit  ^O Save  ^W Search  ^T Theme  Del/Bksp Del  ^N/^P Jump  ^B Files";
+            let current_len = left_part.len() + if diff_available { diff_indicator.len() + 1 } else { 0 };
             let remaining_width = (width as usize).saturating_sub(current_len + 1);
             // Use truncate_to_width for proper handling of wide characters
             use crate::text::truncate_to_width;
@@ -424,7 +449,7 @@ fn render_status_bar(
                 stdout,
                 SetBackgroundColor(colors.status_bg),
                 SetForegroundColor(colors.status_fg),
-                Print(format!(" {:width$}", "Enter: Save  Esc: Cancel  ←→: Move cursor  ↑↓: Navigate lines", width = width as usize - 2)),
+                Print(format!(" {:width$}", "Enter: Save  M-Enter: New line  Esc: Cancel  ←→: Move cursor  ↑↓: Navigate lines", width = width as usize - 2)),
                 ResetColor
             )?;
         }
@@ -448,7 +473,25 @@ fn render_status_bar(
                 stdout,
                 SetBackgroundColor(colors.status_bg),
                 SetForegroundColor(colors.status_fg),
-                Print(format!(" {:width$}", "Unsaved changes! Save before exiting? (y/n/Esc)", width = width as usize - 2)),
+                Print(format!(" {:width$}", quit_prompt_text(buffers), width = width as usize - 2)),
+                ResetColor
+            )?;
+        }
+        EditorState::BufferPicker { .. } => {
+            queue!(
+                stdout,
+                SetBackgroundColor(colors.status_bg),
+                SetForegroundColor(colors.status_fg),
+                Print(format!(" {:width$}", BUFFER_PICKER_HINT, width = width as usize - 2)),
+                ResetColor
+            )?;
+        }
+        EditorState::ExportPrompt => {
+            queue!(
+                stdout,
+                SetBackgroundColor(colors.status_bg),
+                SetForegroundColor(colors.status_fg),
+                Print(format!(" {:width$}", EXPORT_PROMPT_TEXT, width = width as usize - 2)),
                 ResetColor
             )?;
         }
@@ -466,6 +509,236 @@ fn render_status_bar(
     Ok(())
 }
 
+/// Status bar hint shown while the buffer picker is open.
+pub const BUFFER_PICKER_HINT: &str = "Enter: Switch  Esc: Cancel  ↑↓: Select  1-9: Jump";
+
+/// Status bar text shown while choosing an export format.
+pub const EXPORT_PROMPT_TEXT: &str = "Export annotations: (J)SON  (M)arkdown  (S)ARIF  Esc: Cancel";
+
+/// Diff view label, naming the base revision when reviewing against one.
+pub fn diff_label(diff_base: Option<&str>) -> String {
+    match diff_base {
+        Some(base) => format!("DIFF vs {}", base),
+        None => "DIFF".to_string(),
+    }
+}
+
+/// Status bar flags after the file name: unsaved changes or read-only.
+pub fn file_flags(modified: bool, read_only: bool) -> &'static str {
+    match (modified, read_only) {
+        (true, _) => " [Modified]",
+        (false, true) => " [RO]",
+        (false, false) => "",
+    }
+}
+
+/// Position of the active buffer, e.g. "[2/5] ". Empty when only one file is open.
+pub fn buffer_position(buffers: &[BufferInfo], active_buffer: usize) -> String {
+    if buffers.len() > 1 {
+        format!("[{}/{}] ", active_buffer + 1, buffers.len())
+    } else {
+        String::new()
+    }
+}
+
+/// Quit prompt text, naming how many buffers have unsaved changes.
+pub fn quit_prompt_text(buffers: &[BufferInfo]) -> String {
+    let unsaved = buffers.iter().filter(|b| b.modified).count();
+    if unsaved > 1 {
+        format!("{} buffers have unsaved changes! Save all before exiting? (y/n/Esc)", unsaved)
+    } else {
+        "Unsaved changes! Save before exiting? (y/n/Esc)".to_string()
+    }
+}
+
+/// Rows of the annotation window, the first row to show and a label for
+/// its top border. While editing, the rows are the edit buffer; otherwise
+/// they list the cursor line's thread, marking the selected entry when
+/// there is more than one and scrolling it into view.
+/// Rows come with whether they belong to a resolved entry, which is drawn
+/// dimmed. Shared by the normal and diff views.
+pub fn annotation_window(
+    thread: &[Annotation],
+    editor_state: &EditorState,
+    selected_entry: usize,
+    annotation_scroll: usize,
+    width: usize,
+) -> (Vec<(String, bool)>, usize, String) {
+    if let EditorState::Annotating {
+        buffer,
+        entry,
+        severity,
+        span,
+        ..
+    } = editor_state
+    {
+        let text = if buffer.is_empty() { "[Type annotation here...]" } else { buffer.as_str() };
+        let position = match (*entry, thread.len()) {
+            (entry, len) if entry >= len && len > 0 => "Reply · ".to_string(),
+            (entry, len) if len > 1 => format!("{}/{} · ", entry + 1, len),
+            _ => String::new(),
+        };
+        let severity = severity.map_or("none", |s| s.as_str());
+        let lines = if *span == 1 { "1 line".to_string() } else { format!("{} lines", span) };
+        let label = format!(" {}Severity: {} (Tab) · Covers {} (^↓/^↑) ", position, severity, lines);
+        return (plain_rows(wrap_text(text, width), false), annotation_scroll, label);
+    }
+
+    match thread {
+        [] => (plain_rows(wrap_text("[No annotation - Press Enter to add]", width), false), 0, String::new()),
+        [annotation] => (
+            plain_rows(wrap_text(&entry_text(annotation), width), annotation.is_resolved()),
+            annotation_scroll,
+            String::new(),
+        ),
+        _ => {
+            let selected = selected_entry.min(thread.len() - 1);
+            let mut rows = Vec::new();
+            let mut first_row = 0;
+            for (i, annotation) in thread.iter().enumerate() {
+                if i == selected {
+                    first_row = rows.len();
+                }
+                let marker = if i == selected { "▸ " } else { "  " };
+                for (j, row) in wrap_text(&entry_text(annotation), width.saturating_sub(2)).into_iter().enumerate() {
+                    let row = format!("{}{}", if j == 0 { marker } else { "  " }, row);
+                    rows.push((row, annotation.is_resolved()));
+                }
+            }
+            (rows, first_row, format!(" {}/{} ", selected + 1, thread.len()))
+        }
+    }
+}
+
+/// Which lines sit inside a range annotation's band. While an entry is
+/// being edited, the cursor line's band follows the range being picked.
+/// Shared by the normal and diff views.
+pub fn range_bands(lines: &[Line], cursor_line: usize, editor_state: &EditorState) -> Vec<bool> {
+    let editing = match editor_state {
+        EditorState::Annotating { entry, span, .. } => Some((*entry, *span)),
+        _ => None,
+    };
+    models::range_bands(lines, |i, line| match editing {
+        Some((entry, span)) if i == cursor_line => line
+            .annotations
+            .iter()
+            .enumerate()
+            .filter(|(j, a)| *j != entry && !a.is_resolved())
+            .map(|(_, a)| a.span())
+            .chain([span])
+            .max()
+            .unwrap_or(1),
+        _ => models::open_span(line),
+    })
+}
+
+fn plain_rows(rows: Vec<String>, resolved: bool) -> Vec<(String, bool)> {
+    rows.into_iter().map(|row| (row, resolved)).collect()
+}
+
+/// Text of one thread entry as shown in the annotation window, led by its
+/// severity, author, date, range and status when it has any,
+/// e.g. `[major · Jane · 2026-10-16 · 12 lines · resolved] text`.
+fn entry_text(annotation: &Annotation) -> String {
+    let span = annotation.span();
+    let details: Vec<String> = [
+        annotation.severity().map(|s| s.as_str().to_string()),
+        annotation.author().map(str::to_string),
+        annotation.date().map(str::to_string),
+        (span > 1).then(|| format!("{} lines", span)),
+        annotation.is_resolved().then(|| models::RESOLVED.to_string()),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if details.is_empty() {
+        annotation.text.clone()
+    } else {
+        format!("[{}] {}", details.join(" · "), annotation.text)
+    }
+}
+
+/// Top border of the annotation window, with `label` near its left end.
+pub fn annotation_top_border(width: u16, label: &str) -> String {
+    let inner = (width as usize).saturating_sub(2);
+    if label.is_empty() || label.width() + 1 > inner {
+        return format!("╔{}╗", "═".repeat(inner));
+    }
+    format!("╔═{}{}╗", label, "═".repeat(inner - 1 - label.width()))
+}
+
+/// Renders the buffer picker overlay listing every open file.
+/// Shared by the normal and diff views.
+pub fn render_buffer_picker(
+    stdout: &mut impl Write,
+    colors: &ColorScheme,
+    width: u16,
+    height: u16,
+    buffers: &[BufferInfo],
+    selected: usize,
+    active_buffer: usize,
+) -> io::Result<()> {
+    use crate::text::truncate_to_width;
+
+    let box_width = 60.min(width);
+    // Leave room for the annotation area and status bar below
+    let max_rows = height.saturating_sub(9).max(1) as usize;
+    let visible = buffers.len().min(max_rows);
+    let box_height = visible as u16 + 4;
+    let start_x = (width.saturating_sub(box_width)) / 2;
+    let start_y = (height.saturating_sub(5).saturating_sub(box_height)) / 2;
+    let inner_width = box_width as usize - 2;
+
+    // Keep the selection visible when there are more buffers than rows
+    let first = selected.saturating_sub(visible.saturating_sub(1));
+
+    queue!(
+        stdout,
+        SetBackgroundColor(colors.annotation_window_bg),
+        SetForegroundColor(colors.annotation_window_fg),
+        MoveTo(start_x, start_y),
+        Print(format!("╔{}╗", "═".repeat(inner_width))),
+        MoveTo(start_x, start_y + 1),
+        Print(format!("║{:width$}║", " OPEN FILES ", width = inner_width)),
+    )?;
+
+    for row in 0..visible {
+        let index = first + row;
+        let info = &buffers[index];
+        let marker = if index == active_buffer { '*' } else { ' ' };
+        let modified = if info.modified { " [Modified]" } else { "" };
+        let entry = format!(" {}{:>2} {}{}", marker, index + 1, info.name, modified);
+        let entry = truncate_to_width(&entry, inner_width);
+        let padding = inner_width.saturating_sub(entry.width());
+        let bg = if index == selected {
+            colors.annotated_selected_bg
+        } else {
+            colors.annotation_window_bg
+        };
+        queue!(
+            stdout,
+            MoveTo(start_x, start_y + 2 + row as u16),
+            SetBackgroundColor(colors.annotation_window_bg),
+            Print("║"),
+            SetBackgroundColor(bg),
+            Print(format!("{}{}", entry, " ".repeat(padding))),
+            SetBackgroundColor(colors.annotation_window_bg),
+            Print("║"),
+        )?;
+    }
+
+    queue!(
+        stdout,
+        MoveTo(start_x, start_y + 2 + visible as u16),
+        Print(format!("║{:width$}║", "", width = inner_width)),
+        MoveTo(start_x, start_y + 3 + visible as u16),
+        Print(format!("╚{}╝", "═".repeat(inner_width))),
+        ResetColor
+    )?;
+
+    Ok(())
+}
+
 fn position_cursor(
     stdout: &mut impl Write,
     buffer: &str,
@@ -539,9 +812,44 @@ fn render_help_overlay(
     width: u16,
     height: u16,
 ) -> io::Result<()> {
+    let commands = [
+        " HELP MENU ",
+        "",
+        " ^N / ^P    Next / Prev Annotation",
+        " M-N / M-P  Next / Prev Open Annotation",
+        " Del/Bksp   Delete Annotation",
+        " Enter      Add / Edit Annotation",
+        " ^R         Reply to Annotation",
+        " Tab        Next Entry in Thread",
+        " ^K         Resolve / Reopen Entry",
+        " M-K        Remove Resolved Entries",
+        " M-Enter    New Line in Annotation",
+        " ^↓ / ^↑    Extend / Shrink Range",
+        " ^W         Search",
+        " ^D         Toggle Diff View",
+        " ^T         Toggle Theme",
+        " ^O         Save File",
+        " ^X         Exit",
+        " ^G         Toggle Help",
+        " ^B         Open Files",
+        " M-. / M-,  Next / Prev File",
+        " ^E         Export Annotations",
+        "",
+        " Arrow Keys Navigation",
+        " PgUp/PgDn  Page Navigation",
+        "",
+        " Hotkeys work in EN/RU layouts",
+        " Press Any Key to Close",
+    ];
+
+    // Size the box to the entries, clamped to the terminal
+    let box_width = 50.min(width);
+    let box_height = (commands.len() as u16 + 2).min(height);
+    if box_width < 2 || box_height < 2 {
+        return Ok(());
+    }
+
     // Center the box
-    let box_width = 50;
-    let box_height = 20; // Increased for multi-layout note
     let start_x = (width.saturating_sub(box_width)) / 2;
     let start_y = (height.saturating_sub(box_height)) / 2;
 
@@ -576,29 +884,14 @@ fn render_help_overlay(
         )?;
     }
 
-    // Content
-    let commands = [
-        " HELP MENU ",
-        "",
-        " ^N / ^P    Next / Prev Annotation",
-        " Del/Bksp   Delete Annotation",
-        " Enter      Add / Edit Annotation",
-        " ^W         Search",
-        " ^D         Toggle Diff View",
-        " ^T         Toggle Theme",
-        " ^O         Save File",
-        " ^X         Exit",
-        " ^G         Toggle Help",
-        "",
-        " Arrow Keys Navigation",
-        " PgUp/PgDn  Page Navigation",
-        "",
-        " Hotkeys work in EN/RU layouts",
-        " Press Any Key to Close",
-    ];
-
-    for (i, cmd) in commands.iter().enumerate() {
-        if i as u16 >= box_height - 2 { break; }
+    // Content; on a short terminal the last line (how to close) stays
+    let rows = (box_height - 2) as usize;
+    let shown = commands
+        .iter()
+        .take(rows.saturating_sub(1))
+        .chain(commands.last().filter(|_| rows > 0))
+        .take(rows);
+    for (i, cmd) in shown.enumerate() {
         queue!(
             stdout,
             MoveTo(start_x + 2, start_y + 1 + i as u16),
@@ -2,9 +2,13 @@
 
 use crate::diff::{ChangeType, DiffResult, LineChange, WordChange};
 use crate::highlighting::{to_crossterm_color, SyntaxHighlighter};
-use crate::models::{EditorState, Line};
+use crate::models::{BufferInfo, EditorState, Line};
 use crate::text::wrap_text;
 use crate::theme::{ColorScheme, Theme};
+use crate::ui::{
+    annotation_top_border, annotation_window, buffer_position, diff_label, file_flags, quit_prompt_text, range_bands, render_buffer_picker, BUFFER_PICKER_HINT,
+    EXPORT_PROMPT_TEXT,
+};
 use crossterm::{
     cursor::{Hide, MoveTo, Show},
     queue,
@@ -28,10 +32,16 @@ pub fn render_diff_mode(
     modified: bool,
     theme: Theme,
     annotation_scroll: usize,
+    selected_entry: usize,
     highlighter: &SyntaxHighlighter,
     status_message: Option<&str>,
     _lang_comment: &str,
     diff_available: bool,
+    buffers: &[BufferInfo],
+    active_buffer: usize,
+    diff_base: Option<&str>,
+    read_only: bool,
+    syntax: Option<&str>,
 ) -> io::Result<()> {
     let (width, height) = terminal::size()?;
     let content_height = (height.saturating_sub(5)) as usize;
@@ -61,17 +71,8 @@ pub fn render_diff_mode(
     let left_content_width = left_pane_width.saturating_sub(left_gutter_width);
     let right_content_width = right_pane_width.saturating_sub(right_gutter_width);
 
-    // Determine extension for syntax highlighting
-    let extension = file_path
-        .as_deref()
-        .map(|p| {
-            Path::new(p)
-                .extension()
-                .and_then(|e| e.to_str())
-                .unwrap_or("txt")
-        })
-        .unwrap_or("txt");
 
+    let bands = range_bands(lines, cursor_line, editor_state);
     let mut screen_line = 0;
     let mut diff_line_idx = scroll_offset;
 
@@ -97,9 +98,10 @@ pub fn render_diff_mode(
             true, // is_left_pane
             &colors,
             highlighter,
-            extension,
+            syntax,
             screen_line as u16,
             lines,
+            &bands,
         )?;
 
         // Render separator
@@ -123,9 +125,10 @@ pub fn render_diff_mode(
             false, // is_left_pane
             &colors,
             highlighter,
-            extension,
+            syntax,
             screen_line as u16,
             lines,
+            &bands,
         )?;
 
         screen_line += 1;
@@ -171,6 +174,7 @@ pub fn render_diff_mode(
         cursor_line,
         editor_state,
         annotation_scroll,
+        selected_entry,
         &colors,
         width,
         annotation_start,
@@ -189,6 +193,10 @@ pub fn render_diff_mode(
         height,
         status_message,
         diff_available,
+        buffers,
+        active_buffer,
+        diff_base,
+        read_only,
     )?;
 
     // Render help overlay if showing help
@@ -196,8 +204,12 @@ pub fn render_diff_mode(
         render_diff_help_overlay(&mut stdout, &colors, width, height)?;
     }
 
+    if let EditorState::BufferPicker { selected } = editor_state {
+        render_buffer_picker(&mut stdout, &colors, width, height, buffers, *selected, active_buffer)?;
+    }
+
     // Position and show cursor if in annotation edit state
-    if let EditorState::Annotating { buffer, cursor_pos } = editor_state {
+    if let EditorState::Annotating { buffer, cursor_pos, .. } = editor_state {
         position_diff_cursor(
             &mut stdout,
             buffer,
@@ -226,9 +238,10 @@ fn render_diff_pane_line(
     is_left_pane: bool,
     colors: &ColorScheme,
     highlighter: &SyntaxHighlighter,
-    extension: &str,
+    syntax: Option<&str>,
     y: u16,
     lines: &[Line],
+    bands: &[bool],
 ) -> io::Result<()> {
     queue!(stdout, MoveTo(start_x, y))?;
 
@@ -238,28 +251,34 @@ fn render_diff_pane_line(
             let (line_bg, word_added_bg, word_removed_bg) = get_diff_colors(change, is_cursor_line, is_left_pane, colors);
 
             // Render gutter
+            // Check if this line has an annotation (only show on left pane)
+            let annotated_line = (is_left_pane && *line_num > 0 && *line_num <= lines.len())
+                .then(|| &lines[*line_num - 1])
+                .filter(|line| !line.annotations.is_empty());
+            let has_annotation = annotated_line.is_some();
+            let line_number_fg = annotated_line
+                .and_then(Line::severity)
+                .map_or(colors.line_number_fg, |s| colors.severity_fg(s));
+
             let line_num_str = format!("{:>width$} ", line_num, width = gutter_width - 1);
             queue!(
                 stdout,
                 SetBackgroundColor(colors.bg),
-                SetForegroundColor(colors.line_number_fg),
+                SetForegroundColor(line_number_fg),
                 Print(&line_num_str),
             )?;
 
-            // Check if this line has an annotation (only show on left pane)
-            let has_annotation = if is_left_pane && *line_num > 0 && *line_num <= lines.len() {
-                lines[*line_num - 1].annotation.is_some()
-            } else {
-                false
-            };
-
-            // Adjust background for annotated lines
-            let line_bg = if has_annotation && is_cursor_line {
-                colors.annotated_selected_bg
-            } else if has_annotation {
-                colors.annotated_bg
-            } else {
-                line_bg
+            // Adjust background for annotated lines, dimmed once fully resolved
+            let resolved = annotated_line.is_some_and(Line::is_resolved);
+            let in_band = is_left_pane && bands.get(line_num.wrapping_sub(1)).copied().unwrap_or(false);
+            let line_bg = match (has_annotation, is_cursor_line, resolved) {
+                (true, true, true) => colors.resolved_selected_bg,
+                (true, false, true) => colors.resolved_bg,
+                (true, true, false) => colors.annotated_selected_bg,
+                (true, false, false) => colors.annotated_bg,
+                (false, true, _) if in_band => colors.range_selected_bg,
+                (false, false, _) if in_band => colors.range_bg,
+                (false, _, _) => line_bg,
             };
 
             queue!(stdout, SetBackgroundColor(line_bg))?;
@@ -272,7 +291,7 @@ fn render_diff_pane_line(
                 }
                 _ => {
                     // Simple highlight for added/removed/unchanged lines
-                    let styled_spans = highlighter.highlight(content, extension);
+                    let styled_spans = highlighter.highlight(content, syntax);
                     let mut current_width = 0;
                     for (style, text) in styled_spans {
                         if current_width >= content_width {
@@ -373,6 +392,7 @@ fn get_diff_colors(
 }
 
 /// Render word-level diff with highlighting.
+#[allow(clippy::too_many_arguments)]
 fn render_word_diff(
     stdout: &mut impl Write,
     words: &[WordChange],
@@ -462,59 +482,38 @@ fn render_word_diff(
 }
 
 /// Render annotation area in diff mode.
+#[allow(clippy::too_many_arguments)]
 fn render_diff_annotation_area(
     stdout: &mut impl Write,
     lines: &[Line],
     cursor_line: usize,
     editor_state: &EditorState,
     annotation_scroll: usize,
+    selected_entry: usize,
     colors: &ColorScheme,
     width: u16,
     annotation_start: u16,
 ) -> io::Result<()> {
+    let max_annotation_width = width as usize - 4;
+    let thread = lines.get(cursor_line).map_or(&[][..], |line| &line.annotations[..]);
+    let (wrapped_annotation, first_row, label) =
+        annotation_window(thread, editor_state, selected_entry, annotation_scroll, max_annotation_width);
+
     // Top border
     queue!(
         stdout,
         MoveTo(0, annotation_start),
         SetBackgroundColor(colors.annotation_window_bg),
         SetForegroundColor(colors.annotation_window_fg),
-        Print(format!("╔{}╗", "═".repeat(width as usize - 2))),
+        Print(annotation_top_border(width, &label)),
         ResetColor
     )?;
 
-    // Get annotation text based on editor state
-    let annotation_text = match editor_state {
-        EditorState::Annotating { buffer, .. } => {
-            if buffer.is_empty() {
-                "[Type annotation here...]".to_string()
-            } else {
-                buffer.clone()
-            }
-        }
-        _ => {
-            if cursor_line < lines.len() {
-                lines[cursor_line]
-                    .annotation
-                    .clone()
-                    .unwrap_or_else(|| "[No annotation - Press Enter to add]".to_string())
-            } else {
-                "[No annotation - Press Enter to add]".to_string()
-            }
-        }
-    };
-
-    // Wrap annotation text
-    let max_annotation_width = width as usize - 4;
-    let wrapped_annotation = wrap_text(&annotation_text, max_annotation_width);
-
     // Display 2 lines of wrapped annotation with scroll support
     for i in 0..2 {
-        let line_idx = annotation_scroll + i;
-        let display_line = if line_idx < wrapped_annotation.len() {
-            wrapped_annotation[line_idx].clone()
-        } else {
-            String::new()
-        };
+        let line_idx = first_row + i;
+        let (display_line, resolved) = wrapped_annotation.get(line_idx).cloned().unwrap_or_default();
+        let text_fg = if resolved { colors.resolved_fg } else { colors.annotation_window_fg };
 
         let y_pos = annotation_start + 1 + i as u16;
 
@@ -523,11 +522,11 @@ fn render_diff_annotation_area(
             MoveTo(0, y_pos),
             SetBackgroundColor(colors.annotation_window_bg),
             SetForegroundColor(colors.annotation_window_fg),
-            Print(format!(
-                "║ {:width$} ║",
-                display_line,
-                width = max_annotation_width
-            )),
+            Print("║ "),
+            SetForegroundColor(text_fg),
+            Print(format!("{:width$}", display_line, width = max_annotation_width)),
+            SetForegroundColor(colors.annotation_window_fg),
+            Print(" ║"),
             ResetColor
         )?;
     }
@@ -559,6 +558,10 @@ fn render_diff_status_bar(
     height: u16,
     status_message: Option<&str>,
     diff_available: bool,
+    buffers: &[BufferInfo],
+    active_buffer: usize,
+    diff_base: Option<&str>,
+    read_only: bool,
 ) -> io::Result<()> {
     queue!(stdout, MoveTo(0, height - 1))?;
 
@@ -582,12 +585,17 @@ fn render_diff_status_bar(
                 .as_deref()
                 .map(|p| Path::new(p).file_name().and_then(|n| n.to_str()).unwrap_or(p))
                 .unwrap_or("[No Name]");
-            let modified_flag = if modified { " [Modified]" } else { "" };
+            let modified_flag = file_flags(modified, read_only);
 
             // Build the left part: DIFF indicator, filename and line info
             let left_part = format!(
-                " DIFF | {}{} | Line {}/{}",
-                filename, modified_flag, cursor_line + 1, total_lines
+                " {} | {}{}{} | Line {}/{}",
+                diff_label(diff_base),
+                buffer_position(buffers, active_buffer),
+                filename,
+                modified_flag,
+                cursor_line + 1,
+                total_lines
             );
 
             // Render left part with normal status colors
@@ -612,7 +620,7 @@ fn render_diff_status_bar(
             }
 
             // Continue with the rest of the shortcuts
-            let shortcuts = " ^G Help  ^X Exit  ^O Save  ^W Search  ^T Theme  Del/Bksp Del  ^N/^P Jump";
+            let shortcuts = " ^G Help  ^X Exit  ^O Save  ^W Search  ^T Theme  Del/Bksp Del  ^N/^P Jump  ^B Files";
             let current_len = left_part.len() + if diff_available { 15 } else { 0 }; // " " + " ^D Exit Diff "
             let remaining_width = (width as usize).saturating_sub(current_len + 1);
             let shortcuts_truncated: String = shortcuts.chars().take(remaining_width).collect();
@@ -637,7 +645,7 @@ fn render_diff_status_bar(
                 stdout,
                 SetBackgroundColor(colors.status_bg),
                 SetForegroundColor(colors.status_fg),
-                Print(format!(" {:width$}", "Enter: Save  Esc: Cancel  ←→: Move cursor  ↑↓: Navigate lines", width = width as usize - 2)),
+                Print(format!(" {:width$}", "Enter: Save  M-Enter: New line  Esc: Cancel  ←→: Move cursor  ↑↓: Navigate lines", width = width as usize - 2)),
                 ResetColor
             )?;
         }
@@ -656,7 +664,25 @@ fn render_diff_status_bar(
                 stdout,
                 SetBackgroundColor(colors.status_bg),
                 SetForegroundColor(colors.status_fg),
-                Print(format!(" {:width$}", "Unsaved changes! Save before exiting? (y/n/Esc)", width = width as usize - 2)),
+                Print(format!(" {:width$}", quit_prompt_text(buffers), width = width as usize - 2)),
+                ResetColor
+            )?;
+        }
+        EditorState::BufferPicker { .. } => {
+            queue!(
+                stdout,
+                SetBackgroundColor(colors.status_bg),
+                SetForegroundColor(colors.status_fg),
+                Print(format!(" {:width$}", BUFFER_PICKER_HINT, width = width as usize - 2)),
+                ResetColor
+            )?;
+        }
+        EditorState::ExportPrompt => {
+            queue!(
+                stdout,
+                SetBackgroundColor(colors.status_bg),
+                SetForegroundColor(colors.status_fg),
+                Print(format!(" {:width$}", EXPORT_PROMPT_TEXT, width = width as usize - 2)),
                 ResetColor
             )?;
         }
@@ -755,7 +781,7 @@ fn render_diff_help_overlay(
 ) -> io::Result<()> {
     // Center the box
     let box_width = 50;
-    let box_height = 18;
+    let box_height = 26;
     let start_x = (width.saturating_sub(box_width)) / 2;
     let start_y = (height.saturating_sub(box_height)) / 2;
 
@@ -797,14 +823,22 @@ fn render_diff_help_overlay(
         " HELP MENU (DIFF MODE) ",
         "",
         " ^N / ^P    Next / Prev Annotation",
+        " M-N / M-P  Next / Prev Open Annotation",
         " Del/Bksp   Delete Annotation",
         " Enter      Add / Edit Annotation",
+        " ^R         Reply to Annotation",
+        " Tab        Next Entry in Thread",
+        " ^K         Resolve / Reopen Entry",
+        " M-K        Remove Resolved Entries",
         " ^W         Search",
         " ^D / Esc   Exit Diff View",
         " ^T         Toggle Theme",
         " ^O         Save File",
         " ^X         Exit",
         " ^G         Toggle Help",
+        " ^B         Open Files",
+        " M-. / M-,  Next / Prev File",
+        " ^E         Export Annotations",
         "",
         " Arrow Keys Navigation",
         " PgUp/PgDn  Page Navigation",